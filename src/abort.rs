@@ -0,0 +1,283 @@
+use rusty_v8 as v8;
+use std::ffi::c_void;
+
+use crate::helper::{define_class, is_instance, new_instance, require_new, retrieve_tx, set_function, throw_type_error};
+use crate::timer::Timer;
+
+// The state behind an AbortSignal. Its AbortController points at the same state
+pub struct AbortSignal {
+    aborted: bool,
+    reason: Option<v8::Global<v8::Value>>,
+    listeners: Vec<v8::Global<v8::Function>>,
+    // The JS signal, `this` and `target` for the listeners
+    object: Option<v8::Global<v8::Object>>,
+}
+
+impl AbortSignal {
+    fn new() -> Self {
+        AbortSignal { aborted: false, reason: None, listeners: Vec::new(), object: None }
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    pub fn reason<'s>(&self, scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Value> {
+        match &self.reason {
+            Some(reason) => v8::Local::new(scope, reason),
+            None => v8::undefined(scope).into(),
+        }
+    }
+
+    // Listeners registered from Rust, such as fetch() cancelling its request
+    pub fn add_listener(&mut self, listener: v8::Global<v8::Function>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn remove_listener(&mut self, scope: &mut v8::HandleScope, listener: v8::Local<v8::Function>) {
+        self.listeners.retain(|registered| !v8::Local::new(scope, registered).strict_equals(listener.into()));
+    }
+}
+
+// Marks the signal aborted and runs `onabort` and the 'abort' listeners, only the first time
+pub fn abort_signal(scope: &mut v8::HandleScope, signal: &mut AbortSignal, reason: v8::Local<v8::Value>) {
+    if signal.aborted {
+        return;
+    }
+    signal.aborted = true;
+    signal.reason = Some(v8::Global::new(scope, reason));
+    let listeners = std::mem::take(&mut signal.listeners);
+
+    let Some(signal_obj) = signal.object.as_ref().map(|object| v8::Local::new(scope, object)) else {
+        return;
+    };
+    let event = v8::Object::new(scope);
+    for (name, value) in [("type", v8::String::new(scope, "abort").unwrap().into()), ("target", signal_obj.into())] {
+        let key = v8::String::new(scope, name).unwrap();
+        event.set(scope, key.into(), value);
+    }
+
+    let onabort_key = v8::String::new(scope, "onabort").unwrap();
+    let onabort = signal_obj.get(scope, onabort_key.into())
+        .and_then(|onabort| v8::Local::<v8::Function>::try_from(onabort).ok());
+    let mut callbacks: Vec<v8::Local<v8::Function>> = onabort.into_iter().collect();
+    callbacks.extend(listeners.iter().map(|listener| v8::Local::new(scope, listener)));
+    for callback in callbacks {
+        callback.call(scope, signal_obj.into(), &[event.into()]);
+    }
+}
+
+// The default reason, what a DOMException named AbortError looks like
+pub fn abort_error<'s>(scope: &mut v8::HandleScope<'s>, name: &str, message: &str) -> v8::Local<'s, v8::Value> {
+    let message = v8::String::new(scope, message).unwrap();
+    let error = v8::Exception::error(scope, message);
+    let error_obj = v8::Local::<v8::Object>::try_from(error).unwrap();
+    let name_key = v8::String::new(scope, "name").unwrap();
+    let name_value = v8::String::new(scope, name).unwrap();
+    error_obj.set(scope, name_key.into(), name_value.into());
+    error
+}
+
+// The AbortSignal behind a JS value, None if it is not one
+pub fn get_abort_signal<'a>(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<&'a mut AbortSignal> {
+    if !is_instance(scope, value, "AbortSignal") {
+        return None;
+    }
+    let signal_obj = v8::Local::<v8::Object>::try_from(value).unwrap();
+    let internal_field = signal_obj.get_internal_field(scope, 0).unwrap();
+    let external_signal = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    Some(unsafe { &mut *(external_signal.value() as *mut AbortSignal) })
+}
+
+fn signal_pointer(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>) -> *mut AbortSignal {
+    let internal_field = obj.get_internal_field(scope, 0).unwrap();
+    let external_signal = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    external_signal.value() as *mut AbortSignal
+}
+
+// V8 Callbacks
+fn abort_signal_aborted_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &*signal_pointer(scope, args.this()) };
+    rv.set(v8::Boolean::new(scope, signal.aborted).into());
+}
+
+fn abort_signal_reason_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &*signal_pointer(scope, args.this()) };
+    rv.set(signal.reason(scope));
+}
+
+// addEventListener('abort', listener), other event types never fire
+fn abort_signal_add_event_listener_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &mut *signal_pointer(scope, args.this()) };
+    if args.get(0).to_rust_string_lossy(scope) != "abort" || signal.aborted {
+        return;
+    }
+    if let Ok(listener) = v8::Local::<v8::Function>::try_from(args.get(1)) {
+        signal.remove_listener(scope, listener);
+        let listener = v8::Global::new(scope, listener);
+        signal.add_listener(listener);
+    }
+}
+
+fn abort_signal_remove_event_listener_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &mut *signal_pointer(scope, args.this()) };
+    if let Ok(listener) = v8::Local::<v8::Function>::try_from(args.get(1)) {
+        signal.remove_listener(scope, listener);
+    }
+}
+
+fn abort_signal_throw_if_aborted_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &*signal_pointer(scope, args.this()) };
+    if signal.aborted {
+        let reason = signal.reason(scope);
+        scope.throw_exception(reason);
+    }
+}
+
+// Signals only come from an AbortController or the static helpers
+fn abort_signal_constructor(
+    scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    throw_type_error(scope, "Illegal constructor");
+}
+
+pub fn create_abort_signal_object<'s>(scope: &mut v8::HandleScope<'s>) -> (v8::Local<'s, v8::Object>, *mut AbortSignal) {
+    let signal_obj = new_instance(scope, "AbortSignal");
+
+    let aborted_key = v8::String::new(scope, "aborted").unwrap();
+    signal_obj.set_accessor(scope, aborted_key.into(), abort_signal_aborted_getter);
+    let reason_key = v8::String::new(scope, "reason").unwrap();
+    signal_obj.set_accessor(scope, reason_key.into(), abort_signal_reason_getter);
+    let onabort_key = v8::String::new(scope, "onabort").unwrap();
+    let null_value = v8::null(scope);
+    signal_obj.set(scope, onabort_key.into(), null_value.into());
+
+    let mut signal = Box::new(AbortSignal::new());
+    signal.object = Some(v8::Global::new(scope, signal_obj));
+    let signal_ptr = Box::into_raw(signal);
+    let external_signal = v8::External::new(scope, signal_ptr as *mut c_void);
+    signal_obj.set_internal_field(0, external_signal.into());
+
+    (signal_obj, signal_ptr)
+}
+
+// AbortSignal.abort([reason]), a signal that is already aborted
+fn abort_signal_abort_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let (signal_obj, signal_ptr) = create_abort_signal_object(scope);
+    let reason = match args.get(0).is_undefined() {
+        true => abort_error(scope, "AbortError", "This operation was aborted"),
+        false => args.get(0),
+    };
+    abort_signal(scope, unsafe { &mut *signal_ptr }, reason);
+    rv.set(signal_obj.into());
+}
+
+fn abort_signal_timeout_expired(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let external_signal = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let signal = unsafe { &mut *(external_signal.value() as *mut AbortSignal) };
+    let reason = abort_error(scope, "TimeoutError", "The operation was aborted due to timeout");
+    abort_signal(scope, signal, reason);
+}
+
+// AbortSignal.timeout(ms), a signal that aborts with a TimeoutError after `ms`
+fn abort_signal_timeout_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let delay = args.get(0).number_value(scope).unwrap_or(f64::NAN);
+    if !delay.is_finite() || delay < 0.0 {
+        throw_type_error(scope, "The \"milliseconds\" argument must be a non-negative number");
+        return;
+    }
+
+    let (signal_obj, signal_ptr) = create_abort_signal_object(scope);
+    let external_signal = v8::External::new(scope, signal_ptr as *mut c_void);
+    let expired = v8::Function::builder(abort_signal_timeout_expired)
+        .data(external_signal.into())
+        .build(scope)
+        .unwrap();
+
+    let tx = unsafe { &*retrieve_tx(scope, "channel").unwrap() };
+    Timer::new(tx.clone()).set_timeout(v8::Global::new(scope, expired), delay as u64);
+    rv.set(signal_obj.into());
+}
+
+fn abort_controller_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "AbortController") {
+        return;
+    }
+
+    let controller_obj = new_instance(scope, "AbortController");
+    let (signal_obj, signal_ptr) = create_abort_signal_object(scope);
+    let signal_key = v8::String::new(scope, "signal").unwrap();
+    controller_obj.set(scope, signal_key.into(), signal_obj.into());
+
+    // The controller shares the signal's state
+    let external_signal = v8::External::new(scope, signal_ptr as *mut c_void);
+    controller_obj.set_internal_field(0, external_signal.into());
+    rv.set(controller_obj.into());
+}
+
+// controller.abort([reason])
+fn abort_controller_abort_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let signal = unsafe { &mut *signal_pointer(scope, args.this()) };
+    let reason = match args.get(0).is_undefined() {
+        true => abort_error(scope, "AbortError", "This operation was aborted"),
+        false => args.get(0),
+    };
+    abort_signal(scope, signal, reason);
+}
+
+pub fn initialize_abort(scope: &mut v8::ContextScope<'_, v8::HandleScope<'_>>) {
+    let global = scope.get_current_context().global(scope);
+    let (signal_class, signal_prototype) = define_class(scope, global, "AbortSignal", abort_signal_constructor);
+    set_function(scope, signal_prototype, "addEventListener", abort_signal_add_event_listener_callback);
+    set_function(scope, signal_prototype, "removeEventListener", abort_signal_remove_event_listener_callback);
+    set_function(scope, signal_prototype, "throwIfAborted", abort_signal_throw_if_aborted_callback);
+    set_function(scope, signal_class.into(), "abort", abort_signal_abort_callback);
+    set_function(scope, signal_class.into(), "timeout", abort_signal_timeout_callback);
+
+    let (_, controller_prototype) = define_class(scope, global, "AbortController", abort_controller_constructor);
+    set_function(scope, controller_prototype, "abort", abort_controller_abort_callback);
+}
@@ -0,0 +1,398 @@
+use rusty_v8 as v8;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::c_void;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::helper::{define_class, is_instance, new_instance, require_new, set_function, throw_error};
+use crate::tls::{Socket, TlsOptions};
+
+// Idle sockets an agent keeps per host unless told otherwise, like Node
+const MAX_FREE_SOCKETS: usize = 256;
+
+// The global agent keeps connections alive and closes them after 5 seconds of idling, like Node 19+
+const GLOBAL_AGENT_TIMEOUT_MS: u64 = 5000;
+
+// Connections to one host:port
+#[derive(Default)]
+struct Pool {
+    // Sockets handed to requests, including the ones still connecting
+    active: usize,
+    free: Vec<FreeSocket>,
+    // Requests waiting for a socket once maxSockets are active
+    waiting: VecDeque<oneshot::Sender<PooledConnection>>,
+}
+
+struct FreeSocket {
+    id: u64,
+    socket: Socket,
+}
+
+// Hands out connections per host:port. With keepAlive, sockets whose response was complete
+// go back to the pool for the next request to the same host instead of being closed
+pub struct Agent {
+    pub keep_alive: bool,
+    pub max_sockets: usize,
+    pub max_free_sockets: usize,
+    // Free sockets idle longer than this are closed
+    pub timeout: Option<Duration>,
+    pools: Mutex<HashMap<String, Pool>>,
+    next_socket_id: AtomicU64,
+}
+
+impl Agent {
+    pub fn new(keep_alive: bool, max_sockets: usize, max_free_sockets: usize, timeout: Option<Duration>) -> Self {
+        Agent {
+            keep_alive,
+            max_sockets,
+            max_free_sockets,
+            timeout,
+            pools: Mutex::new(HashMap::new()),
+            next_socket_id: AtomicU64::new(0),
+        }
+    }
+
+    // Node's agent.getName(), the key of the pool
+    pub fn name(host: &str, port: u16) -> String {
+        format!("{}:{}:", host, port)
+    }
+
+    // TLS connections get pools of their own, per servername and trusted certificates
+    fn pool_name(host: &str, port: u16, tls: Option<&TlsOptions>) -> String {
+        match tls {
+            Some(tls) => format!("{}{}", Agent::name(host, port), tls.pool_name()),
+            None => Agent::name(host, port),
+        }
+    }
+
+    // A free socket to the host if there is one, otherwise a new connection once fewer than
+    // maxSockets are active. The connection returns to the agent when it is released or dropped
+    pub async fn connect(self: &Arc<Self>, host: &str, port: u16, tls: Option<&TlsOptions>) -> io::Result<PooledConnection> {
+        let name = Agent::pool_name(host, port, tls);
+
+        let waiting = {
+            let mut pools = self.pools.lock().unwrap();
+            let pool = pools.entry(name.clone()).or_default();
+
+            while let Some(free) = pool.free.pop() {
+                if is_reusable(&free.socket) {
+                    pool.active += 1;
+                    return Ok(PooledConnection::new(Some(free.socket), Some((self.clone(), name))));
+                }
+            }
+
+            if pool.active < self.max_sockets {
+                pool.active += 1;
+                None
+            } else {
+                let (waiter, waiting) = oneshot::channel();
+                pool.waiting.push_back(waiter);
+                Some(waiting)
+            }
+        };
+
+        let mut connection = match waiting {
+            Some(waiting) => waiting.await
+                .map_err(|_| io::Error::other("Agent was destroyed"))?,
+            None => PooledConnection::new(None, Some((self.clone(), name))),
+        };
+
+        // Either a slot freed up or another request handed over its socket
+        if connection.socket.is_none() {
+            connection.socket = Some(open_connection(host, port, tls).await?);
+        }
+        Ok(connection)
+    }
+
+    // A request is done with its connection. The slot goes to the next waiting request, with
+    // the socket when it can carry another request, or the socket is kept as a free one
+    fn finish(self: &Arc<Self>, name: &str, mut socket: Option<Socket>) {
+        loop {
+            let waiter = {
+                let mut pools = self.pools.lock().unwrap();
+                let Some(pool) = pools.get_mut(name) else {
+                    return;
+                };
+
+                match pool.waiting.pop_front() {
+                    Some(waiter) => waiter,
+                    None => {
+                        pool.active -= 1;
+                        let Some(socket) = socket.filter(|_| self.keep_alive && pool.free.len() < self.max_free_sockets) else {
+                            return;
+                        };
+                        let id = self.next_socket_id.fetch_add(1, Ordering::SeqCst);
+                        pool.free.push(FreeSocket { id, socket });
+                        drop(pools);
+                        self.expire_later(name.to_string(), id);
+                        return;
+                    }
+                }
+            };
+
+            let connection = PooledConnection::new(socket.take(), Some((self.clone(), name.to_string())));
+            match waiter.send(connection) {
+                Ok(()) => return,
+                // That request went away, the slot is still counted and goes to the next one
+                Err(mut connection) => {
+                    socket = connection.socket.take();
+                    connection.agent = None;
+                }
+            }
+        }
+    }
+
+    fn expire_later(self: &Arc<Self>, name: String, id: u64) {
+        let Some(timeout) = self.timeout else {
+            return;
+        };
+        let agent = Arc::downgrade(self);
+        tokio::task::spawn_local(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(agent) = agent.upgrade() {
+                if let Some(pool) = agent.pools.lock().unwrap().get_mut(&name) {
+                    pool.free.retain(|free| free.id != id);
+                }
+            }
+        });
+    }
+
+    // Closes every free socket, the ones in use close once their request is done
+    pub fn destroy(&self) {
+        for pool in self.pools.lock().unwrap().values_mut() {
+            pool.free.clear();
+        }
+    }
+
+    // (name, in use, free, waiting) for every host the agent has talked to
+    fn counts(&self) -> Vec<(String, usize, usize, usize)> {
+        self.pools.lock().unwrap().iter()
+            .map(|(name, pool)| (name.clone(), pool.active, pool.free.len(), pool.waiting.len()))
+            .collect()
+    }
+}
+
+async fn open_connection(host: &str, port: u16, tls: Option<&TlsOptions>) -> io::Result<Socket> {
+    let socket = TcpStream::connect((host, port)).await
+        .map_err(|e| io::Error::new(e.kind(), format!("connect {}: {}:{}", e, host, port)))?;
+    match tls {
+        Some(tls) => Ok(Socket::Tls(Box::new(tls.connect(host, socket).await?))),
+        None => Ok(Socket::Plain(socket)),
+    }
+}
+
+// An idle socket can be reused unless the server closed it or sent something unasked
+fn is_reusable(socket: &Socket) -> bool {
+    let mut byte = [0u8; 1];
+    matches!(socket.tcp().try_read(&mut byte), Err(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+// The connection of one request. Dropping it closes the socket and frees the agent's slot
+pub struct PooledConnection {
+    socket: Option<Socket>,
+    agent: Option<(Arc<Agent>, String)>,
+}
+
+impl PooledConnection {
+    fn new(socket: Option<Socket>, agent: Option<(Arc<Agent>, String)>) -> Self {
+        PooledConnection { socket, agent }
+    }
+
+    // A connection of its own, for requests with `agent: false`
+    pub async fn open(host: &str, port: u16, tls: Option<&TlsOptions>) -> io::Result<Self> {
+        let socket = open_connection(host, port, tls).await?;
+        Ok(PooledConnection { socket: Some(socket), agent: None })
+    }
+
+    pub fn socket(&mut self) -> &mut Socket {
+        self.socket.as_mut().unwrap()
+    }
+
+    // Gives the socket back for another request once the response was read completely
+    pub fn release(mut self) {
+        if let Some((agent, name)) = self.agent.take() {
+            agent.finish(&name, self.socket.take());
+        }
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some((agent, name)) = self.agent.take() {
+            agent.finish(&name, None);
+        }
+    }
+}
+
+thread_local! {
+    static GLOBAL_AGENT: Arc<Agent> = Arc::new(Agent::new(true, usize::MAX, MAX_FREE_SOCKETS, Some(Duration::from_millis(GLOBAL_AGENT_TIMEOUT_MS))));
+}
+
+// http.globalAgent, used by requests that do not pass an `agent`
+pub fn global_agent() -> Arc<Agent> {
+    GLOBAL_AGENT.with(|agent| agent.clone())
+}
+
+// V8 Callbacks
+pub fn get_agent(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<Arc<Agent>> {
+    if !is_instance(scope, value, "Agent") {
+        return None;
+    }
+    let agent_obj = v8::Local::<v8::Object>::try_from(value).unwrap();
+    let internal_field = agent_obj.get_internal_field(scope, 0).unwrap();
+    let external_agent = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    Some(unsafe { &*(external_agent.value() as *const Arc<Agent>) }.clone())
+}
+
+fn agent_option(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, name: &str) -> Option<f64> {
+    let key = v8::String::new(scope, name).unwrap();
+    let value = options?.get(scope, key.into())?;
+    match value.is_undefined() {
+        true => None,
+        false => Some(value.number_value(scope).unwrap_or(f64::NAN)),
+    }
+}
+
+// A count option, Infinity means no limit
+fn count_option(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, name: &str, default: usize) -> Result<usize, String> {
+    match agent_option(scope, options, name) {
+        None => Ok(default),
+        Some(value) if value == f64::INFINITY => Ok(usize::MAX),
+        Some(value) if value >= 1.0 && value.fract() == 0.0 => Ok(value as usize),
+        Some(value) => Err(format!("The value of \"options.{}\" is out of range. It must be > 0. Received {}", name, value)),
+    }
+}
+
+fn agent_from_options(scope: &mut v8::HandleScope, options: v8::Local<v8::Value>) -> Result<Agent, String> {
+    let options = v8::Local::<v8::Object>::try_from(options).ok();
+
+    let keep_alive_key = v8::String::new(scope, "keepAlive").unwrap();
+    let keep_alive = options
+        .and_then(|options| options.get(scope, keep_alive_key.into()))
+        .map(|value| value.boolean_value(scope))
+        .unwrap_or(false);
+
+    let max_sockets = count_option(scope, options, "maxSockets", usize::MAX)?;
+    let max_free_sockets = count_option(scope, options, "maxFreeSockets", MAX_FREE_SOCKETS)?;
+    let timeout = match agent_option(scope, options, "timeout") {
+        None => None,
+        Some(ms) if ms.is_finite() && ms >= 0.0 => Some(Duration::from_millis(ms as u64)),
+        Some(ms) => return Err(format!("The value of \"options.timeout\" is out of range. Received {}", ms)),
+    };
+
+    Ok(Agent::new(keep_alive, max_sockets, max_free_sockets, timeout))
+}
+
+fn agent_counts_getter(
+    scope: &mut v8::HandleScope,
+    name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let agent = get_agent(scope, args.this().into()).unwrap();
+    let property = name.to_rust_string_lossy(scope);
+
+    let counts_obj = v8::Object::new(scope);
+    for (pool_name, active, free, waiting) in agent.counts() {
+        let count = match property.as_str() {
+            "sockets" => active,
+            "freeSockets" => free,
+            _ => waiting,
+        };
+        if count > 0 {
+            let key = v8::String::new(scope, &pool_name).unwrap();
+            let value = v8::Number::new(scope, count as f64);
+            counts_obj.set(scope, key.into(), value.into());
+        }
+    }
+    rv.set(counts_obj.into());
+}
+
+fn agent_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let agent = get_agent(scope, args.this().into()).unwrap();
+    agent.destroy();
+}
+
+// getName({ host, port }), the pool a request with these options goes to
+fn agent_get_name_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let mut host = "localhost".to_string();
+    let mut port = 80;
+    if let Ok(options) = v8::Local::<v8::Object>::try_from(args.get(0)) {
+        for name in ["host", "hostname"] {
+            let key = v8::String::new(scope, name).unwrap();
+            if let Some(value) = options.get(scope, key.into()).filter(|value| !value.is_null_or_undefined()) {
+                host = value.to_rust_string_lossy(scope);
+            }
+        }
+        let port_key = v8::String::new(scope, "port").unwrap();
+        if let Some(value) = options.get(scope, port_key.into()).filter(|value| !value.is_null_or_undefined()) {
+            port = value.to_rust_string_lossy(scope).parse::<u16>().unwrap_or(80);
+        }
+    }
+    rv.set(v8::String::new(scope, &Agent::name(&host, port)).unwrap().into());
+}
+
+fn create_agent_object<'s>(scope: &mut v8::HandleScope<'s>, agent: Arc<Agent>) -> v8::Local<'s, v8::Object> {
+    let agent_obj = new_instance(scope, "Agent");
+
+    let keep_alive_key = v8::String::new(scope, "keepAlive").unwrap();
+    let keep_alive_value = v8::Boolean::new(scope, agent.keep_alive);
+    agent_obj.set(scope, keep_alive_key.into(), keep_alive_value.into());
+
+    for (name, limit) in [("maxSockets", agent.max_sockets), ("maxFreeSockets", agent.max_free_sockets)] {
+        let key = v8::String::new(scope, name).unwrap();
+        let value = match limit {
+            usize::MAX => v8::Number::new(scope, f64::INFINITY),
+            limit => v8::Number::new(scope, limit as f64),
+        };
+        agent_obj.set(scope, key.into(), value.into());
+    }
+
+    for name in ["sockets", "freeSockets", "requests"] {
+        let key = v8::String::new(scope, name).unwrap();
+        agent_obj.set_accessor(scope, key.into(), agent_counts_getter);
+    }
+
+    let external_agent = v8::External::new(scope, Box::into_raw(Box::new(agent)) as *mut c_void);
+    agent_obj.set_internal_field(0, external_agent.into());
+    agent_obj
+}
+
+// new http.Agent([options])
+fn agent_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "Agent") {
+        return;
+    }
+    match agent_from_options(scope, args.get(0)) {
+        Ok(agent) => rv.set(create_agent_object(scope, Arc::new(agent)).into()),
+        Err(e) => throw_error(scope, &e),
+    }
+}
+
+// http.Agent and http.globalAgent
+pub fn initialize_agent(scope: &mut v8::HandleScope, http_obj: v8::Local<v8::Object>) {
+    let (_, agent_prototype) = define_class(scope, http_obj, "Agent", agent_constructor);
+    set_function(scope, agent_prototype, "destroy", agent_destroy_callback);
+    set_function(scope, agent_prototype, "getName", agent_get_name_callback);
+
+    let global_agent_obj = create_agent_object(scope, global_agent());
+    let global_agent_key = v8::String::new(scope, "globalAgent").unwrap();
+    http_obj.set(scope, global_agent_key.into(), global_agent_obj.into());
+}
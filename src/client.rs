@@ -0,0 +1,1229 @@
+use rusty_v8 as v8;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{oneshot, watch};
+use tokio::time::Instant;
+use url::Url;
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::agent::{get_agent, global_agent, Agent, PooledConnection};
+use crate::emitter::{attach_event_emitter, emit_event, EventEmitter};
+use crate::headers::{header_values_from_js, header_values_to_js, headers_to_object, is_token_byte, raw_headers_array, validate_header, Headers};
+use crate::helper::{set_function, throw_error, value_to_bytes};
+use crate::http::{fill_buffer, read_body, request_keep_alive, BodyLength, Http};
+use crate::interface::{HttpOperation, Operations, StreamEvent};
+use crate::permissions::Permissions;
+use crate::server::MAX_HEADER_SIZE;
+use crate::stream::{Chunk, Utf8Decoder, WRITE_HIGH_WATER_MARK};
+use crate::tls::{Socket, TlsOptions};
+use crate::zlib::Codec;
+
+// The codings read_response() can decode
+const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+
+// Where a request goes and what it starts with, from http.request(url | options)
+pub struct RequestOptions {
+    pub host: String,
+    pub port: u16,
+    pub method: String,
+    pub path: String,
+    pub headers: Headers,
+    // None for `agent: false`, a connection of its own that is closed afterwards
+    pub agent: Option<Arc<Agent>>,
+    // Redirects followed before a response is handed back, with 0 the redirect itself is
+    pub max_redirects: usize,
+    // Emits 'timeout' and gives up once the connection was idle this long
+    pub timeout: Option<Duration>,
+    // Set for https: requests
+    pub tls: Option<TlsOptions>,
+    // Asks for compressed responses and decodes their body, off with `decompress: false`
+    pub decompress: bool,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        RequestOptions {
+            host: "localhost".to_string(),
+            port: 80,
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            headers: Headers::new(),
+            agent: Some(global_agent()),
+            max_redirects: 0,
+            timeout: None,
+            tls: None,
+            decompress: true,
+        }
+    }
+}
+
+impl RequestOptions {
+    // An http: or https: request to localhost
+    fn new(secure: bool) -> Self {
+        match secure {
+            true => RequestOptions { port: 443, tls: Some(TlsOptions::default()), ..RequestOptions::default() },
+            false => RequestOptions::default(),
+        }
+    }
+
+    fn protocol(&self) -> &'static str {
+        if self.tls.is_some() { "https:" } else { "http:" }
+    }
+
+    // http.request(url, [options]) or http.request(options), options override the URL.
+    // https.request() passes `secure`
+    pub fn from_js(scope: &mut v8::HandleScope, url: v8::Local<v8::Value>, options: v8::Local<v8::Value>, secure: bool) -> Result<Self, String> {
+        let mut request_options = RequestOptions::new(secure);
+
+        let options = if url.is_string() {
+            request_options.apply_url(&url.to_rust_string_lossy(scope))?;
+            options
+        } else {
+            url
+        };
+        if let Ok(options) = v8::Local::<v8::Object>::try_from(options) {
+            if !options.is_function() {
+                request_options.apply_object(scope, options)?;
+            }
+        }
+
+        Ok(request_options)
+    }
+
+    // A GET of an absolute http: or https: URL, what fetch() starts from
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        let mut request_options = RequestOptions::new(url.starts_with("https:"));
+        request_options.apply_url(url)?;
+        Ok(request_options)
+    }
+
+    fn apply_url(&mut self, url: &str) -> Result<(), String> {
+        let url = Url::parse(url).map_err(|e| format!("Invalid URL: {}: {}", url, e))?;
+        if format!("{}:", url.scheme()) != self.protocol() {
+            return Err(format!("Protocol \"{}:\" not supported. Expected \"{}\"", url.scheme(), self.protocol()));
+        }
+
+        if url.host_str().is_none() {
+            return Err(format!("Invalid URL: {}", url));
+        }
+        self.host = url_host(&url);
+        self.port = url.port_or_known_default().unwrap_or(80);
+        self.path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        Ok(())
+    }
+
+    fn apply_object(&mut self, scope: &mut v8::HandleScope, options: v8::Local<v8::Object>) -> Result<(), String> {
+        if let Some(protocol) = string_option(scope, options, "protocol") {
+            if protocol != self.protocol() {
+                return Err(format!("Protocol \"{}\" not supported. Expected \"{}\"", protocol, self.protocol()));
+            }
+        }
+
+        // hostname wins over host. An IPv6 host may come in brackets, with a port after them
+        if let Some(host) = string_option(scope, options, "host") {
+            self.host = match host.strip_prefix('[').and_then(|host| host.split_once(']')) {
+                Some((address, rest)) => {
+                    if let Some(port) = rest.strip_prefix(':').and_then(|port| port.parse::<u16>().ok()) {
+                        self.port = port;
+                    }
+                    address.to_string()
+                }
+                None => host,
+            };
+        }
+        if let Some(hostname) = string_option(scope, options, "hostname") {
+            self.host = hostname.trim_start_matches('[').trim_end_matches(']').to_string();
+        }
+
+        if let Some(port) = option(scope, options, "port") {
+            let port_text = port.to_rust_string_lossy(scope);
+            self.port = port_text.parse::<u16>()
+                .map_err(|_| format!("Port should be >= 0 and < 65536. Received {}", port_text))?;
+        }
+
+        if let Some(method) = string_option(scope, options, "method") {
+            if method.is_empty() || !method.bytes().all(is_token_byte) {
+                return Err(format!("Method must be a valid HTTP token [\"{}\"]", method));
+            }
+            self.method = method.to_ascii_uppercase();
+        }
+
+        // Spaces or line breaks in the path would let it rewrite the request line
+        if let Some(path) = string_option(scope, options, "path") {
+            if path.bytes().any(|byte| byte <= b' ' || byte == 0x7f) {
+                return Err("Request path contains unescaped characters".to_string());
+            }
+            self.path = path;
+        }
+
+        if let Some(headers) = option(scope, options, "headers").and_then(|value| v8::Local::<v8::Object>::try_from(value).ok()) {
+            let names = headers.get_own_property_names(scope).unwrap();
+            for i in 0..names.length() {
+                let name = names.get_index(scope, i).unwrap();
+                let value = headers.get(scope, name).unwrap();
+                let name = name.to_rust_string_lossy(scope);
+                let values = header_values_from_js(scope, value);
+                validate_header(&name, &values)?;
+                self.headers.set_all(name, values);
+            }
+        }
+
+        if let Some(agent) = option(scope, options, "agent") {
+            self.agent = match agent.is_false() {
+                true => None,
+                false => Some(get_agent(scope, agent).ok_or_else(|| {
+                    "The \"options.agent\" property must be an Agent, undefined or false".to_string()
+                })?),
+            };
+        }
+
+        if let Some(max_redirects) = option(scope, options, "maxRedirects") {
+            let max_redirects = max_redirects.number_value(scope).unwrap_or(f64::NAN);
+            if !(max_redirects >= 0.0 && max_redirects.fract() == 0.0) {
+                return Err(format!("The value of \"options.maxRedirects\" is out of range. It must be >= 0. Received {}", max_redirects));
+            }
+            self.max_redirects = max_redirects as usize;
+        }
+
+        // 0 turns the timeout off, like Node
+        if let Some(timeout) = option(scope, options, "timeout") {
+            let timeout = timeout.number_value(scope).unwrap_or(f64::NAN);
+            if !(timeout >= 0.0 && timeout.is_finite()) {
+                return Err(format!("The value of \"options.timeout\" is out of range. It must be >= 0. Received {}", timeout));
+            }
+            self.timeout = Some(Duration::from_millis(timeout as u64)).filter(|timeout| !timeout.is_zero());
+        }
+
+        if let Some(decompress) = option(scope, options, "decompress") {
+            self.decompress = decompress.boolean_value(scope);
+        }
+
+        if self.tls.is_some() {
+            self.tls = Some(tls_options(scope, options)?);
+        }
+
+        Ok(())
+    }
+}
+
+// Certificates are checked against the bundled roots, or only against `ca`
+pub fn tls_options(scope: &mut v8::HandleScope, options: v8::Local<v8::Object>) -> Result<TlsOptions, String> {
+    let ca = option(scope, options, "ca").map(|ca| pem_bundle(scope, ca));
+    let reject_unauthorized = option(scope, options, "rejectUnauthorized")
+        .is_none_or(|reject| reject.boolean_value(scope));
+    let servername = string_option(scope, options, "servername");
+    TlsOptions::new(ca.as_deref(), reject_unauthorized, servername)
+}
+
+pub fn option<'s>(scope: &mut v8::HandleScope<'s>, options: v8::Local<v8::Object>, name: &str) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name).unwrap();
+    options.get(scope, key.into()).filter(|value| !value.is_null_or_undefined())
+}
+
+// PEM options such as `ca` and `cert`, one string or Buffer or an array of them
+pub fn pem_bundle(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Vec<u8> {
+    let Ok(bundle) = v8::Local::<v8::Array>::try_from(value) else {
+        return value_to_bytes(scope, value);
+    };
+    let mut pem = Vec::new();
+    for i in 0..bundle.length() {
+        let certificate = bundle.get_index(scope, i).unwrap();
+        pem.extend(value_to_bytes(scope, certificate));
+        pem.push(b'\n');
+    }
+    pem
+}
+
+pub fn string_option(scope: &mut v8::HandleScope, options: v8::Local<v8::Object>, name: &str) -> Option<String> {
+    option(scope, options, name).map(|value| value.to_rust_string_lossy(scope))
+}
+
+// The request line and headers. The exchange keeps them to repeat the request on a redirect
+struct RequestHead {
+    method: String,
+    path: String,
+    headers: Headers,
+}
+
+impl RequestHead {
+    fn serialize(&self) -> Vec<u8> {
+        format!("{} {} HTTP/1.1\r\n{}\r\n", self.method, self.path, self.headers.serialize()).into_bytes()
+    }
+
+    // Whether the request asked to keep the connection open
+    fn keep_alive(&self) -> bool {
+        !self.headers.get_joined("Connection").unwrap_or_default().to_ascii_lowercase().contains("close")
+    }
+}
+
+enum ClientCommand {
+    Head(RequestHead),
+    Write(Vec<u8>, usize, Option<v8::Global<v8::Function>>),
+    End(Option<v8::Global<v8::Function>>),
+}
+
+// Why the exchange was stopped before it finished
+#[derive(Clone, PartialEq)]
+enum Cancel {
+    Running,
+    // req.abort(), the request ends without an error
+    Aborted,
+    // req.destroy([error])
+    Destroyed(Option<String>),
+}
+
+// The Host header for a server, the port is left out when it is the default one. IPv6
+// addresses go in brackets
+fn host_header(host: &str, port: u16, secure: bool) -> String {
+    let host = match host.contains(':') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    };
+    match (port, secure) {
+        (80, false) | (443, true) => host,
+        (port, _) => format!("{}:{}", host, port),
+    }
+}
+
+// The host of a URL as it is connected to, without the brackets of an IPv6 address
+fn url_host(url: &Url) -> String {
+    url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string()
+}
+
+// An outgoing request. Headers may change until the first write() or end(), which sends the
+// head. The connection is opened right away by a task that writes whatever was queued
+// once it is connected, then reads the response
+pub struct ClientRequest {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    options: RequestOptions,
+    commands: UnboundedSender<ClientCommand>,
+    cancel: watch::Sender<Cancel>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    chunked: bool,
+    headers_sent: bool,
+    ended: bool,
+}
+
+impl ClientRequest {
+    pub fn send(options: RequestOptions, tx: UnboundedSender<Operations>, permissions: Arc<Permissions>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<ClientCommand>();
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let need_drain = Arc::new(AtomicBool::new(false));
+        let (cancel, cancel_rx) = watch::channel(Cancel::Running);
+
+        let exchange = Exchange {
+            host: options.host.clone(),
+            port: options.port,
+            agent: options.agent.clone(),
+            permissions,
+            max_redirects: options.max_redirects,
+            timeout: options.timeout,
+            tls: options.tls.clone(),
+            decompress: options.decompress,
+            last_activity: Cell::new(Instant::now()),
+            response: RefCell::new(None),
+            emitter: event_emitter.clone(),
+            buffered: buffered.clone(),
+            need_drain: need_drain.clone(),
+            tx,
+        };
+        tokio::task::spawn_local(exchange.run(commands_rx, cancel_rx));
+
+        ClientRequest {
+            event_emitter,
+            options,
+            commands,
+            cancel,
+            buffered,
+            need_drain,
+            chunked: false,
+            headers_sent: false,
+            ended: false,
+        }
+    }
+
+    pub fn headers_sent(&self) -> bool {
+        self.headers_sent
+    }
+
+    // Without a known length the body is sent chunked, unless the caller framed it
+    fn send_head(&mut self, body_length: Option<usize>) {
+        if self.headers_sent {
+            return;
+        }
+        self.headers_sent = true;
+
+        let headers = &mut self.options.headers;
+        if !headers.contains("Host") {
+            headers.set("Host".to_string(), host_header(&self.options.host, self.options.port, self.options.tls.is_some()));
+        }
+
+        if let Some(transfer_encoding) = headers.get_joined("Transfer-Encoding") {
+            self.chunked = transfer_encoding.to_ascii_lowercase().contains("chunked");
+        } else if !headers.contains("Content-Length") {
+            match body_length {
+                // Methods that expect a body always say how long it is
+                Some(length) if length > 0 || matches!(self.options.method.as_str(), "POST" | "PUT" | "PATCH") => {
+                    headers.set("Content-Length".to_string(), length.to_string());
+                }
+                Some(_) => {}
+                None => {
+                    headers.set("Transfer-Encoding".to_string(), "chunked".to_string());
+                    self.chunked = true;
+                }
+            }
+        }
+
+        if self.options.decompress && !headers.contains("Accept-Encoding") {
+            headers.set("Accept-Encoding".to_string(), ACCEPT_ENCODING.to_string());
+        }
+
+        // Only an agent that keeps connections alive asks for it
+        if !headers.contains("Connection") {
+            let keep_alive = self.options.agent.as_ref().is_some_and(|agent| agent.keep_alive);
+            let connection = if keep_alive { "keep-alive" } else { "close" };
+            headers.set("Connection".to_string(), connection.to_string());
+        }
+
+        let head = RequestHead {
+            method: self.options.method.clone(),
+            path: self.options.path.clone(),
+            headers: headers.clone(),
+        };
+        let _ = self.commands.send(ClientCommand::Head(head));
+    }
+
+    // Returns false once the caller should wait for 'drain' before writing more
+    pub fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<bool, String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+        self.send_head(None);
+
+        let length = data.len();
+        let frame = match self.chunked {
+            // An empty chunk would terminate the body
+            true if length == 0 => Vec::new(),
+            true => {
+                let mut frame = format!("{:x}\r\n", length).into_bytes();
+                frame.extend_from_slice(&data);
+                frame.extend_from_slice(b"\r\n");
+                frame
+            }
+            false => data,
+        };
+        Ok(self.queue(frame, length, callback))
+    }
+
+    pub fn end(&mut self, data: Option<Vec<u8>>, callback: Option<v8::Global<v8::Function>>) -> Result<(), String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+
+        let data = data.unwrap_or_default();
+        self.send_head(Some(data.len()));
+        if !data.is_empty() {
+            self.write(data, None)?;
+        }
+        if self.chunked {
+            self.queue(b"0\r\n\r\n".to_vec(), 0, None);
+        }
+
+        self.ended = true;
+        let _ = self.commands.send(ClientCommand::End(callback));
+        Ok(())
+    }
+
+    // Only the first abort() or destroy() counts, returns whether this was it
+    fn stop(&self, cancel: Cancel) -> bool {
+        self.cancel.send_if_modified(|current| {
+            let running = *current == Cancel::Running;
+            if running {
+                *current = cancel;
+            }
+            running
+        })
+    }
+
+    // Stops the exchange wherever it is without an error, it still ends with 'close'
+    pub fn abort(&self) -> bool {
+        self.stop(Cancel::Aborted)
+    }
+
+    // Stops the exchange and emits `error`, "socket hang up" when no response came yet
+    pub fn destroy(&self, error_message: Option<String>) -> bool {
+        self.stop(Cancel::Destroyed(error_message))
+    }
+
+    pub fn destroyed(&self) -> bool {
+        *self.cancel.borrow() != Cancel::Running
+    }
+
+    fn queue(&mut self, frame: Vec<u8>, length: usize, callback: Option<v8::Global<v8::Function>>) -> bool {
+        let buffered = self.buffered.fetch_add(length, Ordering::SeqCst) + length;
+        let _ = self.commands.send(ClientCommand::Write(frame, length, callback));
+
+        let ok = buffered < WRITE_HIGH_WATER_MARK;
+        if !ok {
+            self.need_drain.store(true, Ordering::SeqCst);
+        }
+        ok
+    }
+}
+
+// The connection side of a ClientRequest
+struct Exchange {
+    host: String,
+    port: u16,
+    agent: Option<Arc<Agent>>,
+    // Redirect targets are checked like the first host
+    permissions: Arc<Permissions>,
+    max_redirects: usize,
+    timeout: Option<Duration>,
+    tls: Option<TlsOptions>,
+    decompress: bool,
+    last_activity: Cell<Instant>,
+    // The response whose body is being read, told when the exchange is cut short
+    response: RefCell<Option<Rc<RefCell<EventEmitter>>>>,
+    emitter: Rc<RefCell<EventEmitter>>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    tx: UnboundedSender<Operations>,
+}
+
+impl Exchange {
+    async fn run(self, commands: UnboundedReceiver<ClientCommand>, mut cancel: watch::Receiver<Cancel>) {
+        let error_message = tokio::select! {
+            result = self.exchange(commands) => result.err().map(|e| e.to_string()),
+            Ok(cancel) = cancel.wait_for(|cancel| *cancel != Cancel::Running) => match &*cancel {
+                Cancel::Destroyed(Some(error_message)) => Some(error_message.clone()),
+                Cancel::Destroyed(None) => self.hang_up(),
+                _ => None,
+            },
+            _ = self.idle_timeout() => {
+                let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: self.emitter.clone(), event: "timeout" }));
+                self.hang_up()
+            }
+        };
+
+        if let Some(error_message) = error_message {
+            let op = StreamEvent::Error{ emitter: self.emitter.clone(), error_message };
+            let _ = self.tx.send(Operations::Stream(op));
+        }
+        // A body that was cut off
+        if let Some(response) = self.response.take() {
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: response.clone(), event: "aborted" }));
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Close{ emitter: response }));
+        }
+        let _ = self.tx.send(Operations::Stream(StreamEvent::Close{ emitter: self.emitter.clone() }));
+    }
+
+    // A request stopped before its response came fails like a dropped connection, later the
+    // response is told instead
+    fn hang_up(&self) -> Option<String> {
+        self.response.borrow().is_none().then(|| "socket hang up".to_string())
+    }
+
+    fn touch(&self) {
+        self.last_activity.set(Instant::now());
+    }
+
+    // Resolves once nothing was sent or received for `timeout`
+    async fn idle_timeout(&self) {
+        let Some(timeout) = self.timeout else {
+            return std::future::pending().await;
+        };
+        loop {
+            let deadline = self.last_activity.get() + timeout;
+            if Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+
+    async fn connect(&self, host: &str, port: u16, tls: Option<&TlsOptions>) -> io::Result<PooledConnection> {
+        let connection = match &self.agent {
+            Some(agent) => agent.connect(host, port, tls).await?,
+            None => PooledConnection::open(host, port, tls).await?,
+        };
+        self.touch();
+        Ok(connection)
+    }
+
+    async fn exchange(&self, mut commands: UnboundedReceiver<ClientCommand>) -> io::Result<()> {
+        let mut tls = self.tls.clone();
+        let mut connection = self.connect(&self.host, self.port, tls.as_ref()).await?;
+
+        // Send the request as it is written, the response is only read after end()
+        let mut head = None;
+        // Kept to send it again when a redirect keeps the body
+        let mut body = Vec::new();
+        loop {
+            match commands.recv().await {
+                Some(ClientCommand::Head(request_head)) => {
+                    connection.socket().write_all(&request_head.serialize()).await?;
+                    self.touch();
+                    head = Some(request_head);
+                }
+                Some(ClientCommand::Write(frame, length, callback)) => {
+                    let result = connection.socket().write_all(&frame).await;
+                    let remaining = self.buffered.fetch_sub(length, Ordering::SeqCst) - length;
+                    self.touch();
+
+                    if let Some(callback) = callback {
+                        let error_message = result.as_ref().err().map(|e| e.to_string());
+                        let _ = self.tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                    }
+                    result?;
+                    if self.max_redirects > 0 {
+                        body.extend_from_slice(&frame);
+                    }
+
+                    if remaining == 0 && self.need_drain.swap(false, Ordering::SeqCst) {
+                        let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: self.emitter.clone(), event: "drain" }));
+                    }
+                }
+                Some(ClientCommand::End(callback)) => {
+                    connection.socket().flush().await?;
+                    let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: self.emitter.clone(), event: "finish" }));
+                    if let Some(callback) = callback {
+                        let _ = self.tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message: None }));
+                    }
+                    break;
+                }
+                // The request was dropped without end()
+                None => return Ok(()),
+            }
+        }
+        // end() always sends the head first
+        let Some(mut head) = head else {
+            return Ok(());
+        };
+
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let mut url = Url::parse(&format!("{}://{}{}", scheme, host_header(&self.host, self.port, tls.is_some()), head.path))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let mut redirects = 0;
+        loop {
+            let mut buffer = Vec::new();
+            let mut response = self.read_head(connection.socket(), &mut buffer).await?;
+            response.url = url.to_string();
+
+            let body_length = response_body_length(head.method == "HEAD", response.status_code, &response.headers)?;
+            let version = if response.http_version == "1.0" { 0 } else { 1 };
+            let keep_alive = head.keep_alive() && request_keep_alive(version, &response.headers)
+                && !matches!(body_length, BodyLength::UntilClose);
+
+            let target = match redirect_target(&url, self.max_redirects, &response)? {
+                Some(target) => target,
+                None => {
+                    // The agent gets the socket back when the response left it ready for another request
+                    if self.read_response(connection.socket(), buffer, response, body_length, keep_alive).await? {
+                        connection.release();
+                    }
+                    return Ok(());
+                }
+            };
+            if redirects == self.max_redirects {
+                return Err(io::Error::other("Maximum number of redirects exceeded"));
+            }
+            redirects += 1;
+
+            // The redirect's own body is skipped so its connection can be reused
+            let result = read_body(connection.socket(), &mut buffer, body_length, MAX_HEADER_SIZE, |_| {}).await;
+            if keep_alive && result.is_ok() && buffer.is_empty() {
+                connection.release();
+            } else {
+                drop(connection);
+            }
+
+            follow_redirect(&mut head, &mut body, &url, &target, response.status_code);
+            // A servername only applies to the server it was given for
+            tls = match target.scheme() {
+                "https" if target.origin() == url.origin() => tls,
+                "https" => Some(tls.map(|tls| tls.without_servername()).unwrap_or_default()),
+                _ => None,
+            };
+            url = target;
+            let host = url_host(&url);
+            let port = url.port_or_known_default().unwrap_or(80);
+            self.permissions.check_net(&host, port)
+                .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e.to_string()))?;
+
+            connection = self.connect(&host, port, tls.as_ref()).await?;
+            let socket = connection.socket();
+            socket.write_all(&head.serialize()).await?;
+            socket.write_all(&body).await?;
+            socket.flush().await?;
+            self.touch();
+        }
+    }
+
+    // The final response head, interim 1xx responses such as 100 Continue come before it
+    async fn read_head(&self, socket: &mut Socket, buffer: &mut Vec<u8>) -> io::Result<ClientResponse> {
+        loop {
+            let (response, head_length) = read_response_head(socket, buffer).await?;
+            buffer.drain(..head_length);
+            self.touch();
+            if !(100..200).contains(&response.status_code) || response.status_code == 101 {
+                return Ok(response);
+            }
+        }
+    }
+
+    // Emits 'response', then streams the body on the response. Returns whether the
+    // connection can carry another request
+    async fn read_response(
+        &self,
+        socket: &mut Socket,
+        mut buffer: Vec<u8>,
+        response: ClientResponse,
+        body_length: BodyLength,
+        keep_alive: bool
+    ) -> io::Result<bool> {
+        let emitter = response.event_emitter.clone();
+        let complete = response.complete.clone();
+        let encoding = response.encoding.clone();
+        let mut content_decoder = if self.decompress { Codec::for_content_encoding(&response.headers) } else { None };
+
+        // The body is only read once the 'response' listeners ran, so setEncoding() applies to every chunk
+        let (ready, ready_rx) = oneshot::channel::<()>();
+        *self.response.borrow_mut() = Some(emitter.clone());
+        let op = HttpOperation::ClientResponse{ request_emitter: self.emitter.clone(), response, ready };
+        let _ = self.tx.send(Operations::Http(op));
+        let _ = ready_rx.await;
+        self.touch();
+
+        let mut decoder = Utf8Decoder::default();
+        let mut emit_chunk = |bytes: Vec<u8>| {
+            if bytes.is_empty() {
+                return;
+            }
+            let chunk = match encoding.lock().unwrap().is_some() {
+                true => Chunk::Text(decoder.decode(&bytes)),
+                false => Chunk::Bytes(bytes),
+            };
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk }));
+        };
+
+        // A body that fails to decompress is an error of the response, the connection is still fine
+        let mut received = false;
+        let mut decode_error = None;
+        let result = read_body(socket, &mut buffer, body_length, MAX_HEADER_SIZE, |bytes| {
+            self.touch();
+            received = true;
+            match content_decoder.as_mut() {
+                Some(_) if decode_error.is_some() => {}
+                Some(content_decoder) => match content_decoder.write(&bytes) {
+                    Ok(bytes) => emit_chunk(bytes),
+                    Err(e) => decode_error = Some(e),
+                },
+                None => emit_chunk(bytes),
+            }
+        }).await;
+        let decoded = match (decode_error, content_decoder) {
+            (Some(e), _) => Err(e),
+            (None, Some(content_decoder)) if result.is_ok() && received => content_decoder.finish().map(&mut emit_chunk),
+            _ => Ok(()),
+        };
+
+        let rest = decoder.finish();
+        if !rest.is_empty() {
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Text(rest) }));
+        }
+
+        // Bytes past the body mean the server is out of step with us
+        let reusable = keep_alive && result.is_ok() && buffer.is_empty();
+        let op = match result.and(decoded) {
+            Ok(()) => {
+                complete.store(true, Ordering::SeqCst);
+                StreamEvent::Emit{ emitter: emitter.clone(), event: "end" }
+            }
+            // The server went away in the middle of the body
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => StreamEvent::Emit{ emitter: emitter.clone(), event: "aborted" },
+            Err(e) => StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() },
+        };
+        self.response.take();
+        let _ = self.tx.send(Operations::Stream(op));
+        let _ = self.tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+        Ok(reusable)
+    }
+}
+
+// Where a redirect response points when redirects are followed, relative to the URL requested
+fn redirect_target(url: &Url, max_redirects: usize, response: &ClientResponse) -> io::Result<Option<Url>> {
+    if max_redirects == 0 || !matches!(response.status_code, 301 | 302 | 303 | 307 | 308) {
+        return Ok(None);
+    }
+    let Some(location) = response.headers.get("Location") else {
+        return Ok(None);
+    };
+
+    let target = url.join(location)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid redirect location {}", location)))?;
+    if !matches!(target.scheme(), "http" | "https") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported protocol {}", target.scheme())));
+    }
+    Ok(Some(target))
+}
+
+// Rewrites the request for the redirect target. 303 and the historical POST behaviour of 301/302
+// turn it into a GET without a body, 307 and 308 repeat it as it was
+fn follow_redirect(head: &mut RequestHead, body: &mut Vec<u8>, url: &Url, target: &Url, status_code: u16) {
+    if (status_code == 303 && head.method != "HEAD") || (matches!(status_code, 301 | 302) && head.method == "POST") {
+        head.method = "GET".to_string();
+        body.clear();
+        for name in ["Content-Length", "Transfer-Encoding", "Content-Type"] {
+            head.headers.remove(name);
+        }
+    }
+
+    // Credentials are not sent to another origin
+    if target.origin() != url.origin() {
+        for name in ["Authorization", "Proxy-Authorization", "Cookie"] {
+            head.headers.remove(name);
+        }
+    }
+
+    let host = url_host(target);
+    let port = target.port_or_known_default().unwrap_or(80);
+    head.headers.set("Host".to_string(), host_header(&host, port, target.scheme() == "https"));
+    head.path = match target.query() {
+        Some(query) => format!("{}?{}", target.path(), query),
+        None => target.path().to_string(),
+    };
+}
+
+// Reads until a complete status line and headers are buffered, returning the head's length
+async fn read_response_head(socket: &mut Socket, buffer: &mut Vec<u8>) -> io::Result<(ClientResponse, usize)> {
+    loop {
+        if !buffer.is_empty() {
+            if let Some(parsed) = parse_response_head(buffer)? {
+                return Ok(parsed);
+            }
+            if buffer.len() > MAX_HEADER_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Response header is too large"));
+            }
+        }
+
+        if !fill_buffer(socket, buffer).await? {
+            return Err(io::Error::new(io::ErrorKind::ConnectionReset, "socket hang up"));
+        }
+    }
+}
+
+fn parse_response_head(data: &[u8]) -> io::Result<Option<(ClientResponse, usize)>> {
+    // Start with room for 64 headers and grow when a response has more
+    let mut header_capacity = 64;
+
+    loop {
+        let mut headers = vec![httparse::EMPTY_HEADER; header_capacity];
+        let mut res = httparse::Response::new(&mut headers);
+
+        let head_length = match res.parse(data) {
+            Ok(httparse::Status::Complete(head_length)) => head_length,
+            Ok(httparse::Status::Partial) => return Ok(None),
+            Err(httparse::Error::TooManyHeaders) => {
+                header_capacity *= 2;
+                continue;
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Parse Error: {}", e))),
+        };
+
+        let response = ClientResponse {
+            status_code: res.code.unwrap_or(0),
+            status_message: res.reason.unwrap_or("").to_string(),
+            http_version: format!("1.{}", res.version.unwrap_or(1)),
+            headers: Headers::from_httparse(res.headers),
+            url: String::new(),
+            event_emitter: Rc::new(RefCell::new(EventEmitter::new())),
+            complete: Arc::new(AtomicBool::new(false)),
+            encoding: Arc::new(Mutex::new(None)),
+        };
+        return Ok(Some((response, head_length)));
+    }
+}
+
+// Responses to HEAD and 1xx/204/304 never have a body, one without a length runs until the server closes
+fn response_body_length(head_request: bool, status_code: u16, headers: &Headers) -> io::Result<BodyLength> {
+    if head_request || (100..200).contains(&status_code) || status_code == 204 || status_code == 304 {
+        return Ok(BodyLength::Empty);
+    }
+
+    if let Some(transfer_encoding) = headers.get_joined("Transfer-Encoding") {
+        if transfer_encoding.to_ascii_lowercase().rsplit(',').next().map(str::trim) == Some("chunked") {
+            return Ok(BodyLength::Chunked);
+        }
+        return Ok(BodyLength::UntilClose);
+    }
+
+    match headers.get_last("Content-Length").map(str::trim) {
+        Some(content_length) => content_length.parse::<usize>()
+            .map(BodyLength::Fixed)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid Content-Length: {}", content_length))),
+        None => Ok(BodyLength::UntilClose),
+    }
+}
+
+// The response side of a client request, Node's IncomingMessage
+pub struct ClientResponse {
+    pub status_code: u16,
+    pub status_message: String,
+    pub http_version: String,
+    pub headers: Headers,
+    // Where the response came from, the last target when redirects were followed
+    pub url: String,
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    // Set once the whole body was received
+    pub complete: Arc<AtomicBool>,
+    // Set by setEncoding(), chunks are then emitted as strings
+    encoding: Arc<Mutex<Option<String>>>,
+}
+
+// V8 Callbacks
+fn get_client_request<'a>(scope: &mut v8::HandleScope, request_obj: v8::Local<v8::Object>) -> &'a mut ClientRequest {
+    let internal_field = request_obj.get_internal_field(scope, 0).unwrap();
+    let external_request = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_request.value() as *mut ClientRequest) }
+}
+
+fn emit_request_error(scope: &mut v8::HandleScope, request: &ClientRequest, error_message: &str) {
+    let error_value = v8::String::new(scope, error_message).unwrap();
+    emit_event(scope, &request.event_emitter, "error", &[error_value.into()]);
+}
+
+// Parses (url | options, [options], [callback]) and starts the request, the callback is a
+// 'response' listener. Throws and returns None when the arguments are invalid
+pub fn start_request(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, force_get: bool, secure: bool) -> Option<ClientRequest> {
+    // Retrieve pointer to Rust Http Struct
+    let js_http_obj = args.this();
+    let internal_field = js_http_obj.get_internal_field(scope, 0).unwrap();
+    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    let http_ptr = unsafe { &*(external_http.value() as *mut Http) };
+
+    let mut options = match RequestOptions::from_js(scope, args.get(0), args.get(1), secure) {
+        Ok(options) => options,
+        Err(e) => {
+            throw_error(scope, &e);
+            return None;
+        }
+    };
+    if force_get {
+        options.method = "GET".to_string();
+    }
+    if let Err(e) = http_ptr.permissions.check_net(&options.host, options.port) {
+        throw_error(scope, &e.to_string());
+        return None;
+    }
+
+    let request = ClientRequest::send(options, http_ptr.tx.clone(), http_ptr.permissions.clone());
+
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok());
+    if let Some(callback) = callback {
+        let callback = v8::Global::new(scope, callback);
+        request.event_emitter.borrow_mut().once("response".to_string(), callback);
+    }
+
+    Some(request)
+}
+
+// http.request(url | options, [options], [callback])
+pub fn create_request_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Some(request) = start_request(scope, &args, false, false) else {
+        return;
+    };
+    let request_obj = create_client_request_object(scope, Box::new(request));
+    rv.set(request_obj.into());
+}
+
+// http.get(url | options, [options], [callback]), a GET request that is ended right away
+pub fn get_request_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Some(mut request) = start_request(scope, &args, true, false) else {
+        return;
+    };
+    let _ = request.end(None, None);
+    let request_obj = create_client_request_object(scope, Box::new(request));
+    rv.set(request_obj.into());
+}
+
+fn client_request_set_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    if request.headers_sent() {
+        throw_error(scope, "Cannot set headers after they are sent to the server");
+        return;
+    }
+
+    let name = args.get(0).to_rust_string_lossy(scope);
+    let values = header_values_from_js(scope, args.get(1));
+    if let Err(e) = validate_header(&name, &values) {
+        throw_error(scope, &e);
+        return;
+    }
+    request.options.headers.set_all(name, values);
+
+    rv.set(args.this().into());
+}
+
+fn client_request_get_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+
+    let values: Vec<&str> = request.options.headers.get_all(&name).collect();
+    rv.set(header_values_to_js(scope, &values));
+}
+
+fn client_request_remove_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    if request.headers_sent() {
+        throw_error(scope, "Cannot remove headers after they are sent to the server");
+        return;
+    }
+
+    let name = args.get(0).to_rust_string_lossy(scope);
+    request.options.headers.remove(&name);
+}
+
+fn client_request_has_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    rv.set(v8::Boolean::new(scope, request.options.headers.contains(&name)).into());
+}
+
+fn client_request_headers_sent_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    rv.set(v8::Boolean::new(scope, request.headers_sent()).into());
+}
+
+// write(chunk, [encoding], [callback])
+fn client_request_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    let chunk = value_to_bytes(scope, args.get(0));
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match request.write(chunk, callback) {
+        Ok(ok) => rv.set(v8::Boolean::new(scope, ok).into()),
+        Err(error_message) => {
+            emit_request_error(scope, request, &error_message);
+            rv.set(v8::Boolean::new(scope, false).into());
+        }
+    }
+}
+
+// end([chunk], [encoding], [callback])
+fn client_request_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    let callback = (0..args.length())
+        .find_map(|i| v8::Local::<v8::Function>::try_from(args.get(i)).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    let mut final_chunk = None;
+    if !args.get(0).is_function() && !args.get(0).is_null_or_undefined() {
+        final_chunk = Some(value_to_bytes(scope, args.get(0)));
+    }
+
+    if let Err(error_message) = request.end(final_chunk, callback) {
+        emit_request_error(scope, request, &error_message);
+    }
+
+    rv.set(args.this().into());
+}
+
+// abort(), emits 'abort' and ends the request without an error
+fn client_request_abort_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    if request.abort() {
+        emit_event(scope, &request.event_emitter, "abort", &[]);
+    }
+}
+
+// destroy([error]), the error is emitted, otherwise "socket hang up" until a response came
+fn client_request_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    let error_message = Some(args.get(0))
+        .filter(|error| !error.is_null_or_undefined())
+        .map(|error| error.to_rust_string_lossy(scope));
+    request.destroy(error_message);
+    rv.set(args.this().into());
+}
+
+fn client_request_destroyed_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let request = get_client_request(scope, args.this());
+    rv.set(v8::Boolean::new(scope, request.destroyed()).into());
+}
+
+pub fn create_client_request_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    request: Box<ClientRequest>
+) -> v8::Local<'s, v8::Object> {
+    let request_template = v8::ObjectTemplate::new(scope);
+    request_template.set_internal_field_count(1);
+    let request_obj = request_template.new_instance(scope).unwrap();
+    attach_event_emitter(scope, request_obj, &request.event_emitter);
+
+    for (name, value) in [("method", &request.options.method), ("path", &request.options.path), ("host", &request.options.host)] {
+        let key = v8::String::new(scope, name).unwrap();
+        let value = v8::String::new(scope, value).unwrap();
+        request_obj.set(scope, key.into(), value.into());
+    }
+
+    set_function(scope, request_obj, "setHeader", client_request_set_header_callback);
+    set_function(scope, request_obj, "getHeader", client_request_get_header_callback);
+    set_function(scope, request_obj, "removeHeader", client_request_remove_header_callback);
+    set_function(scope, request_obj, "hasHeader", client_request_has_header_callback);
+    set_function(scope, request_obj, "write", client_request_write_callback);
+    set_function(scope, request_obj, "end", client_request_end_callback);
+    set_function(scope, request_obj, "abort", client_request_abort_callback);
+    set_function(scope, request_obj, "destroy", client_request_destroy_callback);
+
+    let headers_sent_key = v8::String::new(scope, "headersSent").unwrap();
+    request_obj.set_accessor(scope, headers_sent_key.into(), client_request_headers_sent_getter);
+    let destroyed_key = v8::String::new(scope, "destroyed").unwrap();
+    request_obj.set_accessor(scope, destroyed_key.into(), client_request_destroyed_getter);
+
+    let external_request = v8::External::new(scope, Box::into_raw(request) as *const _ as *mut c_void);
+    request_obj.set_internal_field(0, external_request.into());
+
+    request_obj
+}
+
+pub fn get_client_response<'a>(scope: &mut v8::HandleScope, response_obj: v8::Local<v8::Object>) -> &'a ClientResponse {
+    let internal_field = response_obj.get_internal_field(scope, 0).unwrap();
+    let external_response = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_response.value() as *const ClientResponse) }
+}
+
+fn client_response_complete_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let response = get_client_response(scope, args.this());
+    rv.set(v8::Boolean::new(scope, response.complete.load(Ordering::SeqCst)).into());
+}
+
+// setEncoding([encoding]), 'data' then receives strings instead of Uint8Arrays
+fn client_response_set_encoding_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let response = get_client_response(scope, args.this());
+    let encoding = if args.get(0).is_string() {
+        args.get(0).to_rust_string_lossy(scope)
+    } else {
+        "utf8".to_string()
+    };
+    *response.encoding.lock().unwrap() = Some(encoding);
+    rv.set(args.this().into());
+}
+
+// The head never changes, so it is copied onto the object as plain properties
+pub fn create_client_response_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    response: Box<ClientResponse>
+) -> v8::Local<'s, v8::Object> {
+    let response_template = v8::ObjectTemplate::new(scope);
+    response_template.set_internal_field_count(1);
+    let response_obj = response_template.new_instance(scope).unwrap();
+    attach_event_emitter(scope, response_obj, &response.event_emitter);
+
+    let status_code_key = v8::String::new(scope, "statusCode").unwrap();
+    let status_code_value = v8::Integer::new(scope, response.status_code as i32);
+    response_obj.set(scope, status_code_key.into(), status_code_value.into());
+
+    let status_message_key = v8::String::new(scope, "statusMessage").unwrap();
+    let status_message_value = v8::String::new(scope, &response.status_message).unwrap();
+    response_obj.set(scope, status_message_key.into(), status_message_value.into());
+
+    let http_version_key = v8::String::new(scope, "httpVersion").unwrap();
+    let http_version_value = v8::String::new(scope, &response.http_version).unwrap();
+    response_obj.set(scope, http_version_key.into(), http_version_value.into());
+
+    let headers_key = v8::String::new(scope, "headers").unwrap();
+    let headers_value = headers_to_object(scope, &response.headers);
+    response_obj.set(scope, headers_key.into(), headers_value.into());
+
+    let response_url_key = v8::String::new(scope, "responseUrl").unwrap();
+    let response_url_value = v8::String::new(scope, &response.url).unwrap();
+    response_obj.set(scope, response_url_key.into(), response_url_value.into());
+
+    let raw_headers_key = v8::String::new(scope, "rawHeaders").unwrap();
+    let raw_headers_value = raw_headers_array(scope, &response.headers);
+    response_obj.set(scope, raw_headers_key.into(), raw_headers_value.into());
+
+    let complete_key = v8::String::new(scope, "complete").unwrap();
+    response_obj.set_accessor(scope, complete_key.into(), client_response_complete_getter);
+    set_function(scope, response_obj, "setEncoding", client_response_set_encoding_callback);
+
+    let external_response = v8::External::new(scope, Box::into_raw(response) as *const _ as *mut c_void);
+    response_obj.set_internal_field(0, external_response.into());
+
+    response_obj
+}
@@ -1,15 +1,26 @@
 use rusty_v8 as v8;
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::c_void;
+use std::mem::ManuallyDrop;
+
+struct Listener {
+    callback: v8::Global<v8::Function>,
+    once: bool,
+}
 
 pub struct EventEmitter {
-    listeners: HashMap<String, Vec<v8::Global<v8::Function>>>,
+    listeners: HashMap<String, Vec<Listener>>,
+    // Set once a stream, request or response closed for good, see StreamEvent::Close
+    closed: bool,
 }
 
 impl EventEmitter {
     pub fn new() -> Self {
         EventEmitter {
             listeners: HashMap::new(),
+            closed: false,
         }
     }
 
@@ -18,7 +29,49 @@ impl EventEmitter {
         event: String,
         callback: v8::Global<v8::Function>,
     ) {
-        self.listeners.entry(event).or_insert_with(Vec::new).push(callback);
+        if self.closed {
+            return;
+        }
+        self.listeners.entry(event).or_default().push(Listener { callback, once: false });
+    }
+
+    pub fn once(
+        &mut self,
+        event: String,
+        callback: v8::Global<v8::Function>,
+    ) {
+        if self.closed {
+            return;
+        }
+        self.listeners.entry(event).or_default().push(Listener { callback, once: true });
+    }
+
+    pub fn remove_all_listeners(&mut self, event: Option<&str>) {
+        match event {
+            Some(event) => { self.listeners.remove(event); }
+            None => self.listeners.clear(),
+        }
+    }
+
+    // Releases the listeners, and the JS functions they keep alive, of an object that is done
+    pub fn release(&mut self) {
+        self.closed = true;
+        self.listeners = HashMap::new();
+    }
+
+    pub fn has_listeners(&self, event: &str) -> bool {
+        self.listeners.get(event).is_some_and(|listeners| !listeners.is_empty())
+    }
+
+    // Returns the callbacks to run for an event, dropping the `once` listeners
+    pub fn take_callbacks(&mut self, event: &str) -> Vec<v8::Global<v8::Function>> {
+        let Some(listeners) = self.listeners.get_mut(event) else {
+            return Vec::new();
+        };
+
+        let callbacks = listeners.iter().map(|listener| listener.callback.clone()).collect();
+        listeners.retain(|listener| !listener.once);
+        callbacks
     }
 
     pub fn emit(
@@ -27,13 +80,154 @@ impl EventEmitter {
         event: String,
         args: &[v8::Local<v8::Value>],
     ) {
-
-        if let Some(callbacks) = self.listeners.get_mut(&event) {
-            for callback in callbacks.drain(..) {
-                let local_cb = v8::Local::new(scope, callback);
-                let undefined = v8::undefined(scope).into();
-                local_cb.call(scope, undefined, args);
-            }
+        for callback in self.take_callbacks(&event) {
+            let local_cb = v8::Local::new(scope, callback);
+            let undefined = v8::undefined(scope).into();
+            local_cb.call(scope, undefined, args);
         }
     }
 }
+
+// Emits on a shared emitter without holding the lock while listeners run,
+// so listeners are free to register more listeners on the same object
+pub fn emit_event(
+    scope: &mut v8::HandleScope,
+    emitter: &Rc<RefCell<EventEmitter>>,
+    event: &str,
+    args: &[v8::Local<v8::Value>],
+) {
+    let callbacks = emitter.borrow_mut().take_callbacks(event);
+
+    if callbacks.is_empty() && event == "error" {
+        let message = args.first()
+            .map(|value| value.to_rust_string_lossy(scope))
+            .unwrap_or_default();
+        eprintln!("Unhandled 'error' event: {}", message);
+        return;
+    }
+
+    for callback in callbacks {
+        let local_cb = v8::Local::new(scope, callback);
+        let undefined = v8::undefined(scope).into();
+        local_cb.call(scope, undefined, args);
+    }
+}
+
+// JS bindings, a weak reference to the emitter is bound as the data of each function so
+// the emitter goes away with the Rust object that emits on it
+fn get_emitter(args: &v8::FunctionCallbackArguments) -> Option<Rc<RefCell<EventEmitter>>> {
+    let external = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let emitter = unsafe { Weak::from_raw(external.value() as *const RefCell<EventEmitter>) };
+    ManuallyDrop::new(emitter).upgrade()
+}
+
+fn emitter_on_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let event = args.get(0).to_rust_string_lossy(scope);
+
+    if let (Some(emitter), Ok(callback)) = (get_emitter(&args), v8::Local::<v8::Function>::try_from(args.get(1))) {
+        let callback = v8::Global::new(scope, callback);
+        emitter.borrow_mut().on(event, callback);
+    }
+
+    rv.set(args.this().into());
+}
+
+fn emitter_once_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let event = args.get(0).to_rust_string_lossy(scope);
+
+    if let (Some(emitter), Ok(callback)) = (get_emitter(&args), v8::Local::<v8::Function>::try_from(args.get(1))) {
+        let callback = v8::Global::new(scope, callback);
+        emitter.borrow_mut().once(event, callback);
+    }
+
+    rv.set(args.this().into());
+}
+
+fn emitter_remove_all_listeners_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let event = if args.get(0).is_string() {
+        Some(args.get(0).to_rust_string_lossy(scope))
+    } else {
+        None
+    };
+
+    if let Some(emitter) = get_emitter(&args) {
+        emitter.borrow_mut().remove_all_listeners(event.as_deref());
+    }
+    rv.set(args.this().into());
+}
+
+fn emitter_emit_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let event = args.get(0).to_rust_string_lossy(scope);
+
+    let mut event_args = Vec::new();
+    for i in 1..args.length() {
+        event_args.push(args.get(i));
+    }
+
+    let callbacks = match get_emitter(&args) {
+        Some(emitter) => emitter.borrow_mut().take_callbacks(&event),
+        None => Vec::new(),
+    };
+    let had_listeners = !callbacks.is_empty();
+
+    for callback in callbacks {
+        let local_cb = v8::Local::new(scope, callback);
+        let undefined = v8::undefined(scope).into();
+        local_cb.call(scope, undefined, &event_args);
+    }
+
+    rv.set(v8::Boolean::new(scope, had_listeners).into());
+}
+
+fn set_emitter_method<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    obj: v8::Local<v8::Object>,
+    external_emitter: v8::Local<'s, v8::External>,
+    name: &str,
+    callback: impl v8::MapFnTo<v8::FunctionCallback>,
+) {
+    let function = v8::Function::builder(callback)
+        .data(external_emitter.into())
+        .build(scope)
+        .unwrap();
+    let key = v8::String::new(scope, name).unwrap();
+    obj.set(scope, key.into(), function.into());
+}
+
+// Adds on/once/emit/removeAllListeners to a JS object backed by a Rust EventEmitter
+pub fn attach_event_emitter(
+    scope: &mut v8::HandleScope,
+    obj: v8::Local<v8::Object>,
+    emitter: &Rc<RefCell<EventEmitter>>,
+) {
+    // Never released, the weak reference only keeps the allocation and not the listeners
+    let emitter_ptr = Rc::downgrade(emitter).into_raw();
+    let external_emitter = v8::External::new(scope, emitter_ptr as *const _ as *mut c_void);
+
+    set_emitter_method(scope, obj, external_emitter, "on", emitter_on_callback);
+    set_emitter_method(scope, obj, external_emitter, "once", emitter_once_callback);
+    set_emitter_method(scope, obj, external_emitter, "emit", emitter_emit_callback);
+    set_emitter_method(scope, obj, external_emitter, "removeAllListeners", emitter_remove_all_listeners_callback);
+
+    // Node also exposes addListener as an alias of on
+    let on_key = v8::String::new(scope, "on").unwrap();
+    let on_fn = obj.get(scope, on_key.into()).unwrap();
+    let add_listener_key = v8::String::new(scope, "addListener").unwrap();
+    obj.set(scope, add_listener_key.into(), on_fn);
+}
@@ -0,0 +1,1303 @@
+use rusty_v8 as v8;
+use tokio::sync::mpsc::UnboundedSender;
+use url::Url;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use crate::abort::{create_abort_signal_object, get_abort_signal};
+use crate::client::{get_client_response, ClientRequest, RequestOptions};
+use crate::emitter::EventEmitter;
+use crate::headers::{is_token_byte, validate_header, Headers};
+use crate::helper::{bytes_to_uint8array, call_method, define_class, is_instance, new_instance, require_new, set_function, throw_type_error, value_to_bytes};
+use crate::http::Http;
+use crate::interface::Operations;
+use crate::permissions::Permissions;
+
+// fetch() gives up after this many redirects, like browsers do
+const MAX_REDIRECTS: usize = 20;
+
+// Statuses whose responses never have a body
+const NULL_BODY_STATUSES: [u16; 4] = [101, 204, 205, 304];
+
+// What a body is being read as
+#[derive(Clone, Copy)]
+enum Consume {
+    Text,
+    Json,
+    ArrayBuffer,
+    Read,
+}
+
+// The body of a Request or Response. Fetched bodies fill in while the response streams, so
+// text() and friends or reader.read() wait here until something can settle their promise
+pub struct Body {
+    chunks: VecDeque<Vec<u8>>,
+    done: bool,
+    error: Option<v8::Global<v8::Value>>,
+    used: bool,
+    waiting: Vec<(v8::Global<v8::PromiseResolver>, Consume)>,
+}
+
+type SharedBody = Rc<RefCell<Body>>;
+
+impl Body {
+    fn complete(bytes: Vec<u8>) -> SharedBody {
+        let mut chunks = VecDeque::new();
+        if !bytes.is_empty() {
+            chunks.push_back(bytes);
+        }
+        Rc::new(RefCell::new(Body { chunks, done: true, error: None, used: false, waiting: Vec::new() }))
+    }
+
+    fn streaming() -> SharedBody {
+        Rc::new(RefCell::new(Body { chunks: VecDeque::new(), done: false, error: None, used: false, waiting: Vec::new() }))
+    }
+
+    // The whole body so far, leaving it in place
+    fn bytes(&self) -> Vec<u8> {
+        self.chunks.iter().flatten().copied().collect()
+    }
+}
+
+// text(), json() and arrayBuffer() read everything once the body is complete
+fn consume_body<'s>(scope: &mut v8::HandleScope<'s>, body: &SharedBody, consume: Consume) -> v8::Local<'s, v8::Promise> {
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let promise = resolver.get_promise(scope);
+
+    let mut state = body.borrow_mut();
+    if state.used {
+        drop(state);
+        let message = v8::String::new(scope, "Body is unusable: Body has already been read").unwrap();
+        let exception = v8::Exception::type_error(scope, message);
+        resolver.reject(scope, exception);
+        return promise;
+    }
+    state.used = true;
+
+    if let Some(error) = &state.error {
+        let error = v8::Local::new(scope, error);
+        drop(state);
+        resolver.reject(scope, error);
+    } else if state.done {
+        let bytes = state.bytes();
+        state.chunks.clear();
+        drop(state);
+        settle_body(scope, resolver, consume, Some(bytes));
+    } else {
+        state.waiting.push((v8::Global::new(scope, resolver), consume));
+    }
+    promise
+}
+
+// reader.read(), one chunk at a time
+fn read_body_chunk<'s>(scope: &mut v8::HandleScope<'s>, body: &SharedBody) -> v8::Local<'s, v8::Promise> {
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let promise = resolver.get_promise(scope);
+
+    let mut state = body.borrow_mut();
+    if let Some(chunk) = state.chunks.pop_front() {
+        drop(state);
+        settle_body(scope, resolver, Consume::Read, Some(chunk));
+    } else if let Some(error) = &state.error {
+        let error = v8::Local::new(scope, error);
+        drop(state);
+        resolver.reject(scope, error);
+    } else if state.done {
+        drop(state);
+        settle_body(scope, resolver, Consume::Read, None);
+    } else {
+        state.waiting.push((v8::Global::new(scope, resolver), Consume::Read));
+    }
+    promise
+}
+
+fn settle_body(scope: &mut v8::HandleScope, resolver: v8::Local<v8::PromiseResolver>, consume: Consume, bytes: Option<Vec<u8>>) {
+    let bytes_or_empty = bytes.clone().unwrap_or_default();
+    match consume {
+        Consume::Text => {
+            let text = v8::String::new(scope, &String::from_utf8_lossy(&bytes_or_empty)).unwrap();
+            resolver.resolve(scope, text.into());
+        }
+        Consume::Json => {
+            let try_catch = &mut v8::TryCatch::new(scope);
+            let text = v8::String::new(try_catch, &String::from_utf8_lossy(&bytes_or_empty)).unwrap();
+            match v8::json::parse(try_catch, text) {
+                Some(value) => { resolver.resolve(try_catch, value); }
+                None => {
+                    let exception = try_catch.exception().unwrap();
+                    resolver.reject(try_catch, exception);
+                }
+            }
+        }
+        Consume::ArrayBuffer => {
+            let array = bytes_to_uint8array(scope, bytes_or_empty);
+            let array_buffer = array.buffer(scope).unwrap();
+            resolver.resolve(scope, array_buffer.into());
+        }
+        Consume::Read => {
+            let result = read_result(scope, bytes);
+            resolver.resolve(scope, result.into());
+        }
+    }
+}
+
+// { value, done } of a reader, done once there are no more chunks
+fn read_result<'s>(scope: &mut v8::HandleScope<'s>, chunk: Option<Vec<u8>>) -> v8::Local<'s, v8::Object> {
+    let result = v8::Object::new(scope);
+    let done = chunk.is_none();
+    let value: v8::Local<v8::Value> = match chunk {
+        Some(chunk) => bytes_to_uint8array(scope, chunk).into(),
+        None => v8::undefined(scope).into(),
+    };
+    let value_key = v8::String::new(scope, "value").unwrap();
+    result.set(scope, value_key.into(), value);
+    let done_key = v8::String::new(scope, "done").unwrap();
+    let done_value = v8::Boolean::new(scope, done);
+    result.set(scope, done_key.into(), done_value.into());
+    result
+}
+
+// A chunk from the network goes to a waiting read() or is queued
+fn push_body_chunk(scope: &mut v8::HandleScope, body: &SharedBody, chunk: Vec<u8>) {
+    let mut state = body.borrow_mut();
+    if state.done || state.error.is_some() {
+        return;
+    }
+    let reader = state.waiting.iter().position(|(_, consume)| matches!(consume, Consume::Read));
+    match reader {
+        Some(index) => {
+            let (resolver, _) = state.waiting.remove(index);
+            drop(state);
+            let resolver = v8::Local::new(scope, resolver);
+            settle_body(scope, resolver, Consume::Read, Some(chunk));
+        }
+        None => state.chunks.push_back(chunk),
+    }
+}
+
+fn finish_body(scope: &mut v8::HandleScope, body: &SharedBody) {
+    let mut state = body.borrow_mut();
+    if state.done || state.error.is_some() {
+        return;
+    }
+    state.done = true;
+    let waiting = std::mem::take(&mut state.waiting);
+    let bytes = state.bytes();
+    if waiting.iter().any(|(_, consume)| !matches!(consume, Consume::Read)) {
+        state.chunks.clear();
+    }
+    drop(state);
+
+    for (resolver, consume) in waiting {
+        let resolver = v8::Local::new(scope, resolver);
+        let bytes = match consume {
+            Consume::Read => None,
+            _ => Some(bytes.clone()),
+        };
+        settle_body(scope, resolver, consume, bytes);
+    }
+}
+
+fn fail_body(scope: &mut v8::HandleScope, body: &SharedBody, error: v8::Local<v8::Value>) {
+    let mut state = body.borrow_mut();
+    if state.done || state.error.is_some() {
+        return;
+    }
+    state.error = Some(v8::Global::new(scope, error));
+    let waiting = std::mem::take(&mut state.waiting);
+    drop(state);
+
+    for (resolver, _) in waiting {
+        let resolver = v8::Local::new(scope, resolver);
+        resolver.reject(scope, error);
+    }
+}
+
+// A TypeError with the underlying error as its `cause`, how fetch() reports network errors
+fn type_error_with_cause<'s>(scope: &mut v8::HandleScope<'s>, message: &str, cause: &str) -> v8::Local<'s, v8::Value> {
+    let message = v8::String::new(scope, message).unwrap();
+    let error = v8::Exception::type_error(scope, message);
+    let cause_message = v8::String::new(scope, cause).unwrap();
+    let cause_value = v8::Exception::error(scope, cause_message);
+    let error_obj = v8::Local::<v8::Object>::try_from(error).unwrap();
+    let cause_key = v8::String::new(scope, "cause").unwrap();
+    error_obj.set(scope, cause_key.into(), cause_value);
+    error
+}
+
+// Headers keep lowercase names, which is how they are listed and sent
+fn append_header(headers: &mut Headers, name: &str, value: &str) -> Result<(), String> {
+    let value = value.trim_matches(|c| matches!(c, ' ' | '\t' | '\r' | '\n')).to_string();
+    validate_header(name, std::slice::from_ref(&value))?;
+    headers.append(name.to_ascii_lowercase(), value);
+    Ok(())
+}
+
+fn lowercase_headers(headers: &Headers) -> Headers {
+    let mut lowercase = Headers::new();
+    for (name, value) in headers.iter() {
+        lowercase.append(name.to_ascii_lowercase(), value.to_string());
+    }
+    lowercase
+}
+
+// new Headers(init) accepts another Headers, an array of [name, value] pairs or a record
+fn headers_from_init(scope: &mut v8::HandleScope, init: v8::Local<v8::Value>) -> Result<Headers, String> {
+    let mut headers = Headers::new();
+    if init.is_null_or_undefined() {
+        return Ok(headers);
+    }
+    if is_instance(scope, init, "Headers") {
+        let init = v8::Local::<v8::Object>::try_from(init).unwrap();
+        return Ok(get_headers(scope, init).clone());
+    }
+
+    if let Ok(pairs) = v8::Local::<v8::Array>::try_from(init) {
+        for i in 0..pairs.length() {
+            let pair = pairs.get_index(scope, i).unwrap();
+            let pair = v8::Local::<v8::Array>::try_from(pair).ok()
+                .filter(|pair| pair.length() == 2)
+                .ok_or_else(|| "Header pairs must contain exactly two items".to_string())?;
+            let name = pair.get_index(scope, 0).unwrap().to_rust_string_lossy(scope);
+            let value = pair.get_index(scope, 1).unwrap().to_rust_string_lossy(scope);
+            append_header(&mut headers, &name, &value)?;
+        }
+        return Ok(headers);
+    }
+
+    let Ok(record) = v8::Local::<v8::Object>::try_from(init) else {
+        return Err("Headers init must be an object or an array of pairs".to_string());
+    };
+    let names = record.get_own_property_names(scope).unwrap();
+    for i in 0..names.length() {
+        let name = names.get_index(scope, i).unwrap();
+        let value = record.get(scope, name).unwrap();
+        let name = name.to_rust_string_lossy(scope);
+        let value = value.to_rust_string_lossy(scope);
+        append_header(&mut headers, &name, &value)?;
+    }
+    Ok(headers)
+}
+
+// Name order with repeated headers combined, except Set-Cookie which is never joined
+fn sorted_entries(headers: &Headers) -> Vec<(String, String)> {
+    let mut names: Vec<&str> = headers.iter().map(|(name, _)| name).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let mut entries = Vec::new();
+    for name in names {
+        if name == "set-cookie" {
+            entries.extend(headers.get_all(name).map(|value| (name.to_string(), value.to_string())));
+        } else if let Some(value) = headers.get_joined(name) {
+            entries.push((name.to_string(), value));
+        }
+    }
+    entries
+}
+
+// A request or response body from JS, strings add a default Content-Type
+fn body_from_js(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<(Vec<u8>, Option<&'static str>)> {
+    if value.is_null_or_undefined() {
+        return None;
+    }
+    if value.is_array_buffer_view() || value.is_array_buffer() {
+        return Some((value_to_bytes(scope, value), None));
+    }
+    Some((value_to_bytes(scope, value), Some("text/plain;charset=UTF-8")))
+}
+
+fn option<'s>(scope: &mut v8::HandleScope<'s>, init: Option<v8::Local<v8::Object>>, name: &str) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name).unwrap();
+    init?.get(scope, key.into()).filter(|value| !value.is_undefined())
+}
+
+// Everything a request is made of, from new Request() or the arguments of fetch()
+struct RequestParts {
+    url: Url,
+    method: String,
+    headers: Headers,
+    body: Option<Vec<u8>>,
+    redirect: String,
+    signal: Option<v8::Global<v8::Object>>,
+}
+
+impl RequestParts {
+    // (input, [init]) where input is a URL or a Request, init overrides it
+    fn from_js(scope: &mut v8::HandleScope, input: v8::Local<v8::Value>, init: v8::Local<v8::Value>) -> Result<Self, String> {
+        let mut parts = match is_instance(scope, input, "Request") {
+            true => {
+                let request_obj = v8::Local::<v8::Object>::try_from(input).unwrap();
+                if get_body(scope, request_obj).borrow().used {
+                    return Err("Cannot construct a Request with a Request object that has already been used".to_string());
+                }
+                RequestParts::from_request(scope, request_obj)
+            }
+            false => {
+                let url = input.to_rust_string_lossy(scope);
+                let url = Url::parse(&url).map_err(|_| format!("Failed to parse URL from {}", url))?;
+                RequestParts { url, method: "GET".to_string(), headers: Headers::new(), body: None, redirect: "follow".to_string(), signal: None }
+            }
+        };
+
+        let init = v8::Local::<v8::Object>::try_from(init).ok();
+        if let Some(method) = option(scope, init, "method") {
+            let method = method.to_rust_string_lossy(scope);
+            if method.is_empty() || !method.bytes().all(is_token_byte) {
+                return Err(format!("'{}' is not a valid HTTP method", method));
+            }
+            let upper = method.to_ascii_uppercase();
+            if matches!(upper.as_str(), "CONNECT" | "TRACE" | "TRACK") {
+                return Err(format!("'{}' HTTP method is unsupported", method));
+            }
+            // Only the standard methods are normalized, others keep their case
+            parts.method = match upper.as_str() {
+                "DELETE" | "GET" | "HEAD" | "OPTIONS" | "POST" | "PUT" => upper,
+                _ => method,
+            };
+        }
+
+        if let Some(headers) = option(scope, init, "headers") {
+            parts.headers = headers_from_init(scope, headers)?;
+        }
+
+        if let Some(body) = option(scope, init, "body") {
+            parts.body = match body_from_js(scope, body) {
+                Some((bytes, content_type)) => {
+                    if let Some(content_type) = content_type.filter(|_| !parts.headers.contains("content-type")) {
+                        parts.headers.append("content-type".to_string(), content_type.to_string());
+                    }
+                    Some(bytes)
+                }
+                None => None,
+            };
+        }
+
+        if let Some(redirect) = option(scope, init, "redirect") {
+            let redirect = redirect.to_rust_string_lossy(scope);
+            if !matches!(redirect.as_str(), "follow" | "error" | "manual") {
+                return Err(format!("'{}' is not a valid redirect mode", redirect));
+            }
+            parts.redirect = redirect;
+        }
+
+        if let Some(signal) = option(scope, init, "signal") {
+            parts.signal = match signal.is_null() {
+                true => None,
+                false if is_instance(scope, signal, "AbortSignal") => {
+                    let signal = v8::Local::<v8::Object>::try_from(signal).unwrap();
+                    Some(v8::Global::new(scope, signal))
+                }
+                false => return Err("Failed to construct 'Request': member signal is not of type AbortSignal".to_string()),
+            };
+        }
+
+        if parts.body.is_some() && matches!(parts.method.as_str(), "GET" | "HEAD") {
+            return Err("Request with GET/HEAD method cannot have body".to_string());
+        }
+        Ok(parts)
+    }
+
+    // A copy of an existing Request, read back from its properties
+    fn from_request(scope: &mut v8::HandleScope, request_obj: v8::Local<v8::Object>) -> Self {
+        let property = |scope: &mut v8::HandleScope, name: &str| {
+            let key = v8::String::new(scope, name).unwrap();
+            request_obj.get(scope, key.into()).unwrap().to_rust_string_lossy(scope)
+        };
+        let url = Url::parse(&property(scope, "url")).unwrap();
+        let method = property(scope, "method");
+        let redirect = property(scope, "redirect");
+
+        let headers_key = v8::String::new(scope, "headers").unwrap();
+        let headers_obj = request_obj.get(scope, headers_key.into()).unwrap();
+        let headers = headers_from_init(scope, headers_obj).unwrap_or_default();
+
+        let signal_key = v8::String::new(scope, "signal").unwrap();
+        let signal = request_obj.get(scope, signal_key.into())
+            .and_then(|signal| v8::Local::<v8::Object>::try_from(signal).ok())
+            .map(|signal| v8::Global::new(scope, signal));
+
+        let body = get_body(scope, request_obj).borrow().bytes();
+        let body = Some(body).filter(|_| !matches!(method.as_str(), "GET" | "HEAD"));
+
+        RequestParts { url, method, headers, body, redirect, signal }
+    }
+}
+
+// A request in flight. Each redirect is a new ClientRequest, a hop, and only events of the
+// current hop count. The state is freed once every hop has closed
+struct Fetch {
+    resolver: Option<v8::Global<v8::PromiseResolver>>,
+    tx: UnboundedSender<Operations>,
+    permissions: Arc<Permissions>,
+    url: Url,
+    method: String,
+    headers: Headers,
+    body: Option<Vec<u8>>,
+    redirect: String,
+    redirects: usize,
+    hop: i32,
+    open_hops: usize,
+    request: Option<ClientRequest>,
+    response_body: Option<SharedBody>,
+    signal: Option<v8::Global<v8::Object>>,
+    abort_listener: Option<v8::Global<v8::Function>>,
+}
+
+impl Fetch {
+    fn reject(&mut self, scope: &mut v8::HandleScope, error: v8::Local<v8::Value>) {
+        if let Some(resolver) = self.resolver.take() {
+            let resolver = v8::Local::new(scope, resolver);
+            resolver.reject(scope, error);
+        }
+    }
+
+    fn fail(&mut self, scope: &mut v8::HandleScope, cause: &str) {
+        let error = type_error_with_cause(scope, "fetch failed", cause);
+        self.reject(scope, error);
+        if let Some(request) = &self.request {
+            request.abort();
+        }
+    }
+}
+
+// Sends the current URL as a new hop, aborting the previous one
+fn start_hop(scope: &mut v8::HandleScope, fetch_ptr: *mut Fetch) -> Result<(), String> {
+    let fetch = unsafe { &mut *fetch_ptr };
+
+    let mut options = RequestOptions::from_url(fetch.url.as_str())?;
+    fetch.permissions.check_net(&options.host, options.port).map_err(|e| e.to_string())?;
+    options.method = fetch.method.clone();
+    options.headers = fetch.headers.clone();
+    if !options.headers.contains("accept") {
+        options.headers.set("accept".to_string(), "*/*".to_string());
+    }
+
+    let mut request = ClientRequest::send(options, fetch.tx.clone(), fetch.permissions.clone());
+    fetch.hop += 1;
+    fetch.open_hops += 1;
+
+    let external_fetch = v8::External::new(scope, fetch_ptr as *mut c_void);
+    let hop = v8::Integer::new(scope, fetch.hop);
+    let data = v8::Array::new_with_elements(scope, &[external_fetch.into(), hop.into()]);
+    let emitter = &request.event_emitter;
+    add_listener(scope, emitter, "response", fetch_response_listener, data.into());
+    add_listener(scope, emitter, "error", fetch_error_listener, data.into());
+    add_listener(scope, emitter, "close", fetch_close_listener, data.into());
+
+    let _ = request.end(fetch.body.clone(), None);
+    if let Some(previous) = fetch.request.replace(request) {
+        previous.abort();
+    }
+    Ok(())
+}
+
+// A native listener with the hop's data. Each event fires at most once per request or response
+fn add_listener<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    emitter: &Rc<RefCell<EventEmitter>>,
+    event: &str,
+    callback: impl v8::MapFnTo<v8::FunctionCallback>,
+    data: v8::Local<'s, v8::Value>
+) {
+    let listener = v8::Function::builder(callback).data(data).build(scope).unwrap();
+    let listener = v8::Global::new(scope, listener);
+    emitter.borrow_mut().once(event.to_string(), listener);
+}
+
+// The fetch behind a hop listener, and whether the hop is still the current one
+fn hop_fetch<'a>(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> (&'a mut Fetch, bool) {
+    let data = v8::Local::<v8::Array>::try_from(args.data().unwrap()).unwrap();
+    let external_fetch = data.get_index(scope, 0).unwrap();
+    let external_fetch = v8::Local::<v8::External>::try_from(external_fetch).unwrap();
+    let fetch = unsafe { &mut *(external_fetch.value() as *mut Fetch) };
+    let hop = data.get_index(scope, 1).unwrap().int32_value(scope).unwrap_or(0);
+    let current = hop == fetch.hop;
+    (fetch, current)
+}
+
+// Where a redirect response points, relative to the URL that was requested
+fn redirect_target(fetch: &Fetch, status_code: u16, headers: &Headers) -> Option<Result<Url, String>> {
+    if !matches!(status_code, 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = headers.get("Location")?;
+    Some(fetch.url.join(location).map_err(|_| format!("Invalid redirect location {}", location)))
+}
+
+fn follow_redirect(scope: &mut v8::HandleScope, fetch_ptr: *mut Fetch, status_code: u16, target: Url) -> Result<(), String> {
+    let fetch = unsafe { &mut *fetch_ptr };
+    if fetch.redirects >= MAX_REDIRECTS {
+        return Err("redirect count exceeded".to_string());
+    }
+    fetch.redirects += 1;
+
+    // 303 and the historical POST behaviour of 301/302 turn the request into a GET without a body
+    if (status_code == 303 && !matches!(fetch.method.as_str(), "GET" | "HEAD"))
+        || (matches!(status_code, 301 | 302) && fetch.method == "POST") {
+        fetch.method = "GET".to_string();
+        fetch.body = None;
+        for name in ["content-type", "content-length", "content-encoding", "content-language", "content-location"] {
+            fetch.headers.remove(name);
+        }
+    }
+
+    // Credentials are not sent to another origin
+    if target.origin() != fetch.url.origin() {
+        for name in ["authorization", "proxy-authorization", "cookie"] {
+            fetch.headers.remove(name);
+        }
+    }
+
+    fetch.url = target;
+    start_hop(scope, fetch_ptr)
+}
+
+fn fetch_response_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, current) = hop_fetch(scope, &args);
+    if !current || fetch.resolver.is_none() {
+        return;
+    }
+    let response_obj = v8::Local::<v8::Object>::try_from(args.get(0)).unwrap();
+    let response = get_client_response(scope, response_obj);
+
+    if let Some(target) = redirect_target(fetch, response.status_code, &response.headers) {
+        match fetch.redirect.as_str() {
+            "error" => return fetch.fail(scope, "unexpected redirect"),
+            "follow" => {
+                let result = target.and_then(|target| follow_redirect(scope, fetch, response.status_code, target));
+                if let Err(e) = result {
+                    fetch.fail(scope, &e);
+                }
+                return;
+            }
+            // "manual" hands the redirect itself to the caller
+            _ => {}
+        }
+    }
+
+    let body = Body::streaming();
+    fetch.response_body = Some(body.clone());
+    let data = v8::Local::new(scope, args.data().unwrap());
+    let emitter = &response.event_emitter;
+    add_listener(scope, emitter, "data", fetch_data_listener, data);
+    add_listener(scope, emitter, "end", fetch_end_listener, data);
+    add_listener(scope, emitter, "aborted", fetch_aborted_listener, data);
+    add_listener(scope, emitter, "error", fetch_body_error_listener, data);
+
+    let has_body = fetch.method != "HEAD" && !NULL_BODY_STATUSES.contains(&response.status_code);
+    let head = ResponseHead {
+        status: response.status_code,
+        status_text: response.status_message.clone(),
+        headers: lowercase_headers(&response.headers),
+        url: fetch.url.to_string(),
+        redirected: fetch.redirects > 0,
+        kind: "basic",
+    };
+    let fetch_response_obj = create_response_object(scope, head, body, has_body);
+    if let Some(resolver) = fetch.resolver.take() {
+        let resolver = v8::Local::new(scope, resolver);
+        resolver.resolve(scope, fetch_response_obj.into());
+    }
+}
+
+// The connection failed before there was a response
+fn fetch_error_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, current) = hop_fetch(scope, &args);
+    if current {
+        let cause = args.get(0).to_rust_string_lossy(scope);
+        let error = type_error_with_cause(scope, "fetch failed", &cause);
+        fetch.reject(scope, error);
+    }
+}
+
+fn fetch_close_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, _) = hop_fetch(scope, &args);
+    fetch.open_hops -= 1;
+    if fetch.open_hops == 0 {
+        finish_fetch(scope, fetch);
+    }
+}
+
+fn fetch_data_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, _) = hop_fetch(scope, &args);
+    if let Some(body) = &fetch.response_body {
+        let chunk = value_to_bytes(scope, args.get(0));
+        push_body_chunk(scope, body, chunk);
+    }
+}
+
+fn fetch_end_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, _) = hop_fetch(scope, &args);
+    if let Some(body) = &fetch.response_body {
+        finish_body(scope, body);
+    }
+}
+
+// The server closed the connection in the middle of the body
+fn fetch_aborted_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, _) = hop_fetch(scope, &args);
+    if let Some(body) = &fetch.response_body {
+        let error = type_error_with_cause(scope, "terminated", "other side closed");
+        fail_body(scope, body, error);
+    }
+}
+
+fn fetch_body_error_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let (fetch, _) = hop_fetch(scope, &args);
+    if let Some(body) = &fetch.response_body {
+        let cause = args.get(0).to_rust_string_lossy(scope);
+        let error = type_error_with_cause(scope, "terminated", &cause);
+        fail_body(scope, body, error);
+    }
+}
+
+// The signal rejects the promise, or errors the body if the response already arrived
+fn fetch_abort_listener(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let external_fetch = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let fetch = unsafe { &mut *(external_fetch.value() as *mut Fetch) };
+    let Some(signal) = fetch.signal.as_ref().map(|signal| v8::Local::new(scope, signal)) else {
+        return;
+    };
+    let reason = get_abort_signal(scope, signal.into()).unwrap().reason(scope);
+
+    if fetch.resolver.is_some() {
+        fetch.reject(scope, reason);
+    } else if let Some(body) = &fetch.response_body {
+        fail_body(scope, body, reason);
+    }
+    if let Some(request) = &fetch.request {
+        request.abort();
+    }
+}
+
+// Every hop closed, nothing refers to the state anymore
+fn finish_fetch(scope: &mut v8::HandleScope, fetch: &mut Fetch) {
+    if let (Some(signal), Some(listener)) = (&fetch.signal, &fetch.abort_listener) {
+        let signal = v8::Local::new(scope, signal);
+        let listener = v8::Local::new(scope, listener);
+        if let Some(signal) = get_abort_signal(scope, signal.into()) {
+            signal.remove_listener(scope, listener);
+        }
+    }
+    let error = type_error_with_cause(scope, "fetch failed", "socket hang up");
+    fetch.reject(scope, error);
+    drop(unsafe { Box::from_raw(fetch as *mut Fetch) });
+}
+
+// fetch(input, [init])
+fn fetch_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let external_http = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let http = unsafe { &*(external_http.value() as *const Http) };
+
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    rv.set(resolver.get_promise(scope).into());
+
+    // Invalid arguments reject instead of throwing
+    let parts = match RequestParts::from_js(scope, args.get(0), args.get(1)) {
+        Ok(parts) => parts,
+        Err(e) => {
+            let message = v8::String::new(scope, &e).unwrap();
+            let exception = v8::Exception::type_error(scope, message);
+            resolver.reject(scope, exception);
+            return;
+        }
+    };
+
+    if let Some(signal) = &parts.signal {
+        let signal = v8::Local::new(scope, signal);
+        let signal = get_abort_signal(scope, signal.into()).unwrap();
+        if signal.aborted() {
+            let reason = signal.reason(scope);
+            resolver.reject(scope, reason);
+            return;
+        }
+    }
+
+    let fetch = Box::new(Fetch {
+        resolver: Some(v8::Global::new(scope, resolver)),
+        tx: http.tx.clone(),
+        permissions: http.permissions.clone(),
+        url: parts.url,
+        method: parts.method,
+        headers: parts.headers,
+        body: parts.body,
+        redirect: parts.redirect,
+        redirects: 0,
+        hop: 0,
+        open_hops: 0,
+        request: None,
+        response_body: None,
+        signal: parts.signal,
+        abort_listener: None,
+    });
+    let fetch_ptr = Box::into_raw(fetch);
+    let fetch = unsafe { &mut *fetch_ptr };
+
+    if let Err(e) = start_hop(scope, fetch_ptr) {
+        let error = type_error_with_cause(scope, "fetch failed", &e);
+        fetch.reject(scope, error);
+        drop(unsafe { Box::from_raw(fetch_ptr) });
+        return;
+    }
+
+    if let Some(signal) = fetch.signal.as_ref().map(|signal| v8::Local::new(scope, signal)) {
+        let external_fetch = v8::External::new(scope, fetch_ptr as *mut c_void);
+        let listener = v8::Function::builder(fetch_abort_listener).data(external_fetch.into()).build(scope).unwrap();
+        let listener = v8::Global::new(scope, listener);
+        get_abort_signal(scope, signal.into()).unwrap().add_listener(listener.clone());
+        fetch.abort_listener = Some(listener);
+    }
+}
+
+// V8 Callbacks
+fn get_headers<'a>(scope: &mut v8::HandleScope, headers_obj: v8::Local<v8::Object>) -> &'a mut Headers {
+    let internal_field = headers_obj.get_internal_field(scope, 0).unwrap();
+    let external_headers = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_headers.value() as *mut Headers) }
+}
+
+fn create_headers_object<'s>(scope: &mut v8::HandleScope<'s>, headers: Headers) -> v8::Local<'s, v8::Object> {
+    let headers_obj = new_instance(scope, "Headers");
+    let external_headers = v8::External::new(scope, Box::into_raw(Box::new(headers)) as *mut c_void);
+    headers_obj.set_internal_field(0, external_headers.into());
+    headers_obj
+}
+
+// new Headers([init])
+fn headers_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "Headers") {
+        return;
+    }
+    match headers_from_init(scope, args.get(0)) {
+        Ok(headers) => rv.set(create_headers_object(scope, headers).into()),
+        Err(e) => throw_type_error(scope, &e),
+    }
+}
+
+fn headers_append_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    let value = args.get(1).to_rust_string_lossy(scope);
+    if let Err(e) = append_header(headers, &name, &value) {
+        throw_type_error(scope, &e);
+    }
+}
+
+fn headers_set_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    let value = args.get(1).to_rust_string_lossy(scope);
+
+    let mut replacement = Headers::new();
+    if let Err(e) = append_header(&mut replacement, &name, &value) {
+        throw_type_error(scope, &e);
+        return;
+    }
+    let (name, value) = replacement.iter().next().map(|(name, value)| (name.to_string(), value.to_string())).unwrap();
+    headers.set(name, value);
+}
+
+// get(name), every value joined with ", " or null
+fn headers_get_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    match headers.get_joined(&name) {
+        Some(value) => rv.set(v8::String::new(scope, &value).unwrap().into()),
+        None => rv.set(v8::null(scope).into()),
+    }
+}
+
+fn headers_has_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    rv.set(v8::Boolean::new(scope, headers.contains(&name)).into());
+}
+
+fn headers_delete_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    headers.remove(&name);
+}
+
+fn headers_get_set_cookie_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let headers = get_headers(scope, args.this());
+    let cookies: Vec<v8::Local<v8::Value>> = headers.get_all("set-cookie")
+        .map(|cookie| v8::String::new(scope, cookie).unwrap().into())
+        .collect();
+    rv.set(v8::Array::new_with_elements(scope, &cookies).into());
+}
+
+// forEach(callback, [thisArg]) with (value, name, headers)
+fn headers_for_each_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let Ok(callback) = v8::Local::<v8::Function>::try_from(args.get(0)) else {
+        throw_type_error(scope, "The \"callback\" argument must be of type function");
+        return;
+    };
+    let entries = sorted_entries(get_headers(scope, args.this()));
+    for (name, value) in entries {
+        let name = v8::String::new(scope, &name).unwrap();
+        let value = v8::String::new(scope, &value).unwrap();
+        if callback.call(scope, args.get(1), &[value.into(), name.into(), args.this().into()]).is_none() {
+            return;
+        }
+    }
+}
+
+// keys(), values() and entries() iterate over a snapshot, like an array does
+fn headers_iterator<'s>(scope: &mut v8::HandleScope<'s>, headers_obj: v8::Local<v8::Object>, kind: &str) -> Option<v8::Local<'s, v8::Value>> {
+    let entries = sorted_entries(get_headers(scope, headers_obj));
+    let elements: Vec<v8::Local<v8::Value>> = entries.iter()
+        .map(|(name, value)| {
+            let name: v8::Local<v8::Value> = v8::String::new(scope, name).unwrap().into();
+            let value: v8::Local<v8::Value> = v8::String::new(scope, value).unwrap().into();
+            match kind {
+                "keys" => name,
+                "values" => value,
+                _ => v8::Array::new_with_elements(scope, &[name, value]).into(),
+            }
+        })
+        .collect();
+    let array = v8::Array::new_with_elements(scope, &elements);
+    call_method(scope, array.into(), "values", &[])
+}
+
+fn headers_keys_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(iterator) = headers_iterator(scope, args.this(), "keys") {
+        rv.set(iterator);
+    }
+}
+
+fn headers_values_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(iterator) = headers_iterator(scope, args.this(), "values") {
+        rv.set(iterator);
+    }
+}
+
+fn headers_entries_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(iterator) = headers_iterator(scope, args.this(), "entries") {
+        rv.set(iterator);
+    }
+}
+
+// Requests and responses keep their body in the internal field, the rest are properties
+fn get_body<'a>(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>) -> &'a SharedBody {
+    let internal_field = obj.get_internal_field(scope, 0).unwrap();
+    let external_body = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_body.value() as *const SharedBody) }
+}
+
+fn set_body(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, body: SharedBody) -> *const SharedBody {
+    let body_ptr = Box::into_raw(Box::new(body));
+    let external_body = v8::External::new(scope, body_ptr as *mut c_void);
+    obj.set_internal_field(0, external_body.into());
+
+    let body_used_key = v8::String::new(scope, "bodyUsed").unwrap();
+    obj.set_accessor(scope, body_used_key.into(), body_used_getter);
+    body_ptr
+}
+
+fn set_property(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str, value: v8::Local<v8::Value>) {
+    let key = v8::String::new(scope, name).unwrap();
+    obj.set(scope, key.into(), value);
+}
+
+fn body_used_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let used = get_body(scope, args.this()).borrow().used;
+    rv.set(v8::Boolean::new(scope, used).into());
+}
+
+fn body_text_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    rv.set(consume_body(scope, body, Consume::Text).into());
+}
+
+fn body_json_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    rv.set(consume_body(scope, body, Consume::Json).into());
+}
+
+fn body_array_buffer_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    rv.set(consume_body(scope, body, Consume::ArrayBuffer).into());
+}
+
+// The `body` stream and its reader share the response's body
+fn stream_get_reader_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    let mut state = body.borrow_mut();
+    if state.used {
+        drop(state);
+        throw_type_error(scope, "ReadableStream is locked or the body has already been read");
+        return;
+    }
+    state.used = true;
+    drop(state);
+
+    let reader_obj = create_body_object(scope, body);
+    set_function(scope, reader_obj, "read", reader_read_callback);
+    set_function(scope, reader_obj, "cancel", reader_cancel_callback);
+    set_function(scope, reader_obj, "releaseLock", reader_release_lock_callback);
+    // `for await (const chunk of response.body)` reads through the same reader
+    set_function(scope, reader_obj, "next", reader_read_callback);
+    set_function(scope, reader_obj, "return", reader_cancel_callback);
+    rv.set(reader_obj.into());
+}
+
+fn reader_read_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    rv.set(read_body_chunk(scope, body).into());
+}
+
+// Drops whatever was not read yet, pending reads finish as done
+fn reader_cancel_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let body = get_body(scope, args.this());
+    body.borrow_mut().chunks.clear();
+    finish_body(scope, body);
+
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let result = read_result(scope, None);
+    resolver.resolve(scope, result.into());
+    rv.set(resolver.get_promise(scope).into());
+}
+
+fn reader_release_lock_callback(
+    _scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+}
+
+fn create_body_object<'s>(scope: &mut v8::HandleScope<'s>, body: &SharedBody) -> v8::Local<'s, v8::Object> {
+    let template = v8::ObjectTemplate::new(scope);
+    template.set_internal_field_count(1);
+    let obj = template.new_instance(scope).unwrap();
+    let external_body = v8::External::new(scope, Box::into_raw(Box::new(body.clone())) as *mut c_void);
+    obj.set_internal_field(0, external_body.into());
+    obj
+}
+
+fn create_stream_object<'s>(scope: &mut v8::HandleScope<'s>, body: &SharedBody) -> v8::Local<'s, v8::Object> {
+    let stream_obj = create_body_object(scope, body);
+    set_function(scope, stream_obj, "getReader", stream_get_reader_callback);
+
+    let async_iterator_key = v8::Symbol::get_async_iterator(scope);
+    let get_reader_fn = v8::Function::new(scope, stream_get_reader_callback).unwrap();
+    stream_obj.set(scope, async_iterator_key.into(), get_reader_fn.into());
+    stream_obj
+}
+
+fn create_request_object<'s>(scope: &mut v8::HandleScope<'s>, parts: RequestParts) -> v8::Local<'s, v8::Object> {
+    let request_obj = new_instance(scope, "Request");
+
+    let url = v8::String::new(scope, parts.url.as_str()).unwrap();
+    set_property(scope, request_obj, "url", url.into());
+    let method = v8::String::new(scope, &parts.method).unwrap();
+    set_property(scope, request_obj, "method", method.into());
+    let headers_obj = create_headers_object(scope, parts.headers);
+    set_property(scope, request_obj, "headers", headers_obj.into());
+    let redirect = v8::String::new(scope, &parts.redirect).unwrap();
+    set_property(scope, request_obj, "redirect", redirect.into());
+
+    // A request always has a signal, one that never aborts when none was given
+    let signal_obj = match parts.signal {
+        Some(signal) => v8::Local::new(scope, signal),
+        None => create_abort_signal_object(scope).0,
+    };
+    set_property(scope, request_obj, "signal", signal_obj.into());
+
+    set_body(scope, request_obj, Body::complete(parts.body.unwrap_or_default()));
+    request_obj
+}
+
+// new Request(input, [init])
+fn request_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "Request") {
+        return;
+    }
+    match RequestParts::from_js(scope, args.get(0), args.get(1)) {
+        Ok(parts) => rv.set(create_request_object(scope, parts).into()),
+        Err(e) => throw_type_error(scope, &e),
+    }
+}
+
+// The status line and headers of a Response
+struct ResponseHead {
+    status: u16,
+    status_text: String,
+    headers: Headers,
+    url: String,
+    redirected: bool,
+    kind: &'static str,
+}
+
+impl ResponseHead {
+    // status, statusText and headers of a Response init, throws and returns None when invalid
+    fn from_init(scope: &mut v8::HandleScope, init: v8::Local<v8::Value>) -> Option<Self> {
+        let init = v8::Local::<v8::Object>::try_from(init).ok();
+        let mut head = ResponseHead { status: 200, status_text: String::new(), headers: Headers::new(), url: String::new(), redirected: false, kind: "default" };
+
+        if let Some(status) = option(scope, init, "status") {
+            let status = status.number_value(scope).unwrap_or(0.0);
+            if !(200.0..=599.0).contains(&status) || status.fract() != 0.0 {
+                let message = v8::String::new(scope, "init[\"status\"] must be in the range of 200 to 599, inclusive").unwrap();
+                let exception = v8::Exception::range_error(scope, message);
+                scope.throw_exception(exception);
+                return None;
+            }
+            head.status = status as u16;
+        }
+
+        if let Some(status_text) = option(scope, init, "statusText") {
+            head.status_text = status_text.to_rust_string_lossy(scope);
+            if head.status_text.bytes().any(|byte| byte == b'\r' || byte == b'\n') {
+                throw_type_error(scope, "Invalid statusText");
+                return None;
+            }
+        }
+
+        if let Some(headers) = option(scope, init, "headers") {
+            match headers_from_init(scope, headers) {
+                Ok(headers) => head.headers = headers,
+                Err(e) => {
+                    throw_type_error(scope, &e);
+                    return None;
+                }
+            }
+        }
+        Some(head)
+    }
+}
+
+fn create_response_object<'s>(scope: &mut v8::HandleScope<'s>, head: ResponseHead, body: SharedBody, has_body: bool) -> v8::Local<'s, v8::Object> {
+    let response_obj = new_instance(scope, "Response");
+
+    let status = v8::Integer::new(scope, head.status as i32);
+    set_property(scope, response_obj, "status", status.into());
+    let status_text = v8::String::new(scope, &head.status_text).unwrap();
+    set_property(scope, response_obj, "statusText", status_text.into());
+    let ok = v8::Boolean::new(scope, (200..300).contains(&head.status));
+    set_property(scope, response_obj, "ok", ok.into());
+    let headers_obj = create_headers_object(scope, head.headers);
+    set_property(scope, response_obj, "headers", headers_obj.into());
+    let url = v8::String::new(scope, &head.url).unwrap();
+    set_property(scope, response_obj, "url", url.into());
+    let redirected = v8::Boolean::new(scope, head.redirected);
+    set_property(scope, response_obj, "redirected", redirected.into());
+    let kind = v8::String::new(scope, head.kind).unwrap();
+    set_property(scope, response_obj, "type", kind.into());
+
+    let stream: v8::Local<v8::Value> = match has_body {
+        true => create_stream_object(scope, &body).into(),
+        false => v8::null(scope).into(),
+    };
+    set_property(scope, response_obj, "body", stream);
+    set_body(scope, response_obj, body);
+    response_obj
+}
+
+// new Response([body], [init])
+fn response_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "Response") {
+        return;
+    }
+    let Some(mut head) = ResponseHead::from_init(scope, args.get(1)) else {
+        return;
+    };
+
+    let body = body_from_js(scope, args.get(0));
+    if body.is_some() && NULL_BODY_STATUSES.contains(&head.status) {
+        throw_type_error(scope, &format!("Response with null body status {} cannot have body", head.status));
+        return;
+    }
+    let has_body = body.is_some();
+    let (bytes, content_type) = body.unwrap_or_default();
+    if let Some(content_type) = content_type.filter(|_| !head.headers.contains("content-type")) {
+        head.headers.append("content-type".to_string(), content_type.to_string());
+    }
+
+    let response_obj = create_response_object(scope, head, Body::complete(bytes), has_body);
+    rv.set(response_obj.into());
+}
+
+// Response.json(data, [init])
+fn response_json_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Some(mut head) = ResponseHead::from_init(scope, args.get(1)) else {
+        return;
+    };
+    let Some(json) = v8::json::stringify(scope, args.get(0)) else {
+        throw_type_error(scope, "Value is not JSON serializable");
+        return;
+    };
+    let json = json.to_rust_string_lossy(scope);
+    if json == "undefined" {
+        throw_type_error(scope, "Value is not JSON serializable");
+        return;
+    }
+    if !head.headers.contains("content-type") {
+        head.headers.append("content-type".to_string(), "application/json".to_string());
+    }
+
+    let response_obj = create_response_object(scope, head, Body::complete(json.into_bytes()), true);
+    rv.set(response_obj.into());
+}
+
+// fetch() and its classes go on the global object, fetch() sends through the same Http as http.request()
+pub fn initialize_fetch<'s>(scope: &mut v8::HandleScope<'s>, external_http: v8::Local<'s, v8::External>) {
+    let global = scope.get_current_context().global(scope);
+    let (_, headers_prototype) = define_class(scope, global, "Headers", headers_constructor);
+    set_function(scope, headers_prototype, "append", headers_append_callback);
+    set_function(scope, headers_prototype, "set", headers_set_callback);
+    set_function(scope, headers_prototype, "get", headers_get_callback);
+    set_function(scope, headers_prototype, "has", headers_has_callback);
+    set_function(scope, headers_prototype, "delete", headers_delete_callback);
+    set_function(scope, headers_prototype, "getSetCookie", headers_get_set_cookie_callback);
+    set_function(scope, headers_prototype, "forEach", headers_for_each_callback);
+    set_function(scope, headers_prototype, "keys", headers_keys_callback);
+    set_function(scope, headers_prototype, "values", headers_values_callback);
+    set_function(scope, headers_prototype, "entries", headers_entries_callback);
+    let iterator_key = v8::Symbol::get_iterator(scope);
+    let entries_fn = v8::Function::new(scope, headers_entries_callback).unwrap();
+    headers_prototype.set(scope, iterator_key.into(), entries_fn.into());
+
+    let (_, request_prototype) = define_class(scope, global, "Request", request_constructor);
+    let (response_class, response_prototype) = define_class(scope, global, "Response", response_constructor);
+    for prototype in [request_prototype, response_prototype] {
+        set_function(scope, prototype, "text", body_text_callback);
+        set_function(scope, prototype, "json", body_json_callback);
+        set_function(scope, prototype, "arrayBuffer", body_array_buffer_callback);
+    }
+    set_function(scope, response_class.into(), "json", response_json_callback);
+
+    let fetch_fn = v8::Function::builder(fetch_callback)
+        .data(external_http.into())
+        .build(scope)
+        .unwrap();
+    let fetch_key = v8::String::new(scope, "fetch").unwrap();
+    global.set(scope, fetch_key.into(), fetch_fn.into());
+}
@@ -5,55 +5,148 @@ use tokio::sync::mpsc::UnboundedSender;
 use std::path::Path;
 use std::path::PathBuf;
 use std::ffi::c_void;
+use std::collections::HashMap;
+use std::fs::FileTimes;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::os::unix::fs::{FileExt, MetadataExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::interface::Operations;
 use crate::interface::FsOperation;
 use crate::helper::retrieve_tx; 
+use crate::helper::set_function;
+use crate::helper::value_to_bytes;
+use crate::stream::{fs_create_read_stream_callback, fs_create_write_stream_callback};
+use crate::fs_promises::initialize_fs_promises;
+use crate::permissions::{PermissionDenied, Permissions};
+use crate::watch::{fs_unwatch_file_callback, fs_watch_callback, fs_watch_file_callback, StatWatchers};
 
-pub struct File {
-    path: PathBuf,
-    tx: UnboundedSender<Operations>
+// fs.constants.COPYFILE_EXCL
+const COPYFILE_EXCL: u32 = 1;
+
+// errno for an fd that is not in the table
+const EBADF: i32 = 9;
+
+#[derive(Clone, Default, PartialEq)]
+pub struct FileKind {
+    pub is_file: bool,
+    pub is_directory: bool,
+    pub is_symbolic_link: bool,
 }
 
-impl File {
-    pub fn new(path: PathBuf, tx: UnboundedSender<Operations>) -> Self {
+impl FileKind {
+    pub fn from_file_type(file_type: std::fs::FileType) -> Self {
         Self {
-            path,
-            tx,
+            is_file: file_type.is_file(),
+            is_directory: file_type.is_dir(),
+            is_symbolic_link: file_type.is_symlink(),
         }
     }
+}
+
+// Snapshot of std::fs::Metadata that can be sent back to the event loop
+#[derive(Clone, Default)]
+pub struct FileStats {
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime_ms: f64,
+    pub mtime_ms: f64,
+    pub ctime_ms: f64,
+    pub birthtime_ms: f64,
+    pub kind: FileKind,
+}
+
+impl FileStats {
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let to_ms = |seconds: i64, nanoseconds: i64| seconds as f64 * 1000.0 + nanoseconds as f64 / 1_000_000.0;
+
+        // Not every filesystem records a creation time, Node falls back to ctime
+        let ctime_ms = to_ms(metadata.ctime(), metadata.ctime_nsec());
+        let birthtime_ms = metadata.created().ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs_f64() * 1000.0)
+            .unwrap_or(ctime_ms);
 
-    pub fn set_path(&mut self, path: &str) {
-        self.path = PathBuf::from(path);
+        Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            mode: metadata.mode(),
+            nlink: metadata.nlink(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size(),
+            atime_ms: to_ms(metadata.atime(), metadata.atime_nsec()),
+            mtime_ms: to_ms(metadata.mtime(), metadata.mtime_nsec()),
+            ctime_ms,
+            birthtime_ms,
+            kind: FileKind::from_file_type(metadata.file_type()),
+        }
     }
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub kind: FileKind,
+}
+
+pub struct MkdirOptions {
+    pub recursive: bool,
+    pub mode: u32,
+}
 
-    pub fn path_exists(&self) -> bool {
-        self.path.exists()
+pub struct RmOptions {
+    pub recursive: bool,
+    pub force: bool,
+}
+
+// Open file descriptors. Entries outlive the event loop turn that opened them and
+// are only dropped by close(), in-flight operations keep their own reference
+pub type FdTable = Arc<Mutex<HashMap<i32, Arc<std::fs::File>>>>;
+
+// Shared by every fs call. It holds no per-call state, so concurrent
+// operations each carry their own path and callback into the spawned task
+pub struct Fs {
+    tx: UnboundedSender<Operations>,
+    files: FdTable,
+    pub stat_watchers: StatWatchers,
+    pub permissions: Arc<Permissions>,
+}
+
+impl Fs {
+    pub fn new(tx: UnboundedSender<Operations>, permissions: Arc<Permissions>) -> Self {
+        Self {
+            tx,
+            permissions,
+            files: Arc::new(Mutex::new(HashMap::new())),
+            stat_watchers: Mutex::new(HashMap::new()),
+        }
     }
 
     // Reads the file asynchronously and triggers the callback if set.
-    pub fn read(&self, callback: v8::Global<v8::Function>) {
-        let path_clone = self.path.clone();
-        let tx_clone = self.tx.clone();
-
-        if self.path_exists() == false {
-            println!("Error: The path {} does not exist", path_clone.display());
-            return;
+    pub fn read(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
         }
 
+        let tx_clone = self.tx.clone();
+
         tokio::task::spawn_local(async move {
-            match tokio::fs::read_to_string(&path_clone).await {
+            match tokio::fs::read_to_string(&path).await {
                 Ok(contents) => {
-                    // let contents = v8::String::new(scope, std::str::from_utf8(&contents).unwrap()).unwrap();
-                    // let null_value = v8::null(scope).into(); 
-                    // let args = &[null_value, contents_str.into()];
                     let op = Operations::Fs(FsOperation::ReadFileSuccess{ callback, contents }); 
                     tx_clone.send(op).unwrap();
                 },
                 
                 Err(error_message) => {
-                    // let error_message = v8::String::new(scope, &e.to_string()).unwrap();
-                    // let args = &[error_message.into(), v8::undefined(scope).into()];
                     let error_message = error_message.to_string();
                     let op = Operations::Fs(FsOperation::ReadFileError{ callback, error_message }); 
                     tx_clone.send(op).unwrap();
@@ -62,27 +155,21 @@ impl File {
         });
     }
 
-    pub fn write(&self, data: String, callback: v8::Global<v8::Function>) {
-        let path_clone = self.path.clone();
-        let tx_clone = self.tx.clone();
-
-        if !self.path_exists(){
-            println!("Error: The path {} does not exist", path_clone.display());
-            return;
+    pub fn write(&self, path: PathBuf, data: String, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
         }
 
+        let tx_clone = self.tx.clone();
+
         tokio::task::spawn_local(async move {
-            match tokio::fs::write(&path_clone, data).await {
+            match tokio::fs::write(&path, data).await {
                 Ok(_) => {
-                    // let null_value = v8::null(scope).into();
-                    // let args = &[null_value, undefined_value]
                     let op = Operations::Fs(FsOperation::WriteFileSuccess{ callback }); 
                     tx_clone.send(op).unwrap();
                 }, 
 
                 Err(error_message) => {
-                    // let error_message = v8::String::new(scope, &e.to_string()).unwrap();
-                    // let args = &[error_message.into(), v8::undefined(scope).into()];
                     let error_message = error_message.to_string();
                     let op = Operations::Fs(FsOperation::WriteFileError{ callback, error_message }); 
                     tx_clone.send(op).unwrap();
@@ -91,7 +178,567 @@ impl File {
         });
     }
 
+    pub fn stat(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_stat(callback, async move { tokio::fs::metadata(&path).await });
+    }
+
+    pub fn lstat(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_stat(callback, async move { tokio::fs::symlink_metadata(&path).await });
+    }
+
+    pub fn readdir(&self, path: PathBuf, recursive: bool, with_file_types: bool, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
+        }
+
+        let tx_clone = self.tx.clone();
+
+        tokio::task::spawn_local(async move {
+            let op = match read_dir_entries(&path, recursive).await {
+                Ok(entries) => FsOperation::ReaddirSuccess{ callback, entries, with_file_types },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    pub fn mkdir(&self, path: PathBuf, options: MkdirOptions, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            tokio::fs::DirBuilder::new()
+                .recursive(options.recursive)
+                .mode(options.mode)
+                .create(&path)
+                .await
+        });
+    }
+
+    pub fn rmdir(&self, path: PathBuf, recursive: bool, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            if recursive {
+                tokio::fs::remove_dir_all(&path).await
+            } else {
+                tokio::fs::remove_dir(&path).await
+            }
+        });
+    }
+
+    pub fn rm(&self, path: PathBuf, options: RmOptions, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            let metadata = match tokio::fs::symlink_metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(e) if options.force && e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            if !metadata.is_dir() {
+                return tokio::fs::remove_file(&path).await;
+            }
+
+            if !options.recursive {
+                let message = format!("Path is a directory: rm returned EISDIR (is a directory) {}", path.display());
+                return Err(std::io::Error::other(message));
+            }
 
+            tokio::fs::remove_dir_all(&path).await
+        });
+    }
+
+    pub fn unlink(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move { tokio::fs::remove_file(&path).await });
+    }
+
+    pub fn rename(&self, path: PathBuf, new_path: String, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path)
+            .and_then(|_| self.permissions.check_write(Path::new(&new_path))) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move { tokio::fs::rename(&path, &new_path).await });
+    }
+
+    pub fn copy_file(&self, path: PathBuf, destination: String, mode: u32, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path)
+            .and_then(|_| self.permissions.check_write(Path::new(&destination))) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            if mode & COPYFILE_EXCL != 0 && tokio::fs::try_exists(&destination).await? {
+                let message = format!("EEXIST: file already exists, copyfile '{}' -> '{}'", path.display(), destination);
+                return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, message));
+            }
+            tokio::fs::copy(&path, &destination).await.map(|_| ())
+        });
+    }
+
+    // The link is created at the current path and points to `target`
+    pub fn symlink(&self, path: PathBuf, target: String, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move { tokio::fs::symlink(&target, &path).await });
+    }
+
+    pub fn readlink(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_path(callback, async move { tokio::fs::read_link(&path).await });
+    }
+
+    pub fn realpath(&self, path: PathBuf, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_read(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_path(callback, async move { tokio::fs::canonicalize(&path).await });
+    }
+
+    pub fn chmod(&self, path: PathBuf, mode: u32, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            tokio::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).await
+        });
+    }
+
+    // Times are given in seconds since the epoch, matching Node
+    pub fn utimes(&self, path: PathBuf, atime: f64, mtime: f64, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            let file = tokio::fs::File::open(&path).await?.into_std().await;
+            let times = FileTimes::new()
+                .set_accessed(system_time_from_seconds(atime))
+                .set_modified(system_time_from_seconds(mtime));
+
+            tokio::task::spawn_blocking(move || file.set_times(times)).await?
+        });
+    }
+
+    pub fn truncate(&self, path: PathBuf, len: u64, callback: v8::Global<v8::Function>) {
+        if let Err(e) = self.permissions.check_write(&path) {
+            return self.deny(callback, e);
+        }
+
+        self.spawn_complete(callback, async move {
+            let file = tokio::fs::OpenOptions::new().write(true).open(&path).await?;
+            file.set_len(len).await
+        });
+    }
+
+    pub fn open(&self, path: PathBuf, flags: String, mode: u32, callback: v8::Global<v8::Function>) {
+        if let Err(e) = check_open_permissions(&self.permissions, &path, &flags) {
+            return self.deny(callback, e);
+        }
+
+        let tx_clone = self.tx.clone();
+        let files = self.files.clone();
+
+        tokio::task::spawn_local(async move {
+            let result = async {
+                let Some(mut open_options) = open_options_from_flags(&flags) else {
+                    let message = format!("Unknown file open flag: {}", flags);
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+                };
+                let file = open_options.mode(mode).open(&path).await?.into_std().await;
+
+                let fd = file.as_raw_fd();
+                files.lock().unwrap().insert(fd, Arc::new(file));
+                Ok(fd)
+            }.await;
+
+            let op = match result {
+                Ok(fd) => FsOperation::OpenSuccess{ callback, fd },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    // Reads up to `length` bytes, at `position` or from the current file offset
+    pub fn read_fd(
+        &self,
+        fd: i32,
+        length: usize,
+        position: Option<u64>,
+        buffer: v8::Global<v8::ArrayBufferView>,
+        offset: usize,
+        callback: v8::Global<v8::Function>
+    ) {
+        let tx_clone = self.tx.clone();
+        let file = self.get_file(fd);
+
+        tokio::task::spawn_local(async move {
+            let result = async {
+                let file = file?;
+                run_blocking(move || {
+                    let mut bytes = vec![0u8; length];
+                    let n = match position {
+                        Some(position) => file.read_at(&mut bytes, position)?,
+                        None => (&*file).read(&mut bytes)?,
+                    };
+                    bytes.truncate(n);
+                    Ok(bytes)
+                }).await
+            }.await;
+
+            let op = match result {
+                Ok(bytes) => FsOperation::ReadSuccess{ callback, buffer, offset, bytes },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    // Writes all of `data`, at `position` or at the current file offset
+    pub fn write_fd(
+        &self,
+        fd: i32,
+        data: Vec<u8>,
+        position: Option<u64>,
+        buffer: v8::Global<v8::Value>,
+        callback: v8::Global<v8::Function>
+    ) {
+        let tx_clone = self.tx.clone();
+        let file = self.get_file(fd);
+
+        tokio::task::spawn_local(async move {
+            let result = async {
+                let file = file?;
+                run_blocking(move || {
+                    match position {
+                        Some(position) => file.write_all_at(&data, position)?,
+                        None => (&*file).write_all(&data)?,
+                    };
+                    Ok(data.len())
+                }).await
+            }.await;
+
+            let op = match result {
+                Ok(bytes_written) => FsOperation::WriteSuccess{ callback, bytes_written, buffer },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    pub fn fsync(&self, fd: i32, callback: v8::Global<v8::Function>) {
+        let file = self.get_file(fd);
+        self.spawn_complete(callback, async move {
+            let file = file?;
+            run_blocking(move || file.sync_all()).await
+        });
+    }
+
+    pub fn fdatasync(&self, fd: i32, callback: v8::Global<v8::Function>) {
+        let file = self.get_file(fd);
+        self.spawn_complete(callback, async move {
+            let file = file?;
+            run_blocking(move || file.sync_data()).await
+        });
+    }
+
+    pub fn ftruncate(&self, fd: i32, len: u64, callback: v8::Global<v8::Function>) {
+        let file = self.get_file(fd);
+        self.spawn_complete(callback, async move {
+            let file = file?;
+            run_blocking(move || file.set_len(len)).await
+        });
+    }
+
+    pub fn fstat(&self, fd: i32, callback: v8::Global<v8::Function>) {
+        let file = self.get_file(fd);
+        self.spawn_stat(callback, async move {
+            let file = file?;
+            run_blocking(move || file.metadata()).await
+        });
+    }
+
+    // The descriptor is closed once the last in-flight operation using it finishes
+    pub fn close(&self, fd: i32, callback: v8::Global<v8::Function>) {
+        let removed = self.files.lock().unwrap().remove(&fd);
+        self.spawn_complete(callback, async move {
+            removed.map(|_| ()).ok_or_else(bad_file_descriptor)
+        });
+    }
+
+    // Denied operations fail through the callback without touching the file system
+    fn deny(&self, callback: v8::Global<v8::Function>, error: PermissionDenied) {
+        let op = FsOperation::Error{ callback, error_message: error.to_string() };
+        self.tx.send(Operations::Fs(op)).unwrap();
+    }
+
+    fn get_file(&self, fd: i32) -> std::io::Result<Arc<std::fs::File>> {
+        self.files.lock().unwrap().get(&fd).cloned().ok_or_else(bad_file_descriptor)
+    }
+
+    fn spawn_stat(
+        &self,
+        callback: v8::Global<v8::Function>,
+        task: impl Future<Output = std::io::Result<std::fs::Metadata>> + 'static
+    ) {
+        let tx_clone = self.tx.clone();
+
+        tokio::task::spawn_local(async move {
+            let op = match task.await {
+                Ok(metadata) => FsOperation::StatSuccess{ callback, stats: FileStats::from_metadata(&metadata) },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    fn spawn_path(
+        &self,
+        callback: v8::Global<v8::Function>,
+        task: impl Future<Output = std::io::Result<PathBuf>> + 'static
+    ) {
+        let tx_clone = self.tx.clone();
+
+        tokio::task::spawn_local(async move {
+            let op = match task.await {
+                Ok(path) => FsOperation::PathSuccess{ callback, path: path.to_string_lossy().to_string() },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+
+    fn spawn_complete(
+        &self,
+        callback: v8::Global<v8::Function>,
+        task: impl Future<Output = std::io::Result<()>> + 'static
+    ) {
+        let tx_clone = self.tx.clone();
+
+        tokio::task::spawn_local(async move {
+            let op = match task.await {
+                Ok(_) => FsOperation::Success{ callback },
+                Err(error_message) => FsOperation::Error{ callback, error_message: error_message.to_string() },
+            };
+            tx_clone.send(Operations::Fs(op)).unwrap();
+        });
+    }
+}
+
+// Lists a directory, walking into subdirectories when `recursive` is set.
+// Nested entries are named relative to `root`, e.g. "assets/logo.png"
+pub async fn read_dir_entries(root: &Path, recursive: bool) -> std::io::Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = pending.pop() {
+        let mut read_dir = tokio::fs::read_dir(root.join(&relative_dir)).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let relative_path = relative_dir.join(entry.file_name());
+            let kind = FileKind::from_file_type(entry.file_type().await?);
+
+            // file_type() does not follow symlinks, so linked directories are not walked
+            if recursive && kind.is_directory {
+                pending.push(relative_path.clone());
+            }
+
+            entries.push(DirEntry {
+                name: relative_path.to_string_lossy().to_string(),
+                kind,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+// Translates Node's string flags ("r", "w", "a+", "wx", ...) into open options
+// Flags starting with r read, the rest write, and + does both
+pub fn check_open_permissions(permissions: &Permissions, path: &Path, flags: &str) -> Result<(), PermissionDenied> {
+    if flags.starts_with('r') || flags.contains('+') {
+        permissions.check_read(path)?;
+    }
+    if !flags.starts_with('r') || flags.contains('+') {
+        permissions.check_write(path)?;
+    }
+    Ok(())
+}
+
+pub fn open_options_from_flags(flags: &str) -> Option<tokio::fs::OpenOptions> {
+    let mut options = tokio::fs::OpenOptions::new();
+
+    match flags {
+        "r" | "rs" | "sr" => options.read(true),
+        "r+" | "rs+" | "sr+" => options.read(true).write(true),
+        "w" => options.write(true).create(true).truncate(true),
+        "wx" | "xw" => options.write(true).create_new(true),
+        "w+" => options.read(true).write(true).create(true).truncate(true),
+        "wx+" | "xw+" => options.read(true).write(true).create_new(true),
+        "a" | "as" | "sa" => options.append(true).create(true),
+        "ax" | "xa" => options.append(true).create_new(true),
+        "a+" | "as+" | "sa+" => options.read(true).append(true).create(true),
+        "ax+" | "xa+" => options.read(true).append(true).create_new(true),
+        _ => return None,
+    };
+
+    Some(options)
+}
+
+fn bad_file_descriptor() -> std::io::Error {
+    std::io::Error::from_raw_os_error(EBADF)
+}
+
+async fn run_blocking<T: Send + 'static>(
+    task: impl FnOnce() -> std::io::Result<T> + Send + 'static
+) -> std::io::Result<T> {
+    tokio::task::spawn_blocking(task).await?
+}
+
+fn system_time_from_seconds(seconds: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0))
+}
+
+// JS Objects
+fn fs_type_predicate_callback(
+    _scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    // The answer is bound as the function's data when the object is created
+    if let Some(value) = args.data() {
+        rv.set(value);
+    }
+}
+
+fn set_type_predicates(
+    scope: &mut v8::HandleScope,
+    obj: v8::Local<v8::Object>,
+    kind: &FileKind
+) {
+    let predicates = [
+        ("isFile", kind.is_file),
+        ("isDirectory", kind.is_directory),
+        ("isSymbolicLink", kind.is_symbolic_link),
+    ];
+
+    for (name, value) in predicates {
+        let data = v8::Boolean::new(scope, value);
+        let function = v8::Function::builder(fs_type_predicate_callback)
+            .data(data.into())
+            .build(scope)
+            .unwrap();
+        let key = v8::String::new(scope, name).unwrap();
+        obj.set(scope, key.into(), function.into());
+    }
+}
+
+fn set_number(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str, value: f64) {
+    let key = v8::String::new(scope, name).unwrap();
+    let value = v8::Number::new(scope, value);
+    obj.set(scope, key.into(), value.into());
+}
+
+fn set_date(scope: &mut v8::HandleScope, obj: v8::Local<v8::Object>, name: &str, time_ms: f64) {
+    let key = v8::String::new(scope, name).unwrap();
+    let value = v8::Date::new(scope, time_ms).unwrap();
+    obj.set(scope, key.into(), value.into());
+}
+
+pub fn create_stats_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    stats: &FileStats
+) -> v8::Local<'s, v8::Object> {
+    let stats_obj = v8::Object::new(scope);
+
+    set_number(scope, stats_obj, "dev", stats.dev as f64);
+    set_number(scope, stats_obj, "ino", stats.ino as f64);
+    set_number(scope, stats_obj, "mode", stats.mode as f64);
+    set_number(scope, stats_obj, "nlink", stats.nlink as f64);
+    set_number(scope, stats_obj, "uid", stats.uid as f64);
+    set_number(scope, stats_obj, "gid", stats.gid as f64);
+    set_number(scope, stats_obj, "size", stats.size as f64);
+    set_number(scope, stats_obj, "atimeMs", stats.atime_ms);
+    set_number(scope, stats_obj, "mtimeMs", stats.mtime_ms);
+    set_number(scope, stats_obj, "ctimeMs", stats.ctime_ms);
+    set_number(scope, stats_obj, "birthtimeMs", stats.birthtime_ms);
+    set_date(scope, stats_obj, "atime", stats.atime_ms);
+    set_date(scope, stats_obj, "mtime", stats.mtime_ms);
+    set_date(scope, stats_obj, "ctime", stats.ctime_ms);
+    set_date(scope, stats_obj, "birthtime", stats.birthtime_ms);
+    set_type_predicates(scope, stats_obj, &stats.kind);
+
+    stats_obj
+}
+
+// Copies bytes read on a worker thread into a JS buffer
+pub fn copy_into_buffer(
+    scope: &mut v8::HandleScope,
+    buffer: v8::Local<v8::ArrayBufferView>,
+    offset: usize,
+    bytes: &[u8]
+) {
+    let Some(array_buffer) = buffer.buffer(scope) else { return };
+    let backing_store = array_buffer.get_backing_store();
+
+    let start = buffer.byte_offset() + offset;
+    for (cell, byte) in backing_store[start..start + bytes.len()].iter().zip(bytes) {
+        cell.set(*byte);
+    }
+}
+
+// Builds the readdir result, either an array of names or of Dirent objects
+pub fn create_readdir_array<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    entries: &[DirEntry],
+    with_file_types: bool
+) -> v8::Local<'s, v8::Array> {
+    let array = v8::Array::new(scope, entries.len() as i32);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let name = v8::String::new(scope, &entry.name).unwrap();
+
+        let value: v8::Local<v8::Value> = if with_file_types {
+            let dirent_obj = v8::Object::new(scope);
+            let name_key = v8::String::new(scope, "name").unwrap();
+            dirent_obj.set(scope, name_key.into(), name.into());
+            set_type_predicates(scope, dirent_obj, &entry.kind);
+            dirent_obj.into()
+        } else {
+            name.into()
+        };
+
+        array.set_index(scope, i as u32, value);
+    }
+
+    array
 }
 
 pub fn fs_read_file_callback(
@@ -102,11 +749,11 @@ pub fn fs_read_file_callback(
     // Retrieve the JS object (the "this" object in JavaScript)
     let js_fs_obj = args.this();
 
-    // Get the internal field (the Rust File struct)
+    // Get the internal field (the Rust Fs struct)
     let internal_field = js_fs_obj.get_internal_field(scope, 0).unwrap();
     let external_fs = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-    let file_ptr = unsafe { &mut *(external_fs.value() as *mut File) };
-    
+    let fs_ptr = unsafe { &*(external_fs.value() as *const Fs) };
+
     // Extract the file path from the arguments
     let path = args.get(0).to_rust_string_lossy(scope);
     let callback = args.get(1);
@@ -114,9 +761,7 @@ pub fn fs_read_file_callback(
     let callback_function = v8::Local::<v8::Function>::try_from(callback).unwrap();
     let persistent_callback = v8::Global::new(scope, callback_function);
 
-    file_ptr.set_path(&path);
-    file_ptr.read(persistent_callback)
-
+    fs_ptr.read(PathBuf::from(path), persistent_callback)
 }
 
 pub fn fs_write_file_callback(
@@ -127,10 +772,10 @@ pub fn fs_write_file_callback(
     // Retrieve the JS object (the "this" object in JavaScript)
     let js_fs_obj = args.this();
 
-    // Get the internal field (the Rust Request struct)
+    // Get the internal field (the Rust Fs struct)
     let internal_field = js_fs_obj.get_internal_field(scope, 0).unwrap();
     let external_fs = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-    let file_ptr = unsafe { &mut *(external_fs.value() as *mut File) };
+    let fs_ptr = unsafe { &*(external_fs.value() as *const Fs) };
 
     // Extract the file path from the arguments
     let path = args.get(0).to_rust_string_lossy(scope);
@@ -140,16 +785,506 @@ pub fn fs_write_file_callback(
     let callback_function = v8::Local::<v8::Function>::try_from(callback).unwrap();
     let persistent_callback = v8::Global::new(scope, callback_function);
 
-    file_ptr.set_path(&path);
-    file_ptr.write(contents, persistent_callback);
+    fs_ptr.write(PathBuf::from(path), contents, persistent_callback);
+}
+
+// Helper function to retrieve the Fs instance stored on the fs object.
+// Only shared references are handed out, Fs is never mutated after initialize_fs
+pub fn get_fs_instance<'a>(
+    scope: &mut v8::HandleScope,
+    args: &v8::FunctionCallbackArguments
+) -> &'a Fs {
+    let internal_field = args.this().get_internal_field(scope, 0).unwrap();
+    let external_fs = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_fs.value() as *const Fs) }
+}
+
+// Node allows the options argument to be left out, in which case the callback takes its place
+fn parse_options_and_callback<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    index: i32
+) -> Option<(Option<v8::Local<'s, v8::Object>>, v8::Global<v8::Function>)> {
+    let (options, callback) = if args.get(index).is_function() {
+        (None, args.get(index))
+    } else {
+        let options = v8::Local::<v8::Object>::try_from(args.get(index)).ok()
+            .map(|options| v8::Local::new(scope, options));
+        (options, args.get(index + 1))
+    };
+
+    match v8::Local::<v8::Function>::try_from(callback) {
+        Ok(callback_function) => Some((options, v8::Global::new(scope, callback_function))),
+        Err(_) => {
+            eprintln!("Error: fs callback must be a function");
+            None
+        }
+    }
+}
+
+fn get_option<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    options: Option<v8::Local<'s, v8::Object>>,
+    name: &str
+) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name).unwrap();
+    options
+        .and_then(|options| options.get(scope, key.into()))
+        .filter(|value| !value.is_null_or_undefined())
+}
+
+fn get_bool_option<'s>(scope: &mut v8::HandleScope<'s>, options: Option<v8::Local<'s, v8::Object>>, name: &str) -> bool {
+    get_option(scope, options, name)
+        .map(|value| value.boolean_value(scope))
+        .unwrap_or(false)
+}
+
+// Modes may be passed as numbers (0o755) or octal strings ("755")
+pub fn parse_mode(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<u32> {
+    if value.is_string() {
+        u32::from_str_radix(&value.to_rust_string_lossy(scope), 8).ok()
+    } else {
+        value.uint32_value(scope)
+    }
+}
+
+// Dates are converted to seconds, numbers are already seconds
+fn parse_time(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> f64 {
+    let time = value.number_value(scope).unwrap_or(0.0);
+    if value.is_date() { time / 1000.0 } else { time }
+}
+
+pub fn fs_stat_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    fs_ptr.stat(PathBuf::from(path), callback);
+}
+
+pub fn fs_lstat_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    fs_ptr.lstat(PathBuf::from(path), callback);
+}
+
+pub fn fs_readdir_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((options, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    let recursive = get_bool_option(scope, options, "recursive");
+    let with_file_types = get_bool_option(scope, options, "withFileTypes");
+
+    fs_ptr.readdir(PathBuf::from(path), recursive, with_file_types, callback);
+}
+
+pub fn fs_mkdir_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((options, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    let recursive = get_bool_option(scope, options, "recursive");
+    let mode = get_option(scope, options, "mode")
+        .and_then(|value| parse_mode(scope, value))
+        .unwrap_or(0o777);
+
+    fs_ptr.mkdir(PathBuf::from(path), MkdirOptions { recursive, mode }, callback);
+}
+
+pub fn fs_rmdir_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((options, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    let recursive = get_bool_option(scope, options, "recursive");
+
+    fs_ptr.rmdir(PathBuf::from(path), recursive, callback);
+}
+
+pub fn fs_rm_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((options, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    let recursive = get_bool_option(scope, options, "recursive");
+    let force = get_bool_option(scope, options, "force");
+
+    fs_ptr.rm(PathBuf::from(path), RmOptions { recursive, force }, callback);
+}
+
+pub fn fs_unlink_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    fs_ptr.unlink(PathBuf::from(path), callback);
+}
+
+pub fn fs_rename_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let old_path = args.get(0).to_rust_string_lossy(scope);
+    let new_path = args.get(1).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 2) else { return };
+
+    fs_ptr.rename(PathBuf::from(old_path), new_path, callback);
+}
+
+pub fn fs_copy_file_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let source = args.get(0).to_rust_string_lossy(scope);
+    let destination = args.get(1).to_rust_string_lossy(scope);
+
+    // copyFile(src, dest, [mode], callback)
+    let (mode, callback_index) = if args.get(2).is_function() {
+        (0, 2)
+    } else {
+        (args.get(2).uint32_value(scope).unwrap_or(0), 3)
+    };
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, callback_index) else { return };
+
+    fs_ptr.copy_file(PathBuf::from(source), destination, mode, callback);
+}
+
+pub fn fs_symlink_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let target = args.get(0).to_rust_string_lossy(scope);
+    let path = args.get(1).to_rust_string_lossy(scope);
+
+    // The optional `type` argument only matters on Windows
+    let callback_index = if args.get(2).is_function() { 2 } else { 3 };
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, callback_index) else { return };
+
+    fs_ptr.symlink(PathBuf::from(path), target, callback);
+}
+
+pub fn fs_readlink_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    fs_ptr.readlink(PathBuf::from(path), callback);
+}
+
+pub fn fs_realpath_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 1) else { return };
+
+    fs_ptr.realpath(PathBuf::from(path), callback);
+}
+
+pub fn fs_chmod_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some(mode) = parse_mode(scope, args.get(1)) else {
+        eprintln!("Error: Invalid mode passed to chmod");
+        return;
+    };
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 2) else { return };
+
+    fs_ptr.chmod(PathBuf::from(path), mode, callback);
+}
+
+pub fn fs_utimes_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let atime = parse_time(scope, args.get(1));
+    let mtime = parse_time(scope, args.get(2));
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, 3) else { return };
+
+    fs_ptr.utimes(PathBuf::from(path), atime, mtime, callback);
+}
+
+pub fn fs_truncate_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+
+    // truncate(path, [len], callback)
+    let (len, callback_index) = if args.get(1).is_function() {
+        (0, 1)
+    } else {
+        (args.get(1).integer_value(scope).unwrap_or(0).max(0) as u64, 2)
+    };
+    let Some((_, callback)) = parse_options_and_callback(scope, &args, callback_index) else { return };
+
+    fs_ptr.truncate(PathBuf::from(path), len, callback);
+}
+
+// The callback of the fd functions always comes last, after any optional arguments
+fn find_trailing_callback(
+    scope: &mut v8::HandleScope,
+    args: &v8::FunctionCallbackArguments
+) -> Option<v8::Global<v8::Function>> {
+    let callback = (0..args.length()).rev()
+        .map(|i| args.get(i))
+        .find(|value| value.is_function())
+        .and_then(|value| v8::Local::<v8::Function>::try_from(value).ok());
+
+    match callback {
+        Some(callback_function) => Some(v8::Global::new(scope, callback_function)),
+        None => {
+            eprintln!("Error: fs callback must be a function");
+            None
+        }
+    }
+}
+
+// null, undefined and negative positions mean "use the current file offset"
+pub fn parse_position(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Option<u64> {
+    if !value.is_number() {
+        return None;
+    }
+    value.integer_value(scope).filter(|position| *position >= 0).map(|position| position as u64)
+}
+
+// Resolves the optional offset and length of a read, clamped to the buffer
+pub fn parse_read_range(
+    scope: &mut v8::HandleScope,
+    buffer: v8::Local<v8::ArrayBufferView>,
+    offset: v8::Local<v8::Value>,
+    length: v8::Local<v8::Value>
+) -> (usize, usize) {
+    let byte_length = buffer.byte_length();
+    let offset = (offset.integer_value(scope).unwrap_or(0).max(0) as usize).min(byte_length);
+    let length = if length.is_number() {
+        (length.integer_value(scope).unwrap_or(0).max(0) as usize).min(byte_length - offset)
+    } else {
+        byte_length - offset
+    };
+    (offset, length)
+}
+
+// Buffers are written as (buffer, [offset], [length], [position]),
+// strings as (string, [position], [encoding])
+pub fn parse_write_args(
+    scope: &mut v8::HandleScope,
+    data: v8::Local<v8::Value>,
+    first: v8::Local<v8::Value>,
+    second: v8::Local<v8::Value>,
+    third: v8::Local<v8::Value>
+) -> (Vec<u8>, Option<u64>) {
+    let bytes = value_to_bytes(scope, data);
+
+    if !data.is_array_buffer_view() {
+        return (bytes, parse_position(scope, first));
+    }
+
+    let offset = if first.is_number() {
+        (first.integer_value(scope).unwrap_or(0).max(0) as usize).min(bytes.len())
+    } else {
+        0
+    };
+    let length = if second.is_number() {
+        (second.integer_value(scope).unwrap_or(0).max(0) as usize).min(bytes.len() - offset)
+    } else {
+        bytes.len() - offset
+    };
+    (bytes[offset..offset + length].to_vec(), parse_position(scope, third))
+}
+
+pub fn parse_fd(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> i32 {
+    value.int32_value(scope).unwrap_or(-1)
+}
+
+pub fn fs_open_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    // open(path, [flags], [mode], callback)
+    let flags = if args.get(1).is_string() {
+        args.get(1).to_rust_string_lossy(scope)
+    } else {
+        "r".to_string()
+    };
+    let mode = if args.get(2).is_function() {
+        None
+    } else {
+        parse_mode(scope, args.get(2))
+    };
+
+    fs_ptr.open(PathBuf::from(path), flags, mode.unwrap_or(0o666), callback);
+}
+
+pub fn fs_read_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    let Ok(buffer) = v8::Local::<v8::ArrayBufferView>::try_from(args.get(1)) else {
+        eprintln!("Error: fs.read expects a Uint8Array buffer");
+        return;
+    };
+
+    // read(fd, buffer, offset, length, position, callback)
+    let (offset, length) = parse_read_range(scope, buffer, args.get(2), args.get(3));
+    let position = parse_position(scope, args.get(4));
+
+    let buffer = v8::Global::new(scope, buffer);
+    fs_ptr.read_fd(fd, length, position, buffer, offset, callback);
+}
+
+pub fn fs_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    let data = args.get(1);
+    let (bytes, position) = parse_write_args(scope, data, args.get(2), args.get(3), args.get(4));
+
+    let buffer = v8::Global::new(scope, data);
+    fs_ptr.write_fd(fd, bytes, position, buffer, callback);
+}
+
+pub fn fs_fsync_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    fs_ptr.fsync(fd, callback);
+}
+
+pub fn fs_fdatasync_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    fs_ptr.fdatasync(fd, callback);
+}
+
+pub fn fs_ftruncate_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    // ftruncate(fd, [len], callback)
+    let len = if args.get(1).is_number() {
+        args.get(1).integer_value(scope).unwrap_or(0).max(0) as u64
+    } else {
+        0
+    };
+
+    fs_ptr.ftruncate(fd, len, callback);
+}
+
+pub fn fs_fstat_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    fs_ptr.fstat(fd, callback);
+}
+
+pub fn fs_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = parse_fd(scope, args.get(0));
+    let Some(callback) = find_trailing_callback(scope, &args) else { return };
+
+    fs_ptr.close(fd, callback);
 }
 
 pub fn initialize_fs(
     scope: &mut v8::ContextScope<'_, v8::HandleScope<'_>>,
-    tx: UnboundedSender<Operations>
+    tx: UnboundedSender<Operations>,
+    permissions: Arc<Permissions>
 ){
     let fs_template = v8::ObjectTemplate::new(scope);
-    fs_template.set_internal_field_count(2); // Store the Rust Response struct and socket internally
+    fs_template.set_internal_field_count(1); // Store the Rust Fs struct internally
     let fs_obj = fs_template.new_instance(scope).unwrap();
 
     let read_file_fn_template = v8::FunctionTemplate::new(scope, fs_read_file_callback);
@@ -164,18 +1299,55 @@ pub fn initialize_fs(
     fs_obj.set(scope, read_file_key.into(), read_file_fn.into());
     fs_obj.set(scope, write_file_key.into(), write_file_fn.into());
 
-    let empty_path = PathBuf::new();
-    let file = File::new(empty_path, tx.clone());
+    set_function(scope, fs_obj, "stat", fs_stat_callback);
+    set_function(scope, fs_obj, "lstat", fs_lstat_callback);
+    set_function(scope, fs_obj, "readdir", fs_readdir_callback);
+    set_function(scope, fs_obj, "mkdir", fs_mkdir_callback);
+    set_function(scope, fs_obj, "rmdir", fs_rmdir_callback);
+    set_function(scope, fs_obj, "rm", fs_rm_callback);
+    set_function(scope, fs_obj, "unlink", fs_unlink_callback);
+    set_function(scope, fs_obj, "rename", fs_rename_callback);
+    set_function(scope, fs_obj, "copyFile", fs_copy_file_callback);
+    set_function(scope, fs_obj, "symlink", fs_symlink_callback);
+    set_function(scope, fs_obj, "readlink", fs_readlink_callback);
+    set_function(scope, fs_obj, "realpath", fs_realpath_callback);
+    set_function(scope, fs_obj, "chmod", fs_chmod_callback);
+    set_function(scope, fs_obj, "utimes", fs_utimes_callback);
+    set_function(scope, fs_obj, "truncate", fs_truncate_callback);
+    set_function(scope, fs_obj, "createReadStream", fs_create_read_stream_callback);
+    set_function(scope, fs_obj, "createWriteStream", fs_create_write_stream_callback);
+    set_function(scope, fs_obj, "open", fs_open_callback);
+    set_function(scope, fs_obj, "read", fs_read_callback);
+    set_function(scope, fs_obj, "write", fs_write_callback);
+    set_function(scope, fs_obj, "fsync", fs_fsync_callback);
+    set_function(scope, fs_obj, "fdatasync", fs_fdatasync_callback);
+    set_function(scope, fs_obj, "ftruncate", fs_ftruncate_callback);
+    set_function(scope, fs_obj, "fstat", fs_fstat_callback);
+    set_function(scope, fs_obj, "close", fs_close_callback);
+    set_function(scope, fs_obj, "watch", fs_watch_callback);
+    set_function(scope, fs_obj, "watchFile", fs_watch_file_callback);
+    set_function(scope, fs_obj, "unwatchFile", fs_unwatch_file_callback);
+
+    // fs.constants
+    let constants_obj = v8::Object::new(scope);
+    let copyfile_excl_key = v8::String::new(scope, "COPYFILE_EXCL").unwrap();
+    let copyfile_excl_value = v8::Integer::new_from_unsigned(scope, COPYFILE_EXCL);
+    constants_obj.set(scope, copyfile_excl_key.into(), copyfile_excl_value.into());
+    let constants_key = v8::String::new(scope, "constants").unwrap();
+    fs_obj.set(scope, constants_key.into(), constants_obj.into());
+
+    let fs = Fs::new(tx.clone(), permissions);
 
     let context = scope.get_current_context();
     let global = context.global(scope);
     let global_key = v8::String::new(scope, "fs").unwrap();
 
-    // Create a Rust File object and wrap it in External
-    let boxed_file = Box::new(file);
-    let external_fs = v8::External::new(scope, Box::into_raw(boxed_file) as *const _ as *mut c_void);
+    // Create a Rust Fs object and wrap it in External, it lives for the rest of the program
+    let boxed_fs = Box::new(fs);
+    let external_fs = v8::External::new(scope, Box::into_raw(boxed_fs) as *const _ as *mut c_void);
 
-    // Set the Rust Response object as an internal field of the JS object
+    // Set the Rust Fs object as an internal field of the JS object
     fs_obj.set_internal_field(0, external_fs.into());
+    initialize_fs_promises(scope, fs_obj, external_fs);
     global.set(scope, global_key.into(), fs_obj.into());
 }
\ No newline at end of file
@@ -0,0 +1,313 @@
+use rusty_v8 as v8;
+use std::ffi::c_void;
+use std::path::PathBuf;
+
+use crate::fs::{
+    get_fs_instance, parse_fd, parse_mode, parse_position, parse_read_range, parse_write_args,
+};
+use crate::helper::set_function;
+
+// How the (err, ...results) of a callback based fs operation settles its promise
+#[derive(Clone, Copy)]
+enum Settle {
+    Undefined = 0,
+    Value = 1,
+    Read = 2,
+    Write = 3,
+    FileHandle = 4,
+}
+
+impl Settle {
+    fn from_i32(value: i32) -> Self {
+        match value {
+            1 => Settle::Value,
+            2 => Settle::Read,
+            3 => Settle::Write,
+            4 => Settle::FileHandle,
+            _ => Settle::Undefined,
+        }
+    }
+}
+
+// Creates a promise along with the native callback that settles it. The callback is
+// handed to the regular Fs methods, so promises share the callback implementation
+fn create_promise_callback<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    settle: Settle,
+    external_fs: v8::Local<'s, v8::Value>
+) -> (v8::Local<'s, v8::Promise>, v8::Global<v8::Function>) {
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let promise = resolver.get_promise(scope);
+
+    // The resolver is not a JS value, so it is boxed and reclaimed when the promise settles
+    let boxed_resolver = Box::new(v8::Global::new(scope, resolver));
+    let external_resolver = v8::External::new(scope, Box::into_raw(boxed_resolver) as *mut c_void);
+    let settle = v8::Integer::new(scope, settle as i32);
+    let data = v8::Array::new_with_elements(scope, &[external_resolver.into(), settle.into(), external_fs]);
+
+    let function = v8::Function::builder(settle_promise_callback)
+        .data(data.into())
+        .build(scope)
+        .unwrap();
+
+    (promise, v8::Global::new(scope, function))
+}
+
+fn settle_promise_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let data = v8::Local::<v8::Array>::try_from(args.data().unwrap()).unwrap();
+    let external_resolver = data.get_index(scope, 0).unwrap();
+    let external_resolver = v8::Local::<v8::External>::try_from(external_resolver).unwrap();
+    let resolver = unsafe { Box::from_raw(external_resolver.value() as *mut v8::Global<v8::PromiseResolver>) };
+    let resolver = v8::Local::new(scope, *resolver);
+    let settle = data.get_index(scope, 1).unwrap().int32_value(scope).unwrap_or(0);
+    let external_fs = data.get_index(scope, 2).unwrap();
+
+    // Errors arrive as message strings, they are rejected as Error objects
+    let error = args.get(0);
+    if !error.is_null_or_undefined() {
+        let message = error.to_string(scope).unwrap();
+        let exception = v8::Exception::error(scope, message);
+        resolver.reject(scope, exception);
+        return;
+    }
+
+    let value: v8::Local<v8::Value> = match Settle::from_i32(settle) {
+        Settle::Undefined => v8::undefined(scope).into(),
+        Settle::Value => args.get(1),
+        Settle::Read => {
+            let result = v8::Object::new(scope);
+            set_property(scope, result, "bytesRead", args.get(1));
+            set_property(scope, result, "buffer", args.get(2));
+            result.into()
+        }
+        Settle::Write => {
+            let result = v8::Object::new(scope);
+            set_property(scope, result, "bytesWritten", args.get(1));
+            set_property(scope, result, "buffer", args.get(2));
+            result.into()
+        }
+        Settle::FileHandle => {
+            let fd = parse_fd(scope, args.get(1));
+            create_file_handle_object(scope, external_fs, fd).into()
+        }
+    };
+
+    resolver.resolve(scope, value);
+}
+
+fn set_property(
+    scope: &mut v8::HandleScope,
+    obj: v8::Local<v8::Object>,
+    name: &str,
+    value: v8::Local<v8::Value>
+) {
+    let key = v8::String::new(scope, name).unwrap();
+    obj.set(scope, key.into(), value);
+}
+
+fn rejected_promise<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    message: &str
+) -> v8::Local<'s, v8::Promise> {
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let message = v8::String::new(scope, message).unwrap();
+    let exception = v8::Exception::type_error(scope, message);
+    resolver.reject(scope, exception);
+    resolver.get_promise(scope)
+}
+
+// The Fs External stored on `this`, FileHandles share the one from fs.promises
+fn get_external_fs<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments
+) -> v8::Local<'s, v8::Value> {
+    let internal_field = args.this().get_internal_field(scope, 0).unwrap();
+    v8::Local::new(scope, internal_field)
+}
+
+fn get_handle_fd(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> i32 {
+    let key = v8::String::new(scope, "fd").unwrap();
+    let fd = args.this().get(scope, key.into()).unwrap();
+    parse_fd(scope, fd)
+}
+
+// A FileHandle wraps an fd from the shared fd table, each method returns a promise
+fn create_file_handle_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    external_fs: v8::Local<v8::Value>,
+    fd: i32
+) -> v8::Local<'s, v8::Object> {
+    let handle_template = v8::ObjectTemplate::new(scope);
+    handle_template.set_internal_field_count(1);
+    let handle_obj = handle_template.new_instance(scope).unwrap();
+    handle_obj.set_internal_field(0, external_fs);
+
+    let fd = v8::Integer::new(scope, fd);
+    set_property(scope, handle_obj, "fd", fd.into());
+
+    set_function(scope, handle_obj, "read", file_handle_read_callback);
+    set_function(scope, handle_obj, "write", file_handle_write_callback);
+    set_function(scope, handle_obj, "stat", file_handle_stat_callback);
+    set_function(scope, handle_obj, "truncate", file_handle_truncate_callback);
+    set_function(scope, handle_obj, "sync", file_handle_sync_callback);
+    set_function(scope, handle_obj, "datasync", file_handle_datasync_callback);
+    set_function(scope, handle_obj, "close", file_handle_close_callback);
+
+    handle_obj
+}
+
+fn fs_promises_open_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = args.get(0).to_rust_string_lossy(scope);
+
+    // open(path, [flags], [mode])
+    let flags = if args.get(1).is_string() {
+        args.get(1).to_rust_string_lossy(scope)
+    } else {
+        "r".to_string()
+    };
+    let mode = parse_mode(scope, args.get(2)).unwrap_or(0o666);
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::FileHandle, external_fs);
+    fs_ptr.open(PathBuf::from(path), flags, mode, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_read_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let Ok(buffer) = v8::Local::<v8::ArrayBufferView>::try_from(args.get(0)) else {
+        let promise = rejected_promise(scope, "filehandle.read expects a Uint8Array buffer");
+        rv.set(promise.into());
+        return;
+    };
+
+    // read(buffer, [offset], [length], [position])
+    let (offset, length) = parse_read_range(scope, buffer, args.get(1), args.get(2));
+    let position = parse_position(scope, args.get(3));
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Read, external_fs);
+    let buffer = v8::Global::new(scope, buffer);
+    fs_ptr.read_fd(fd, length, position, buffer, offset, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let data = args.get(0);
+    let (bytes, position) = parse_write_args(scope, data, args.get(1), args.get(2), args.get(3));
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Write, external_fs);
+    let buffer = v8::Global::new(scope, data);
+    fs_ptr.write_fd(fd, bytes, position, buffer, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_stat_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Value, external_fs);
+    fs_ptr.fstat(fd, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_truncate_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+    let len = args.get(0).integer_value(scope).unwrap_or(0).max(0) as u64;
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Undefined, external_fs);
+    fs_ptr.ftruncate(fd, len, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_sync_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Undefined, external_fs);
+    fs_ptr.fsync(fd, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_datasync_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Undefined, external_fs);
+    fs_ptr.fdatasync(fd, callback);
+    rv.set(promise.into());
+}
+
+fn file_handle_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let fd = get_handle_fd(scope, &args);
+
+    let external_fs = get_external_fs(scope, &args);
+    let (promise, callback) = create_promise_callback(scope, Settle::Undefined, external_fs);
+    fs_ptr.close(fd, callback);
+    rv.set(promise.into());
+}
+
+// Adds fs.promises, backed by the same Rust Fs (and fd table) as the callback API
+pub fn initialize_fs_promises(
+    scope: &mut v8::HandleScope,
+    fs_obj: v8::Local<v8::Object>,
+    external_fs: v8::Local<v8::External>
+) {
+    let promises_template = v8::ObjectTemplate::new(scope);
+    promises_template.set_internal_field_count(1);
+    let promises_obj = promises_template.new_instance(scope).unwrap();
+    promises_obj.set_internal_field(0, external_fs.into());
+
+    set_function(scope, promises_obj, "open", fs_promises_open_callback);
+
+    set_property(scope, fs_obj, "promises", promises_obj.into());
+}
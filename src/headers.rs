@@ -0,0 +1,188 @@
+use rusty_v8 as v8;
+
+// HTTP headers in the order they were received or set. Names keep their case on the
+// wire but lookups ignore it, and a name may appear more than once (Set-Cookie)
+#[derive(Clone, Default)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+// Repeats of these are dropped when headers are turned into an object, like Node does
+const SINGLE_VALUE_HEADERS: [&str; 17] = [
+    "age", "authorization", "content-length", "content-type", "etag", "expires", "from",
+    "host", "if-modified-since", "if-unmodified-since", "last-modified", "location",
+    "max-forwards", "proxy-authorization", "referer", "retry-after", "user-agent",
+];
+
+impl Headers {
+    pub fn new() -> Self {
+        Headers { entries: Vec::new() }
+    }
+
+    pub fn from_httparse(headers: &[httparse::Header]) -> Self {
+        let entries = headers.iter()
+            .map(|header| (header.name.to_string(), String::from_utf8_lossy(header.value).to_string()))
+            .collect();
+        Headers { entries }
+    }
+
+    // First value of the header
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Last value of the header, the one that counts for Content-Length
+    pub fn get_last(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .rev()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries.iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Every value as one comma separated list, how repeated list headers combine
+    pub fn get_joined(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self.get_all(name).collect();
+        if values.is_empty() {
+            return None;
+        }
+        Some(values.join(", "))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn append(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    // Replaces every value of the header
+    pub fn set(&mut self, name: String, value: String) {
+        self.set_all(name, vec![value]);
+    }
+
+    pub fn set_all(&mut self, name: String, values: Vec<String>) {
+        self.remove(&name);
+        for value in values {
+            self.entries.push((name.clone(), value));
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    // The header block of a message, one line per value so Set-Cookie arrays stay separate
+    pub fn serialize(&self) -> String {
+        let mut block = String::new();
+        for (key, value) in &self.entries {
+            block.push_str(key);
+            block.push_str(": ");
+            block.push_str(value);
+            block.push_str("\r\n");
+        }
+        block
+    }
+}
+
+// RFC 9110 token characters, what methods and header names are made of
+pub fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+// Rejects headers that would break out of their line
+pub fn validate_header(name: &str, values: &[String]) -> Result<(), String> {
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        return Err(format!("Header name must be a valid HTTP token [\"{}\"]", name));
+    }
+    if values.iter().any(|value| value.bytes().any(|byte| byte == b'\r' || byte == b'\n' || byte == 0)) {
+        return Err(format!("Invalid character in header content [\"{}\"]", name));
+    }
+    Ok(())
+}
+
+// Node's `headers` object: lowercase names, Set-Cookie as an array, Cookie joined
+// with "; ", single value headers keep the first value and others are joined with ", "
+pub fn headers_to_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    headers: &Headers
+) -> v8::Local<'s, v8::Object> {
+    let js_headers = v8::Object::new(scope);
+
+    let mut names: Vec<String> = Vec::new();
+    for (key, _) in headers.iter() {
+        let name = key.to_ascii_lowercase();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    for name in names {
+        let values: Vec<&str> = headers.get_all(&name).collect();
+        let js_value: v8::Local<v8::Value> = match name.as_str() {
+            "set-cookie" => string_array(scope, &values).into(),
+            "cookie" => v8::String::new(scope, &values.join("; ")).unwrap().into(),
+            _ if SINGLE_VALUE_HEADERS.contains(&name.as_str()) => v8::String::new(scope, values[0]).unwrap().into(),
+            _ => v8::String::new(scope, &values.join(", ")).unwrap().into(),
+        };
+        let js_key = v8::String::new(scope, &name).unwrap();
+        js_headers.set(scope, js_key.into(), js_value);
+    }
+
+    js_headers
+}
+
+// Node's `rawHeaders`: names and values alternating, as they were received
+pub fn raw_headers_array<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    headers: &Headers
+) -> v8::Local<'s, v8::Array> {
+    let raw: Vec<&str> = headers.iter().flat_map(|(key, value)| [key, value]).collect();
+    string_array(scope, &raw)
+}
+
+// A header value passed from JS, arrays become one value per element
+pub fn header_values_from_js(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Vec<String> {
+    let Ok(array) = v8::Local::<v8::Array>::try_from(value) else {
+        return vec![value.to_rust_string_lossy(scope)];
+    };
+
+    let mut values = Vec::new();
+    for i in 0..array.length() {
+        if let Some(element) = array.get_index(scope, i) {
+            values.push(element.to_rust_string_lossy(scope));
+        }
+    }
+    values
+}
+
+// The value getHeader() returns, an array when the header has several values
+pub fn header_values_to_js<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    values: &[&str]
+) -> v8::Local<'s, v8::Value> {
+    match values {
+        [] => v8::undefined(scope).into(),
+        [value] => v8::String::new(scope, value).unwrap().into(),
+        _ => string_array(scope, values).into(),
+    }
+}
+
+fn string_array<'s>(scope: &mut v8::HandleScope<'s>, values: &[&str]) -> v8::Local<'s, v8::Array> {
+    let elements: Vec<v8::Local<v8::Value>> = values.iter()
+        .map(|value| v8::String::new(scope, value).unwrap().into())
+        .collect();
+    v8::Array::new_with_elements(scope, &elements)
+}
@@ -1,4 +1,6 @@
 use std::any::type_name;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::File; 
 use std::io::prelude::*;
 
@@ -23,6 +25,167 @@ pub fn print_type_of<T>(_: &T) {
     println!("Type: {}", type_name::<T>());
 }
 
+// Attaches a native callback to a JS object under the given key
+pub fn set_function(
+    scope: &mut v8::HandleScope,
+    obj: v8::Local<v8::Object>,
+    name: &str,
+    callback: impl v8::MapFnTo<v8::FunctionCallback>
+){
+    let function_template = v8::FunctionTemplate::new(scope, callback);
+    let function = function_template.get_function(scope).unwrap();
+    let key = v8::String::new(scope, name).unwrap();
+    obj.set(scope, key.into(), function.into());
+}
+
+// Calls obj[name](...args), returning None if the method does not exist
+pub fn call_method<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    obj: v8::Local<v8::Object>,
+    name: &str,
+    args: &[v8::Local<v8::Value>]
+) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name).unwrap();
+    let method = obj.get(scope, key.into())?;
+    let method = v8::Local::<v8::Function>::try_from(method).ok()?;
+    method.call(scope, obj.into(), args)
+}
+
+// Copies Rust bytes into a new Uint8Array, our stand-in for Node's Buffer
+pub fn bytes_to_uint8array<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    bytes: Vec<u8>
+) -> v8::Local<'s, v8::Uint8Array> {
+    let len = bytes.len();
+    let array_buffer = if len == 0 {
+        v8::ArrayBuffer::new(scope, 0)
+    } else {
+        let backing_store = v8::ArrayBuffer::new_backing_store_from_boxed_slice(bytes.into_boxed_slice()).make_shared();
+        v8::ArrayBuffer::with_backing_store(scope, &backing_store)
+    };
+    v8::Uint8Array::new(scope, array_buffer, 0, len).unwrap()
+}
+
+// Accepts strings, typed arrays and ArrayBuffers as chunks of data
+pub fn value_to_bytes(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Vec<u8> {
+    if let Ok(view) = v8::Local::<v8::ArrayBufferView>::try_from(value) {
+        let mut bytes = vec![0u8; view.byte_length()];
+        view.copy_contents(&mut bytes);
+        return bytes;
+    }
+
+    if let Ok(array_buffer) = v8::Local::<v8::ArrayBuffer>::try_from(value) {
+        let len = array_buffer.byte_length();
+        let view = v8::Uint8Array::new(scope, array_buffer, 0, len).unwrap();
+        let mut bytes = vec![0u8; len];
+        view.copy_contents(&mut bytes);
+        return bytes;
+    }
+
+    value.to_rust_string_lossy(scope).into_bytes()
+}
+
+// Throws a JS Error from inside a native callback
+pub fn throw_error(scope: &mut v8::HandleScope, message: &str) {
+    let message = v8::String::new(scope, message).unwrap();
+    let exception = v8::Exception::error(scope, message);
+    scope.throw_exception(exception);
+}
+
+// Throws a JS TypeError, what web APIs throw for bad arguments
+pub fn throw_type_error(scope: &mut v8::HandleScope, message: &str) {
+    let message = v8::String::new(scope, message).unwrap();
+    let exception = v8::Exception::type_error(scope, message);
+    scope.throw_exception(exception);
+}
+
+thread_local! {
+    // Prototypes of the global classes, so instances made from Rust share them
+    static PROTOTYPES: RefCell<HashMap<&'static str, v8::Global<v8::Object>>> = RefCell::new(HashMap::new());
+}
+
+// Defines a class on `target`, the global object or a module, and returns its prototype for the
+// methods. The constructor builds its instance with new_instance() and returns it, `this` is
+// only used to require `new`
+pub fn define_class<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    target: v8::Local<v8::Object>,
+    name: &'static str,
+    constructor: impl v8::MapFnTo<v8::FunctionCallback>
+) -> (v8::Local<'s, v8::Function>, v8::Local<'s, v8::Object>) {
+    let template = v8::FunctionTemplate::new(scope, constructor);
+    register_class(scope, target, name, template)
+}
+
+// define_class() for constructors that need state from Rust, passed as args.data()
+pub fn define_class_with_data<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    target: v8::Local<v8::Object>,
+    name: &'static str,
+    constructor: impl v8::MapFnTo<v8::FunctionCallback>,
+    data: v8::Local<'s, v8::Value>
+) -> (v8::Local<'s, v8::Function>, v8::Local<'s, v8::Object>) {
+    let template = v8::FunctionTemplate::builder(constructor).data(data).build(scope);
+    register_class(scope, target, name, template)
+}
+
+fn register_class<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    target: v8::Local<v8::Object>,
+    name: &'static str,
+    template: v8::Local<v8::FunctionTemplate>
+) -> (v8::Local<'s, v8::Function>, v8::Local<'s, v8::Object>) {
+    let class_name = v8::String::new(scope, name).unwrap();
+    template.set_class_name(class_name);
+    let class = template.get_function(scope).unwrap();
+
+    let prototype_key = v8::String::new(scope, "prototype").unwrap();
+    let prototype = class.get(scope, prototype_key.into()).unwrap();
+    let prototype = v8::Local::<v8::Object>::try_from(prototype).unwrap();
+    let global_prototype = v8::Global::new(scope, prototype);
+    PROTOTYPES.with(|prototypes| prototypes.borrow_mut().insert(name, global_prototype));
+
+    target.set(scope, class_name.into(), class.into());
+    (class, prototype)
+}
+
+// An object of a class from define_class(), with one internal field for its Rust state
+pub fn new_instance<'s>(scope: &mut v8::HandleScope<'s>, name: &str) -> v8::Local<'s, v8::Object> {
+    let template = v8::ObjectTemplate::new(scope);
+    template.set_internal_field_count(1);
+    let instance = template.new_instance(scope).unwrap();
+
+    let prototype = PROTOTYPES.with(|prototypes| {
+        prototypes.borrow().get(name).map(|prototype| v8::Local::new(scope, prototype))
+    });
+    if let Some(prototype) = prototype {
+        instance.set_prototype(scope, prototype.into());
+    }
+    instance
+}
+
+pub fn is_instance(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>, name: &str) -> bool {
+    let Ok(object) = v8::Local::<v8::Object>::try_from(value) else {
+        return false;
+    };
+    let prototype = PROTOTYPES.with(|prototypes| {
+        prototypes.borrow().get(name).map(|prototype| v8::Local::new(scope, prototype))
+    });
+    match (object.get_prototype(scope), prototype) {
+        (Some(actual), Some(expected)) => actual.strict_equals(expected.into()),
+        _ => false,
+    }
+}
+
+// Constructors called without `new` get the global object as `this`
+pub fn require_new(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments, name: &str) -> bool {
+    if is_instance(scope, args.this().into(), name) {
+        return true;
+    }
+    throw_type_error(scope, &format!("Class constructor {} cannot be invoked without 'new'", name));
+    false
+}
+
 //Needs to be abstracted with an enum return type
 //Perhaps need to be in a class method
 pub fn retrieve_tx(
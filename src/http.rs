@@ -1,349 +1,80 @@
 use rusty_v8 as v8;
 use tokio;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tokio::io::AsyncWriteExt;
 use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::mpsc::UnboundedSender;
 use std;
 use std::io;    
 use std::ffi::c_void;
-use std::collections::HashMap;
-use url::Url;
-
-use crate::interface::{ResponseEvent, HttpOperation, Operations};
-use crate::request::create_request_object;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::cell::Cell;
+use std::rc::Rc;
+use tokio::time::Instant;
+
+use crate::interface::{HttpOperation, Operations, StreamEvent};
+use crate::stream::Chunk;
 use crate::request::Request;
-use crate::emitter::EventEmitter;
 use crate::helper::print_type_of;
 use crate::helper::retrieve_tx;
+use crate::permissions::Permissions;
+use crate::headers::Headers;
+use crate::response::status_reason;
+use crate::server::{create_server_callback, ConnectionHandle, ServerState};
+use crate::client::{create_request_callback, get_request_callback};
+use crate::agent::initialize_agent;
+use crate::fetch::initialize_fetch;
+use crate::https::initialize_https;
+use crate::http2::initialize_http2;
+use crate::websocket::initialize_websocket;
+use crate::static_files::initialize_static;
 
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::sync::MutexGuard;
 
-pub struct IncomingMessage {
-    pub event_emitter: EventEmitter
+// How long a response gets to write a 400 or 408 before its connection is destroyed anyway
+const REJECT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Write side of a served connection, boxed so other kinds of streams can be served the same way
+pub type ConnectionWriter = Box<dyn AsyncWrite + Unpin>;
+pub type ConnectionReader = Box<dyn AsyncRead + Unpin>;
+
+// A connection handed over to another protocol by an 'upgrade' request, with the bytes the
+// client sent after the request head
+pub struct UpgradedConnection {
+    pub reader: ConnectionReader,
+    pub writer: ConnectionWriter,
+    pub head: Vec<u8>,
 }
 
-impl IncomingMessage {
-    pub fn new() -> Self {
-        Self {
-            event_emitter: EventEmitter::new(),
-        }
-    }
+// A connection lent to the response of a served request. It is handed back through `done`
+// once the response finished, and `rejected` fires with a status when the body was malformed
+pub struct LentConnection {
+    pub writer: ConnectionWriter,
+    pub done: oneshot::Sender<Option<ConnectionWriter>>,
+    pub rejected: oneshot::Receiver<u16>,
 }
 
 pub struct Http {
     pub tx: UnboundedSender<Operations>,
+    pub permissions: Arc<Permissions>,
 }
 
 impl Http {
-    pub fn new(tx: UnboundedSender<Operations>) -> Self {
+    pub fn new(tx: UnboundedSender<Operations>, permissions: Arc<Permissions>) -> Self {
         Self { 
             tx,
+            permissions,
         }
     }
-
-    pub fn server_listen(&self, host: String, port: u16, js_callback_global: v8::Global<v8::Function>) {
-        let tx = self.tx.clone();
-        
-        tokio::task::spawn_local(async move {
-            let listener = match tokio::net::TcpListener::bind((host.as_str(), port)).await {
-                Ok(listener) => listener,
-                Err(e) => {
-                    eprintln!("Failed to bind to {}:{}", host, port);
-                    eprintln!("{}", e);
-                    return;
-                }
-            };
-
-            loop {
-                match listener.accept().await {
-                    Ok((mut socket, _)) => {
-                        if let Some(req) = parse_http_request(&mut socket).await {
-                            let http_operation = Operations::Http(HttpOperation::Listen(req, socket, js_callback_global.clone()));
-                            tx.send(http_operation).unwrap();
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to accept connection: {}", e);
-                    }
-                }
-            }
-        });
-    }
-
-    pub fn get_request(&self, url: String, callback: v8::Global<v8::Function>) {
-        let tx = self.tx.clone();
-
-        tokio::task::spawn_local(async move {
-            match Url::parse(&url) {
-                Ok(parsed_url) => {
-                    let hostname = match parsed_url.host_str() {
-                        Some(host) => host.to_string(),
-                        None => {
-                            eprintln!("Invalid hostname in URL");
-                            return;
-                        }
-                    };
-                    let port = parsed_url.port_or_known_default().unwrap_or(80);
-                    let path = parsed_url.path().to_owned();
-
-                    match tokio::net::TcpStream::connect((hostname.as_str(), port)).await {
-                        Ok(mut socket) => {
-                            let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, hostname);
-                            if let Err(e) = socket.write_all(request.as_bytes()).await {
-                                eprintln!("Failed to send HTTP request: {}", e);
-                                return;
-                            }
-
-                            //Register the callback
-                            let incoming_message = Arc::new(Mutex::new(IncomingMessage::new()));
-                            let res = incoming_message.clone();
-                            
-                            let (sender, receiver) = oneshot::channel::<bool>();
-
-                            let http_operation = Operations::Http(HttpOperation::Get(res, callback, sender));
-                            tx.send(http_operation).unwrap();
-
-                            //Blocking until outercallback has finished running
-                            match receiver.await {
-                                Ok(value) => {
-
-                                }
-                                Err(e) => {
-                                    println!("Sender dropped");
-                                    eprintln!("Failed to send HTTP request: {}", e);
-                                    return;
-                                }
-                            }
-
-                            //need to handle getting the headers here
-
-                            println!("Starting to read from the socket");
-                            let mut buffer = [0u8, 255];
-                            loop{
-                                match socket.read(&mut buffer).await {
-                                    //EOF
-                                    Ok(0) => {
-                                        let res = incoming_message.clone();
-                                        let op = Operations::Response(ResponseEvent::End{ res }); 
-                                        tx.send(op);
-                                        break;
-                                    }
-
-                                    //Data recieved
-                                    Ok(n) => {
-                                        let res = incoming_message.clone();
-                                        let chunk = buffer[..n].to_vec();
-                                        let op = Operations::Response(ResponseEvent::Data{ res, chunk }); 
-                                        tx.send(op);
-                                    }
-
-                                    //Error
-                                    Err(e) => {
-                                        eprintln!("Error reading from socket: {}", e);
-                                        let res = incoming_message.clone();
-                                        let error_message = e.to_string();
-                                        let op = Operations::Response(ResponseEvent::Error{ res, error_message });
-                                        tx.send(op);
-                                    }
-                                }
-                            }
-
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to connect to {}:{} - {}", hostname, port, e);
-                        }
-                    }
-                }
-                Err(e) => eprintln!("Failed to parse URL: {}", e),
-            }
-        });
-    }
-
-    //add support for headers later...
-    pub fn request(
-        &self, 
-        options: HashMap<String, String>, 
-        callback: v8::Global<v8::Function>
-    ) -> Option<(Request, std::net::TcpStream)> {
-        let tx = self.tx.clone();
-        let hostname = options.get("hostname").unwrap().to_string();
-        let port = options.get("port").unwrap().parse::<u16>().unwrap_or(80);
-        let method = options.get("method").unwrap_or(&"GET".to_string()).to_string();
-        let path = options.get("path").unwrap().to_string();
-        let headers: HashMap<String, String> = HashMap::new();
-
-        let request = Request {
-            method,
-            url: format!("{}{}", hostname, path),
-            headers,
-            body: String::new(),
-            tx_request: Some(tx.clone()),
-        };
-
-        match std::net::TcpStream::connect((hostname.as_str(), port)){
-            Ok(socket) => {
-                Some((request, socket))
-            }
-
-            Err(e) => {
-                eprintln!("Failed to connect to {}:{} - {}", hostname, port, e);
-                None
-            }
-        }
-
-    }
-}
-
-// V8 Callbacks
-pub fn create_server_callback(
-    scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
-    mut rv: v8::ReturnValue,
-) {
-    // Retrieve pointer to Rust Http Struct
-    let js_server_obj = args.this(); // we are in server object not http
-    let internal_field = js_server_obj.get_internal_field(scope, 0).unwrap();
-    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-
-    // Parse Callback
-    let js_callback = args.get(0);
-    let js_callback_function = v8::Local::<v8::Function>::try_from(js_callback).unwrap();
-
-    let object_template = v8::ObjectTemplate::new(scope);
-    object_template.set_internal_field_count(1);
-    let server_obj = object_template.new_instance(scope).unwrap(); 
-
-    // Store Http within the server object
-    server_obj.set_internal_field(0, external_http.into());
-
-    // Store callback within the server object 
-    let callback_key = v8::String::new(scope, "requestHandler").unwrap();
-    server_obj.set(scope, callback_key.into(), js_callback.into());
-
-    // Attach the listen function to this object
-    let listen_fn = v8::FunctionTemplate::new(scope, http_server_listen_callback);
-    let listen_key = v8::String::new(scope, "listen").unwrap();
-    let listen_func = listen_fn.get_function(scope).unwrap();
-    server_obj.set(scope, listen_key.into(), listen_func.into());
-
-    rv.set(server_obj.into());
-}
-
-pub fn http_server_listen_callback(
-    scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
-    rv : v8::ReturnValue,
-) {
-    // let raw_ptr = retrieve_tx(scope, "channel").unwrap();
-    // let tx = unsafe { &*raw_ptr };   // Retrieve pointer to Rust Http Struct
-
-    let js_server_obj = args.this(); // we are in server object not http
-    let internal_field = js_server_obj.get_internal_field(scope, 0).unwrap();
-    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-    let http_ptr = unsafe { &*(external_http.value() as *mut Http) };
-
-    // Get the 'requestHandler' callback function from the server object
-    let callback_key = v8::String::new(scope, "requestHandler").unwrap();
-    let callback_value = js_server_obj.get(scope, callback_key.into()).unwrap();
-    let js_callback = v8::Local::<v8::Function>::try_from(callback_value).unwrap();
-    let js_callback_global= v8::Global::new(scope, js_callback);
-
-    // Parse arguements (with defaults) 
-    let port = if args.length() > 0 && args.get(0).is_number() {
-        args.get(0).integer_value(scope).unwrap() as u16
-    } else {
-        8000
-    };
-
-    let host = if args.length() > 1 && args.get(1).is_string() {
-        args.get(1).to_rust_string_lossy(scope)
-    } else {
-        "127.0.0.1".to_string()
-    };
-
-
-    // Call the listen method on the Http instance
-
-    http_ptr.server_listen(host, port, js_callback_global);
-}
-
-pub fn get_request_callback(
-    scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
-    rv: v8::ReturnValue,
-) {
-    // Retrieve pointer to Rust Http Struct
-    let js_http_obj = args.this();
-    let internal_field = js_http_obj.get_internal_field(scope, 0).unwrap();
-    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-    let http_ptr = unsafe { &*(external_http.value() as *mut Http) };
-
-    // Parse arguements
-    let url = args.get(0).to_rust_string_lossy(scope);
-    let js_callback = args.get(1);
-
-    // Convert callback to persistent handle
-    let callback_function = v8::Local::<v8::Function>::try_from(js_callback).unwrap();
-    let persistent_callback = v8::Global::new(scope, callback_function);
-
-    http_ptr.get_request(url, persistent_callback);
-}
-
-pub fn create_request_callback(
-    scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
-    mut rv: v8::ReturnValue,
-) {
-    // Retrieve pointer to Rust Http Struct
-    let js_http_obj = args.this();
-    let internal_field = js_http_obj.get_internal_field(scope, 0).unwrap();
-    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-    let http_ptr = unsafe { &*(external_http.value() as *mut Http) };
-
-    // Parse arguements
-    let options_obj = args.get(0).to_object(scope).unwrap();
-    let js_callback = args.get(1);
-
-    // Convert callback to persistent handle
-    let callback_function = v8::Local::<v8::Function>::try_from(js_callback).unwrap();
-    let persistent_callback = v8::Global::new(scope, callback_function);
-
-    // Parse Options object
-    let mut options = HashMap::new();
-    let keys = vec!["hostname", "method", "port", "path"];
-
-    for key in &keys {
-        let key_str = v8::String::new(scope, key).unwrap();
-        let value = options_obj.get(scope, key_str.into()).unwrap().to_rust_string_lossy(scope);
-        options.insert(key.to_string(), value);
-    }
-
-    match http_ptr.request(options, persistent_callback.clone()) {
-        Some((request, socket)) => {
-            // Proceed if `request` was successful
-            let tokio_socket = tokio::net::TcpStream::from_std(socket).unwrap();
-            let boxed_socket = Box::new(tokio_socket);
-            let boxed_request = Box::new(request);
-            let request_obj = create_request_object(scope, boxed_request, Some(boxed_socket), Some(persistent_callback));
-            rv.set(request_obj.into());
-        }
-        None => {
-            
-            // Optionally set the return value to undefined or a placeholder object
-            println!("Failed to return request object");
-            rv.set(v8::undefined(scope).into());
-        }
-    }
-
 }
 
 pub fn initialize_http(
     scope: &mut v8::ContextScope<'_, v8::HandleScope<'_>>,
-    tx: UnboundedSender<Operations>
+    tx: UnboundedSender<Operations>,
+    permissions: Arc<Permissions>
 ){
     let http_template = v8::ObjectTemplate::new(scope);
     http_template.set_internal_field_count(1); // Store the Rust Response struct and socket internally
@@ -364,203 +95,508 @@ pub fn initialize_http(
     http_obj.set(scope, create_server_key.into(), create_server_fn.into());
     http_obj.set(scope, get_key.into(), get_fn.into());
     http_obj.set(scope, request_key.into(), request_fn.into());
+    initialize_agent(scope, http_obj);
 
     let context = scope.get_current_context();
     let global = context.global(scope);
     let global_key = v8::String::new(scope, "http").unwrap();
 
     // Create a Rust File object and wrap it in External
-    let http = Http::new(tx.clone());
+    let http = Http::new(tx.clone(), permissions);
     let boxed_http = Box::new(http);
     let external_http = v8::External::new(scope, Box::into_raw(boxed_http) as *const _ as *mut c_void);
 
     // Set the Rust Response object as an internal field of the JS object
     http_obj.set_internal_field(0, external_http.into());
     global.set(scope, global_key.into(), http_obj.into());
+
+    initialize_https(scope, http_obj, external_http);
+    initialize_http2(scope, external_http);
+    initialize_fetch(scope, external_http);
+    initialize_websocket(scope, http_obj, external_http);
+    initialize_static(scope, http_obj, external_http);
 }
 
-pub fn incoming_message_on_callback(
-    scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
-    mut rv: v8::ReturnValue,
+// Serves requests on one connection until either side closes it. Each request head is handed
+// to JS, then its body is streamed as 'data' and 'end' events on the request. Body events use
+// the same channel as Listen so they arrive after the handler ran. The next request, which may
+// already be buffered when the client pipelines, is only read once the response gave the
+// connection back, so responses are always written in order
+pub async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin + 'static>(
+    socket: S,
+    remote_address: Option<SocketAddr>,
+    server: Rc<ServerState>,
+    handle: Rc<ConnectionHandle>
 ) {
-    // Retrieve the 'this' object
-    let js_response_obj = args.this();
-
-    // Parse arguements 
-    let event = args.get(0).to_rust_string_lossy(scope);
-    let callback = v8::Local::<v8::Function>::try_from(args.get(1)).unwrap();
-    //let global_callback = v8::Global::new(scope, callback);
-
-    queue_stream_event_in_object(scope, js_response_obj, &event.clone(), callback);
-    
-    if event == "end"{
-        // Get IncommingMessage object
-        let internal_field = js_response_obj.get_internal_field(scope, 0).unwrap();
-        let external_response = v8::Local::<v8::External>::try_from(internal_field).unwrap();
-        let incoming_message_ptr = external_response.value() as *const Mutex<IncomingMessage>;
-        let incoming_message: Arc<Mutex<IncomingMessage>> = unsafe { Arc::from_raw(incoming_message_ptr) };
-        let incoming_message_clone = incoming_message.clone();
-        let mut incoming_message_gaurd = incoming_message.lock().unwrap();
-
-        trigger_event_callbacks(scope, js_response_obj, incoming_message_gaurd);
+    let tx = server.tx.clone();
+    let mut closing = server.closing_signal();
+    let (mut reader, writer) = tokio::io::split(socket);
+    let mut writer: ConnectionWriter = Box::new(writer);
+    let mut buffer = Vec::new();
+    let mut first_request = true;
+
+    loop {
+        // A new connection may stay quiet for server.timeout, an idle keep-alive one for keepAliveTimeout
+        if buffer.is_empty() {
+            let idle_timeout = if first_request { server.socket_timeout() } else { server.keep_alive_timeout() };
+            handle.set_idle(true);
+            let wait = wait_for_request(&mut reader, &mut buffer, idle_timeout, &mut closing).await;
+            handle.set_idle(false);
+            match wait {
+                Wait::Request => {}
+                Wait::Closed => break,
+                Wait::TimedOut => {
+                    if first_request {
+                        server.emit_socket_event("timeout", remote_address);
+                    }
+                    break;
+                }
+            }
+        }
+        first_request = false;
+
+        // The head and the whole request are timed from their first byte, so a client trickling
+        // bytes can't hold the connection. server.timeout can only shorten this
+        let started = Instant::now();
+        let request_deadline = server.request_timeout().map(|timeout| started + timeout);
+        let head_deadline = [server.headers_timeout(), server.request_timeout(), server.socket_timeout()]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|timeout| started + timeout);
+
+        let parsed = match head_deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, parse_http_request(&mut reader, &mut buffer, server.max_header_size)).await.ok(),
+            None => Some(parse_http_request(&mut reader, &mut buffer, server.max_header_size).await),
+        };
+        let mut head = match parsed {
+            Some(Ok(Some(head))) => head,
+            Some(Ok(None)) => break,
+            Some(Err(HeadError::Io(e))) => {
+                if !is_disconnect(&e) {
+                    eprintln!("Failed to read HTTP request: {}", e);
+                }
+                break;
+            }
+            Some(Err(HeadError::Malformed(e))) => {
+                eprintln!("Failed to parse HTTP request: {}", e);
+                reject_request(&mut writer, 400).await;
+                return;
+            }
+            Some(Err(HeadError::TooLarge)) => {
+                reject_request(&mut writer, 431).await;
+                return;
+            }
+            None => {
+                reject_request(&mut writer, 408).await;
+                return;
+            }
+        };
+
+        head.request.remote_address = remote_address;
+
+        // Upgrades leave HTTP for good, the server stops tracking the connection. Without
+        // 'upgrade' listeners they are answered as plain requests
+        if head.upgrade && server.event_emitter.borrow().has_listeners("upgrade") {
+            head.request.complete.set(true);
+            let connection = UpgradedConnection { reader: Box::new(reader), writer, head: buffer };
+            let op = HttpOperation::Upgrade { request: head.request, connection, emitter: server.event_emitter.clone() };
+            let _ = tx.send(Operations::Http(op));
+            return;
+        }
+
+        let emitter = head.request.event_emitter.clone();
+        let complete = head.request.complete.clone();
+        let (done, finished) = oneshot::channel::<Option<ConnectionWriter>>();
+        let (reject, rejected) = oneshot::channel::<u16>();
+        let http_operation = Operations::Http(HttpOperation::Listen {
+            request: head.request,
+            connection: LentConnection { writer, done, rejected },
+            version: head.version,
+            keep_alive: head.keep_alive,
+            emitter: server.event_emitter.clone(),
+            closing: server.closing_signal(),
+        });
+        tx.send(http_operation).unwrap();
+
+        // The response owns the connection now, a body that is too slow is answered through it
+        let last_read = Cell::new(Instant::now());
+        let body = read_body(&mut reader, &mut buffer, head.body_length, server.max_header_size, |chunk| {
+            last_read.set(Instant::now());
+            let op = StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Bytes(chunk) };
+            let _ = tx.send(Operations::Stream(op));
+        });
+        let result = tokio::select! {
+            result = body => result,
+            _ = sleep_until(request_deadline) => Err(io::Error::new(io::ErrorKind::TimedOut, "Request timeout")),
+            _ = inactivity(&last_read, server.socket_timeout()) => {
+                server.emit_socket_event("timeout", remote_address);
+                Err(io::Error::new(io::ErrorKind::TimedOut, "Socket timeout"))
+            }
+        };
+
+        let body_complete = result.is_ok();
+        let reject_status = match &result {
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Some(400),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Some(408),
+            _ => None,
+        };
+        let op = match result {
+            Ok(()) => {
+                complete.set(true);
+                StreamEvent::Emit{ emitter: emitter.clone(), event: "end" }
+            }
+            Err(e) if is_disconnect(&e) => StreamEvent::Emit{ emitter: emitter.clone(), event: "aborted" },
+            Err(e) => StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() },
+        };
+        let _ = tx.send(Operations::Stream(op));
+
+        // Without the whole body the connection can't be reused, and the client may be gone.
+        // A malformed or late body is answered with 400 or 408 unless the response was already
+        // started, then the connection is destroyed so the response can't keep it open
+        if !body_complete {
+            if let Some(status) = reject_status {
+                let _ = reject.send(status);
+                let _ = tokio::time::timeout(REJECT_WRITE_TIMEOUT, finished).await;
+            }
+            handle.destroy();
+            let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+            return;
+        }
+
+        // The response hands the connection back when it may be reused
+        let finished = finished.await;
+        let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+        writer = match finished {
+            Ok(Some(writer)) => writer,
+            _ => return,
+        };
+
+        // A closing server lets busy connections finish their response, then ends them
+        if server.is_closing() {
+            break;
+        }
     }
-    
-    rv.set(v8::undefined(scope).into())
+
+    let _ = writer.shutdown().await;
 }
 
-pub fn trigger_event_callbacks(
-    scope: &mut v8::HandleScope,
-    js_response_obj: v8::Local<v8::Object>,
-    mut incoming_message: MutexGuard<'_, IncomingMessage> 
-) {
-    // Retrieve the "eventCallbacks" object from `this`
-    let event_callbacks_key = v8::String::new(scope, "eventCallbacks").unwrap();
-    let event_callbacks_value = js_response_obj.get(scope, event_callbacks_key.into()).unwrap();
+enum Wait {
+    Request,
+    Closed,
+    TimedOut,
+}
+
+// Waits until the next request starts to arrive. Idle connections end when the server
+// closes, or after `idle_timeout`
+async fn wait_for_request<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    idle_timeout: Option<Duration>,
+    closing: &mut watch::Receiver<bool>
+) -> Wait {
+    let read = async {
+        let more = match idle_timeout {
+            Some(idle_timeout) => match tokio::time::timeout(idle_timeout, fill_buffer(reader, buffer)).await {
+                Ok(more) => more,
+                Err(_) => return Wait::TimedOut,
+            },
+            None => fill_buffer(reader, buffer).await,
+        };
+        if matches!(more, Ok(true)) { Wait::Request } else { Wait::Closed }
+    };
 
-    if !event_callbacks_value.is_object() {
-        // If there is no "eventCallbacks" object, return early
-        return;
+    tokio::select! {
+        wait = read => wait,
+        _ = closing.wait_for(|closing| *closing) => Wait::Closed,
     }
+}
+
+// Completes at the deadline, never without one
+pub async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+// Completes once nothing was read for `timeout`
+async fn inactivity(last_read: &Cell<Instant>, timeout: Option<Duration>) {
+    let Some(timeout) = timeout else {
+        return std::future::pending().await;
+    };
+    loop {
+        let deadline = last_read.get() + timeout;
+        if Instant::now() >= deadline {
+            return;
+        }
+        tokio::time::sleep_until(deadline).await;
+    }
+}
 
-    let event_callbacks_obj = event_callbacks_value.to_object(scope).unwrap();
+// Answers a request that won't be handed to JS, then closes the connection
+pub async fn reject_request(writer: &mut ConnectionWriter, status_code: u16) {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        status_code,
+        status_reason(status_code)
+    );
+    let _ = writer.write_all(response.as_bytes()).await;
+    let _ = writer.shutdown().await;
+}
 
-    // Convert the event name to a V8 string
-    let data_key = v8::String::new(scope, "data").unwrap();
-    let end_key = v8::String::new(scope, "end").unwrap();
+// The client closed the connection before the request was complete
+fn is_disconnect(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe
+    )
+}
 
-    let data_callbacks_array_value = event_callbacks_obj.get(scope, data_key.into()).unwrap();
-    let end_callbacks_array_value = event_callbacks_obj.get(scope, end_key.into()).unwrap();
+// A parsed request head and what it says about the rest of the exchange
+pub struct RequestHead {
+    pub request: Request,
+    pub body_length: BodyLength,
+    // Minor version, HTTP/1.0 clients can't receive chunked responses
+    pub version: u8,
+    pub keep_alive: bool,
+    // Connection: upgrade with an Upgrade header, such as a WebSocket handshake
+    pub upgrade: bool,
+}
 
-    let data_callbacks_array = v8::Local::<v8::Array>::try_from(data_callbacks_array_value).unwrap();
-    let end_callbacks_array = v8::Local::<v8::Array>::try_from(end_callbacks_array_value).unwrap();
+// Why a request head could not be read, which decides what the client is told
+pub enum HeadError {
+    // The connection failed, there is no one to answer
+    Io(io::Error),
+    // 400 Bad Request
+    Malformed(String),
+    // 431 Request Header Fields Too Large
+    TooLarge,
+}
 
-    let data_array_length = data_callbacks_array.length();
-    let end_array_length = end_callbacks_array.length();
+// Reads from the socket until a complete request head is buffered. The head is removed
+// from `buffer`, leaving whatever part of the body was read along with it. Heads longer
+// than `max_header_size` are refused rather than buffered without bound
+pub async fn parse_http_request<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    max_header_size: usize
+) -> Result<Option<RequestHead>, HeadError> {
+    loop {
+        if !buffer.is_empty() {
+            let parsed = parse_request_head(buffer).map_err(|e| HeadError::Malformed(e.to_string()))?;
+            match parsed {
+                Some((_, head_length)) if head_length > max_header_size => return Err(HeadError::TooLarge),
+                Some((head, head_length)) => {
+                    buffer.drain(..head_length);
+                    return Ok(Some(head));
+                }
+                None if buffer.len() > max_header_size => return Err(HeadError::TooLarge),
+                None => {}
+            }
+        }
 
-    // Iterate through the array of callbacks
-    for i in 0..data_array_length {
-        let callback_value = data_callbacks_array.get_index(scope, i).unwrap();
-        let callback_function = v8::Local::<v8::Function>::try_from(callback_value).unwrap();
-        let persistent_callback = v8::Global::new(scope, callback_function);
-        incoming_message.event_emitter.on("data".to_string(), persistent_callback);
+        if !fill_buffer(reader, buffer).await.map_err(HeadError::Io)? {
+            // A connection closed between requests is not an error
+            if buffer.is_empty() {
+                return Ok(None);
+            }
+            return Err(HeadError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before the request head was complete")));
+        }
     }
+}
+
+fn parse_request_head(data: &[u8]) -> io::Result<Option<(RequestHead, usize)>> {
+    // Start with room for 64 headers and grow when a request has more
+    let mut header_capacity = 64;
 
-    for i in 0..end_array_length {
-        let callback_value = end_callbacks_array.get_index(scope, i).unwrap();
-        let callback_function = v8::Local::<v8::Function>::try_from(callback_value).unwrap();
-        let persistent_callback = v8::Global::new(scope, callback_function);
-        incoming_message.event_emitter.on("end".to_string(), persistent_callback);
+    loop {
+        let mut headers = vec![httparse::EMPTY_HEADER; header_capacity];
+        let mut req = httparse::Request::new(&mut headers);
+
+        let head_length = match req.parse(data) {
+            Ok(httparse::Status::Complete(head_length)) => head_length,
+            Ok(httparse::Status::Partial) => return Ok(None),
+            Err(httparse::Error::TooManyHeaders) => {
+                header_capacity *= 2;
+                continue;
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        };
+
+        let headers = Headers::from_httparse(req.headers);
+        let body_length = request_body_length(&headers)?;
+        let version = req.version.unwrap_or(1);
+        let keep_alive = request_keep_alive(version, &headers);
+        let upgrade = request_upgrade(&headers);
+
+        let mut request = Request::new(
+            req.method.unwrap_or("").to_string(),
+            req.path.unwrap_or("").to_string(),
+            headers,
+            String::new()
+        );
+        request.http_version = format!("1.{}", version);
+
+        return Ok(Some((RequestHead { request, body_length, version, keep_alive, upgrade }, head_length)));
     }
 }
 
-pub fn queue_stream_event_in_object(
-    scope: &mut v8::HandleScope,
-    js_response_obj: v8::Local<v8::Object>,
-    event: &str,
-    local_callback: v8::Local<v8::Function>,
-) {
-    // Initialize or retrieve `this.eventCallbacks`
-    let event_callbacks_key = v8::String::new(scope, "eventCallbacks").unwrap();
-    let event_callbacks_value = js_response_obj.get(scope, event_callbacks_key.into()).unwrap();
+// How the body following a message head is delimited
+pub enum BodyLength {
+    Empty,
+    Fixed(usize),
+    Chunked,
+    // Responses without a length end when the server closes the connection
+    UntilClose,
+}
+
+// HTTP/1.1 connections persist unless the peer asks to close, HTTP/1.0 ones only on request
+pub fn request_keep_alive(version: u8, headers: &Headers) -> bool {
+    let connection = headers.get_joined("Connection")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let has_token = |token: &str| connection.split(',').any(|value| value.trim() == token);
 
-    let event_callbacks_obj = if event_callbacks_value.is_object() {
-        // Use the existing eventCallbacks object
-        event_callbacks_value.to_object(scope).unwrap()
+    if version == 0 {
+        has_token("keep-alive")
     } else {
-        // Create a new object if eventCallbacks is not already an object
-        let new_obj = v8::Object::new(scope);
-        js_response_obj.set(scope, event_callbacks_key.into(), new_obj.into());
-        new_obj
-    };
+        !has_token("close")
+    }
+}
 
-    // Initialize or retrieve `this.eventCallbacks[event]`
-    let event_key = v8::String::new(scope, &event).unwrap();
-    let callbacks_array_value = event_callbacks_obj.get(scope, event_key.into()).unwrap();
-
-    let callbacks_array = v8::Local::<v8::Array>::try_from(callbacks_array_value)
-    .unwrap_or_else(|_| {
-        // Create a new array if the value is not already an array
-        let new_array = v8::Array::new(scope, 0);
-        event_callbacks_obj.set(scope, event_key.into(), new_array.into());
-        new_array
-    });
-
-    // Add the new callback to the array
-    let array_length = callbacks_array.length();
-    let array_length_value = v8::Number::new(scope, array_length as f64);
-    callbacks_array.set(scope, array_length_value.into(), local_callback.into());
+// The client asks to switch this connection to another protocol
+fn request_upgrade(headers: &Headers) -> bool {
+    let connection = headers.get_joined("Connection")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    headers.get("Upgrade").is_some() && connection.split(',').any(|value| value.trim() == "upgrade")
 }
 
-pub async fn parse_http_request(socket: &mut TcpStream) -> Option<Request> {
-    let mut buffer = [0u8; 1024]; // A fixed size buffer for reading data
-    let mut raw_data = Vec::new();
+// A request without Content-Length or Transfer-Encoding has no body. Framing a proxy in
+// front of the server could read differently is refused, so both always agree where a
+// request ends
+fn request_body_length(headers: &Headers) -> io::Result<BodyLength> {
+    // Repeated Transfer-Encoding headers form one list of codings, Content-Length is ignored
+    if let Some(transfer_encoding) = headers.get_joined("Transfer-Encoding") {
+        if transfer_encoding.to_ascii_lowercase().rsplit(',').next().map(str::trim) == Some("chunked") {
+            return Ok(BodyLength::Chunked);
+        }
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Transfer-Encoding must end with chunked: {}", transfer_encoding)));
+    }
 
-    // Read data from the socket into the buffer
-    match socket.read(&mut buffer).await {
-        Ok(n) if n > 0 => {
-            raw_data.extend_from_slice(&buffer[..n]); // Collect the data into raw_data
+    // Repeated Content-Length headers, or lists in one, must all carry the same length
+    let mut length = None;
+    for value in headers.get_all("Content-Length").flat_map(|value| value.split(',')).map(str::trim) {
+        let parsed = value.parse::<usize>()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid Content-Length: {}", value)))?;
+        if length.is_some_and(|length| length != parsed) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Conflicting Content-Length headers"));
         }
-        Ok(_) => {
-            println!("No data read from socket.");
-            return None;
+        length = Some(parsed);
+    }
+    Ok(length.map_or(BodyLength::Empty, BodyLength::Fixed))
+}
+
+// Appends the next read to `buffer`, returning false once the peer has closed the connection
+pub async fn fill_buffer<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>) -> io::Result<bool> {
+    let mut chunk = [0u8; 8192];
+    let n = reader.read(&mut chunk).await?;
+    buffer.extend_from_slice(&chunk[..n]);
+    Ok(n > 0)
+}
+
+// Passes the body to `on_chunk` as it arrives, starting with the bytes already in `buffer`.
+// Bytes past the end of the body are left in `buffer`
+pub async fn read_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    body_length: BodyLength,
+    max_line: usize,
+    mut on_chunk: impl FnMut(Vec<u8>)
+) -> io::Result<()> {
+    match body_length {
+        BodyLength::Empty => Ok(()),
+        BodyLength::Fixed(length) => read_fixed_body(reader, buffer, length, &mut on_chunk).await,
+        BodyLength::Chunked => read_chunked_body(reader, buffer, max_line, &mut on_chunk).await,
+        BodyLength::UntilClose => {
+            loop {
+                if !buffer.is_empty() {
+                    on_chunk(std::mem::take(buffer));
+                }
+                if !fill_buffer(reader, buffer).await? {
+                    return Ok(());
+                }
+            }
         }
-        Err(e) => {
-            println!("Failed to read from socket: {}", e);
-            return None;
+    }
+}
+
+async fn read_fixed_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    length: usize,
+    on_chunk: &mut impl FnMut(Vec<u8>)
+) -> io::Result<()> {
+    let mut remaining = length;
+
+    while remaining > 0 {
+        if buffer.is_empty() && !fill_buffer(reader, buffer).await? {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before the body was complete"));
         }
+
+        let n = remaining.min(buffer.len());
+        on_chunk(buffer.drain(..n).collect());
+        remaining -= n;
     }
 
-    let mut headers = [httparse::EMPTY_HEADER; 16]; // A fixed size buffer for headers
-    let mut req = httparse::Request::new(&mut headers);
+    Ok(())
+}
 
-    match req.parse(&raw_data) {
-        Ok(status) => {
-            if status.is_partial() {
-                println!("Partial HTTP request. Waiting for more data.");
-                return None;
-            }
+async fn read_chunked_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    max_line: usize,
+    on_chunk: &mut impl FnMut(Vec<u8>)
+) -> io::Result<()> {
+    loop {
+        // Chunk size in hex, optionally followed by ;extensions
+        let size_line = read_line(reader, buffer, max_line).await?;
+        let size_line = String::from_utf8_lossy(&size_line);
+        let size_text = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_text, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid chunk size: {}", size_text)))?;
+
+        if size == 0 {
+            // Skip the trailer section, which ends with an empty line
+            while !read_line(reader, buffer, max_line).await?.is_empty() {}
+            return Ok(());
+        }
 
-            // Extract the method, path, and headers
-            let method = req.method.unwrap_or("").to_string();
-            let url = req.path.unwrap_or("").to_string();
-
-            let headers = req.headers.iter().fold(HashMap::new(), |mut map, header| {
-                map.insert(
-                    header.name.to_string(),
-                    std::str::from_utf8(header.value).unwrap_or("").to_string(),
-                );
-                map
-            });
-
-            // If there is a Content-Length header, use it to read the body
-            let body_offset = status.unwrap();
-            let body = if let Some(content_length) = headers.get("Content-Length") {
-                let length: usize = content_length.parse().unwrap_or(0);
-                if body_offset + length <= raw_data.len() {
-                    Some(
-                        std::str::from_utf8(&raw_data[body_offset..(body_offset + length)])
-                            .unwrap_or("")
-                            .to_string(),
-                    )
-                } else {
-                    None // Incomplete body
-                }
-            } else {
-                None // No body
-            };
-
-            Some(Request {
-                method,
-                url,
-                headers,
-                body: body.unwrap_or_default(),
-                tx_request: None,
-            })
+        read_fixed_body(reader, buffer, size, on_chunk).await?;
+
+        if !read_line(reader, buffer, max_line).await?.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Missing CRLF after chunk data"));
+        }
+    }
+}
+
+// Takes the next CRLF terminated line out of `buffer`, without the line ending. Lines longer
+// than `max_line` are refused rather than buffered without bound
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>, max_line: usize) -> io::Result<Vec<u8>> {
+    loop {
+        let end = buffer.windows(2).position(|window| window == b"\r\n");
+        if end.unwrap_or(buffer.len()) > max_line {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Chunked body line longer than {} bytes", max_line)));
         }
-        Err(e) => {
-            println!("Error parsing HTTP request: {}", e);
-            None
+        if let Some(end) = end {
+            let line = buffer[..end].to_vec();
+            buffer.drain(..end + 2);
+            return Ok(line);
+        }
+
+        if !fill_buffer(reader, buffer).await? {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed in the middle of a chunked body"));
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,978 @@
+use rusty_v8 as v8;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use bytes::Bytes;
+use h2::client::{Connection, ResponseFuture, SendRequest};
+use h2::server::SendResponse;
+use h2::{Reason, RecvStream, SendStream};
+use http::HeaderMap;
+use url::Url;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::future::poll_fn;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::client::{option, tls_options};
+use crate::emitter::{attach_event_emitter, emit_event, EventEmitter};
+use crate::headers::{header_values_from_js, headers_to_object, Headers};
+use crate::helper::{set_function, throw_error, value_to_bytes};
+use crate::http::{sleep_until, Http};
+use crate::interface::{HttpOperation, Operations, StreamEvent};
+use crate::request::Request;
+use crate::server::{create_server, ConnectionHandle, ServerState};
+use crate::stream::{Chunk, Utf8Decoder, WRITE_HIGH_WATER_MARK};
+use crate::tls::{Socket, TlsOptions};
+
+// The SETTINGS a server or session sends, h2's defaults for the ones left out
+#[derive(Clone, Copy, Default)]
+pub struct Http2Settings {
+    initial_window_size: Option<u32>,
+    max_frame_size: Option<u32>,
+    max_concurrent_streams: Option<u32>,
+    max_header_list_size: Option<u32>,
+}
+
+impl Http2Settings {
+    // options.settings, Node's names
+    pub fn from_js(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>) -> Self {
+        let settings = options
+            .and_then(|options| option(scope, options, "settings"))
+            .and_then(|settings| v8::Local::<v8::Object>::try_from(settings).ok());
+        let Some(settings) = settings else {
+            return Http2Settings::default();
+        };
+
+        let mut setting = |name: &str| {
+            option(scope, settings, name)
+                .and_then(|value| value.integer_value(scope))
+                .map(|value| value.clamp(0, u32::MAX as i64) as u32)
+        };
+        Http2Settings {
+            initial_window_size: setting("initialWindowSize"),
+            max_frame_size: setting("maxFrameSize"),
+            max_concurrent_streams: setting("maxConcurrentStreams"),
+            max_header_list_size: setting("maxHeaderListSize"),
+        }
+    }
+
+    fn server_builder(&self, max_header_size: usize) -> h2::server::Builder {
+        let mut builder = h2::server::Builder::new();
+        builder.max_header_list_size(self.max_header_list_size.unwrap_or(max_header_size as u32));
+        if let Some(size) = self.initial_window_size {
+            builder.initial_window_size(size);
+        }
+        if let Some(size) = self.max_frame_size {
+            builder.max_frame_size(size);
+        }
+        if let Some(max) = self.max_concurrent_streams {
+            builder.max_concurrent_streams(max);
+        }
+        builder
+    }
+
+    // Clients never accept pushed streams
+    fn client_builder(&self) -> h2::client::Builder {
+        let mut builder = h2::client::Builder::new();
+        builder.enable_push(false);
+        if let Some(size) = self.initial_window_size {
+            builder.initial_window_size(size);
+        }
+        if let Some(size) = self.max_frame_size {
+            builder.max_frame_size(size);
+        }
+        if let Some(max) = self.max_concurrent_streams {
+            builder.max_concurrent_streams(max);
+        }
+        if let Some(max) = self.max_header_list_size {
+            builder.max_header_list_size(max);
+        }
+        builder
+    }
+}
+
+// What makes an http2 server different from an http one
+#[derive(Clone, Copy)]
+pub struct Http2Options {
+    pub settings: Http2Settings,
+    // Secure servers also serve clients that only offer http/1.1 through ALPN
+    pub allow_http1: bool,
+}
+
+impl Http2Options {
+    pub fn from_js(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, secure: bool) -> Self {
+        let allow_http1 = secure && options
+            .and_then(|options| option(scope, options, "allowHTTP1"))
+            .is_some_and(|allow| allow.boolean_value(scope));
+        Http2Options { settings: Http2Settings::from_js(scope, options), allow_http1 }
+    }
+}
+
+// Header names are sent in lowercase, the ones HTTP/2 can't carry are dropped
+pub fn header_map(headers: &Headers) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers.iter() {
+        let name = http::HeaderName::from_bytes(name.as_bytes());
+        let value = http::HeaderValue::from_str(value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            map.append(name, value);
+        }
+    }
+    map
+}
+
+pub fn headers_from_map(map: &HeaderMap) -> Headers {
+    let mut headers = Headers::new();
+    for (name, value) in map {
+        headers.append(name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string());
+    }
+    headers
+}
+
+// Sends `data` as fast as the peer's flow control window allows
+pub async fn send_data(stream: &mut SendStream<Bytes>, data: Vec<u8>, end_of_stream: bool) -> Result<(), h2::Error> {
+    let mut data = Bytes::from(data);
+    if data.is_empty() {
+        return match end_of_stream {
+            true => stream.send_data(data, true),
+            false => Ok(()),
+        };
+    }
+
+    while !data.is_empty() {
+        stream.reserve_capacity(data.len());
+        let capacity = match poll_fn(|cx| stream.poll_capacity(cx)).await {
+            Some(capacity) => capacity?,
+            None => return Err(Reason::STREAM_CLOSED.into()),
+        };
+        let chunk = data.split_to(capacity.min(data.len()));
+        stream.send_data(chunk, end_of_stream && data.is_empty())?;
+    }
+    Ok(())
+}
+
+// Hands each DATA frame to `on_chunk` and returns the trailers. The window is given back
+// right away, the bytes are buffered in the event loop instead
+async fn receive_body(body: &mut RecvStream, mut on_chunk: impl FnMut(Vec<u8>)) -> Result<Option<HeaderMap>, h2::Error> {
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        on_chunk(chunk.to_vec());
+    }
+    body.trailers().await
+}
+
+// Serves the streams of one HTTP/2 connection, each as a 'request' with its response like
+// HTTP/1.x requests. A closing server sends GOAWAY and lets the open streams finish
+pub async fn serve_http2_connection<S: AsyncRead + AsyncWrite + Unpin + 'static>(
+    socket: S,
+    remote_address: Option<SocketAddr>,
+    server: Rc<ServerState>,
+    handle: Rc<ConnectionHandle>,
+    settings: Http2Settings
+) {
+    let mut closing = server.closing_signal();
+
+    // The preface and SETTINGS have as long as a request head would
+    let handshake = settings.server_builder(server.max_header_size).handshake::<_, Bytes>(socket);
+    let connection = match server.headers_timeout() {
+        Some(timeout) => tokio::time::timeout(timeout, handshake).await.ok(),
+        None => Some(handshake.await),
+    };
+    let mut connection = match connection {
+        Some(Ok(connection)) => connection,
+        Some(Err(e)) => {
+            if !e.is_io() {
+                eprintln!("Failed to start HTTP/2 connection: {}", e);
+            }
+            return;
+        }
+        None => return,
+    };
+
+    let mut shutting_down = false;
+    loop {
+        // Without open streams closeIdleConnections() may end the connection
+        handle.set_idle(!connection.has_streams());
+        let idle_deadline = server.keep_alive_timeout().map(|timeout| Instant::now() + timeout);
+        let accepted = tokio::select! {
+            accepted = connection.accept() => accepted,
+            _ = closing.wait_for(|closing| *closing), if !shutting_down => {
+                connection.graceful_shutdown();
+                shutting_down = true;
+                continue;
+            }
+            // A connection without open streams after keepAliveTimeout is closed
+            _ = sleep_until(idle_deadline), if !shutting_down => {
+                if !connection.has_streams() {
+                    connection.graceful_shutdown();
+                    shutting_down = true;
+                }
+                continue;
+            }
+        };
+
+        match accepted {
+            Some(Ok((request, respond))) => dispatch_request(&server, request, respond, remote_address),
+            Some(Err(e)) => {
+                if !e.is_io() && e.reason() != Some(Reason::NO_ERROR) {
+                    eprintln!("HTTP/2 connection error: {}", e);
+                }
+                break;
+            }
+            None => break,
+        }
+    }
+}
+
+// Emits 'request' with the pseudo-headers first in req.headers, like Node, then streams the body
+fn dispatch_request(
+    server: &Rc<ServerState>,
+    request: http::Request<RecvStream>,
+    respond: SendResponse<Bytes>,
+    remote_address: Option<SocketAddr>
+) {
+    let (parts, body) = request.into_parts();
+    let path = parts.uri.path_and_query().map_or("/", |path| path.as_str()).to_string();
+
+    let mut headers = Headers::new();
+    headers.append(":method".to_string(), parts.method.to_string());
+    headers.append(":path".to_string(), path.clone());
+    if let Some(scheme) = parts.uri.scheme_str() {
+        headers.append(":scheme".to_string(), scheme.to_string());
+    }
+    if let Some(authority) = parts.uri.authority() {
+        headers.append(":authority".to_string(), authority.to_string());
+    }
+    for (name, value) in headers_from_map(&parts.headers).iter() {
+        headers.append(name.to_string(), value.to_string());
+    }
+
+    let mut request = Request::new(parts.method.to_string(), path, headers, String::new());
+    request.http_version = "2.0".to_string();
+    request.remote_address = remote_address;
+
+    let emitter = request.event_emitter.clone();
+    let complete = request.complete.clone();
+    let trailers = request.trailers.clone();
+    let op = HttpOperation::Http2Request{ request, respond, emitter: server.event_emitter.clone() };
+    let _ = server.tx.send(Operations::Http(op));
+
+    // Body events use the same channel, so they arrive after the handler ran
+    let tx = server.tx.clone();
+    tokio::task::spawn_local(async move {
+        let mut body = body;
+        let result = receive_body(&mut body, |chunk| {
+            let op = StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Bytes(chunk) };
+            let _ = tx.send(Operations::Stream(op));
+        }).await;
+
+        let op = match result {
+            Ok(received) => {
+                if let Some(received) = received {
+                    *trailers.borrow_mut() = headers_from_map(&received);
+                }
+                complete.set(true);
+                StreamEvent::Emit{ emitter: emitter.clone(), event: "end" }
+            }
+            // The client reset the stream or went away
+            Err(e) if e.is_reset() || e.is_io() => StreamEvent::Emit{ emitter: emitter.clone(), event: "aborted" },
+            Err(e) => StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() },
+        };
+        let _ = tx.send(Operations::Stream(op));
+        let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+    });
+}
+
+// The client side, Node's ClientHttp2Session. Streams are opened on one connection
+pub struct Http2Session {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    commands: UnboundedSender<SessionCommand>,
+    // Sent as :scheme and :authority on every stream
+    scheme: String,
+    authority: String,
+    closed: bool,
+    destroyed: bool,
+}
+
+enum SessionCommand {
+    Request(Box<StreamRequest>),
+    // No new streams, the connection ends once the open ones did
+    Close,
+    Destroy,
+}
+
+// What a stream task needs, handed over when request() is called
+struct StreamRequest {
+    head: http::Request<()>,
+    end_stream: bool,
+    commands: UnboundedReceiver<StreamCommand>,
+    emitter: Rc<RefCell<EventEmitter>>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    encoding: Arc<Mutex<Option<String>>>,
+}
+
+enum StreamCommand {
+    Write(Vec<u8>, usize, Option<v8::Global<v8::Function>>),
+    End(Option<v8::Global<v8::Function>>),
+    Close(Reason),
+}
+
+// Node's ClientHttp2Stream, the request body is written to it and the response read from it
+pub struct Http2Stream {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    commands: UnboundedSender<StreamCommand>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    // Set by setEncoding(), chunks are then emitted as strings
+    encoding: Arc<Mutex<Option<String>>>,
+    // The writable side was ended, by end() or by endStream
+    ended: bool,
+}
+
+impl Http2Session {
+    // Connects right away, requests made before the connection is ready wait for it
+    pub fn connect(host: String, port: u16, tls: Option<TlsOptions>, settings: Http2Settings, tx: UnboundedSender<Operations>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<SessionCommand>();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let authority = match (tls.is_some(), port) {
+            (false, 80) | (true, 443) => host.clone(),
+            _ if host.contains(':') => format!("[{}]:{}", host, port),
+            _ => format!("{}:{}", host, port),
+        };
+
+        let emitter = event_emitter.clone();
+        tokio::task::spawn_local(async move {
+            run_session(host, port, tls, settings, commands_rx, emitter, tx).await;
+        });
+
+        Http2Session { event_emitter, commands, scheme: scheme.to_string(), authority, closed: false, destroyed: false }
+    }
+
+    // Opens a stream. `headers` may set :method (GET), :path (/), :scheme and :authority
+    pub fn request(&self, headers: Headers, end_stream: Option<bool>) -> Result<Http2Stream, String> {
+        if self.closed {
+            return Err("The session has been closed".to_string());
+        }
+
+        let pseudo = |name: &str| headers.get(name).map(str::to_string);
+        let method = pseudo(":method").unwrap_or_else(|| "GET".to_string());
+        let path = pseudo(":path").unwrap_or_else(|| "/".to_string());
+        let scheme = pseudo(":scheme").unwrap_or_else(|| self.scheme.clone());
+        let authority = pseudo(":authority").unwrap_or_else(|| self.authority.clone());
+
+        let mut regular = Headers::new();
+        for (name, value) in headers.iter().filter(|(name, _)| !name.starts_with(':')) {
+            regular.append(name.to_string(), value.to_string());
+        }
+
+        let mut head = http::Request::new(());
+        *head.method_mut() = http::Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("Invalid :method \"{}\"", method))?;
+        *head.uri_mut() = format!("{}://{}{}", scheme, authority, path).parse()
+            .map_err(|_| format!("Invalid :path \"{}\"", path))?;
+        *head.headers_mut() = header_map(&regular);
+
+        // Like Node, requests that carry no payload end their writable side right away
+        let end_stream = end_stream.unwrap_or(matches!(method.as_str(), "GET" | "HEAD" | "DELETE"));
+
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<StreamCommand>();
+        let stream = Http2Stream {
+            event_emitter: Rc::new(RefCell::new(EventEmitter::new())),
+            commands,
+            buffered: Arc::new(AtomicUsize::new(0)),
+            need_drain: Arc::new(AtomicBool::new(false)),
+            encoding: Arc::new(Mutex::new(None)),
+            ended: end_stream,
+        };
+        let request = StreamRequest {
+            head,
+            end_stream,
+            commands: commands_rx,
+            emitter: stream.event_emitter.clone(),
+            buffered: stream.buffered.clone(),
+            need_drain: stream.need_drain.clone(),
+            encoding: stream.encoding.clone(),
+        };
+        let _ = self.commands.send(SessionCommand::Request(Box::new(request)));
+        Ok(stream)
+    }
+
+    pub fn close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            let _ = self.commands.send(SessionCommand::Close);
+        }
+    }
+
+    pub fn destroy(&mut self) {
+        if !self.destroyed {
+            self.closed = true;
+            self.destroyed = true;
+            let _ = self.commands.send(SessionCommand::Destroy);
+        }
+    }
+}
+
+impl Http2Stream {
+    // Returns false once the caller should wait for 'drain' before writing more
+    pub fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<bool, String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+
+        let length = data.len();
+        let buffered = self.buffered.fetch_add(length, Ordering::SeqCst) + length;
+        let _ = self.commands.send(StreamCommand::Write(data, length, callback));
+
+        let ok = buffered < WRITE_HIGH_WATER_MARK;
+        if !ok {
+            self.need_drain.store(true, Ordering::SeqCst);
+        }
+        Ok(ok)
+    }
+
+    pub fn end(&mut self, data: Option<Vec<u8>>, callback: Option<v8::Global<v8::Function>>) -> Result<(), String> {
+        if let Some(data) = data.filter(|data| !data.is_empty()) {
+            self.write(data, None)?;
+        }
+        self.ended = true;
+        let _ = self.commands.send(StreamCommand::End(callback));
+        Ok(())
+    }
+
+    // close([code]) resets the stream, CANCEL unless another error code is given
+    pub fn close(&mut self, code: u32) {
+        self.ended = true;
+        let _ = self.commands.send(StreamCommand::Close(Reason::from(code)));
+    }
+}
+
+async fn connect_session(
+    host: &str,
+    port: u16,
+    tls: Option<&TlsOptions>,
+    settings: Http2Settings
+) -> Result<(SendRequest<Bytes>, Connection<Socket, Bytes>), String> {
+    let socket = TcpStream::connect((host, port)).await.map_err(|e| e.to_string())?;
+    let _ = socket.set_nodelay(true);
+
+    let socket = match tls {
+        Some(tls) => {
+            let socket = tls.connect(host, socket).await.map_err(|e| e.to_string())?;
+            if socket.get_ref().1.alpn_protocol() != Some(&b"h2"[..]) {
+                return Err("The server does not support HTTP/2 (no h2 through ALPN)".to_string());
+            }
+            Socket::Tls(Box::new(socket))
+        }
+        None => Socket::Plain(socket),
+    };
+
+    settings.client_builder().handshake(socket).await.map_err(|e| e.to_string())
+}
+
+// Connects, emits 'connect', then opens a task per stream while driving the connection.
+// The connection closes itself once close() was called and the last stream ended
+async fn run_session(
+    host: String,
+    port: u16,
+    tls: Option<TlsOptions>,
+    settings: Http2Settings,
+    mut commands: UnboundedReceiver<SessionCommand>,
+    emitter: Rc<RefCell<EventEmitter>>,
+    tx: UnboundedSender<Operations>
+) {
+    let (send_request, connection) = match connect_session(&host, port, tls.as_ref(), settings).await {
+        Ok(connected) => connected,
+        Err(error_message) => {
+            // Streams requested before the connection failed end with the same error
+            commands.close();
+            while let Ok(command) = commands.try_recv() {
+                if let SessionCommand::Request(stream) = command {
+                    let op = StreamEvent::Error{ emitter: stream.emitter.clone(), error_message: error_message.clone() };
+                    let _ = tx.send(Operations::Stream(op));
+                    let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter: stream.emitter }));
+                }
+            }
+            let _ = tx.send(Operations::Stream(StreamEvent::Error{ emitter: emitter.clone(), error_message }));
+            let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter, event: "close" }));
+            return;
+        }
+    };
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "connect" }));
+
+    let mut send_request = Some(send_request);
+    tokio::pin!(connection);
+    loop {
+        tokio::select! {
+            result = &mut connection => {
+                // A GOAWAY without an error is how a server ends a session normally
+                if let Err(e) = result {
+                    if e.reason() != Some(Reason::NO_ERROR) {
+                        let _ = tx.send(Operations::Stream(StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() }));
+                    }
+                }
+                break;
+            }
+            Some(command) = commands.recv() => match command {
+                SessionCommand::Request(stream) => match &send_request {
+                    Some(send_request) => {
+                        let send_request = send_request.clone();
+                        let tx = tx.clone();
+                        tokio::task::spawn_local(run_stream(send_request, *stream, tx));
+                    }
+                    None => {
+                        let op = StreamEvent::Error{ emitter: stream.emitter.clone(), error_message: "The session has been closed".to_string() };
+                        let _ = tx.send(Operations::Stream(op));
+                    }
+                },
+                SessionCommand::Close => send_request = None,
+                SessionCommand::Destroy => break,
+            }
+        }
+    }
+
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter, event: "close" }));
+}
+
+// Opens the stream, then writes the request body while the response is read. close()
+// resets the stream wherever it is
+async fn run_stream(send_request: SendRequest<Bytes>, stream: StreamRequest, tx: UnboundedSender<Operations>) {
+    let StreamRequest { head, end_stream, mut commands, emitter, buffered, need_drain, encoding } = stream;
+
+    let opened = match send_request.ready().await {
+        Ok(mut send_request) => send_request.send_request(head, end_stream),
+        Err(e) => Err(e),
+    };
+    let (response, mut send_stream) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = tx.send(Operations::Stream(StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() }));
+            let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+            return;
+        }
+    };
+
+    let receiving = receive_response(response, &emitter, &encoding, &tx);
+    tokio::pin!(receiving);
+    let mut writing = !end_stream;
+    let result = loop {
+        tokio::select! {
+            result = &mut receiving => break result,
+            Some(command) = commands.recv() => match command {
+                StreamCommand::Write(data, length, callback) => {
+                    let result = match writing {
+                        true => send_data(&mut send_stream, data, false).await,
+                        false => Ok(()),
+                    };
+                    let remaining = buffered.fetch_sub(length, Ordering::SeqCst) - length;
+
+                    if let Some(callback) = callback {
+                        let error_message = result.as_ref().err().map(|e| e.to_string());
+                        let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                    }
+                    if let Err(e) = result {
+                        break Err(e);
+                    }
+                    if remaining == 0 && need_drain.swap(false, Ordering::SeqCst) {
+                        let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "drain" }));
+                    }
+                }
+                StreamCommand::End(callback) => {
+                    let result = match writing {
+                        true => send_stream.send_data(Bytes::new(), true),
+                        false => Ok(()),
+                    };
+                    writing = false;
+                    if let Some(callback) = callback {
+                        let error_message = result.as_ref().err().map(|e| e.to_string());
+                        let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                    }
+                    if let Err(e) = result {
+                        break Err(e);
+                    }
+                }
+                StreamCommand::Close(reason) => {
+                    send_stream.send_reset(reason);
+                    break Ok(());
+                }
+            }
+        }
+    };
+
+    if let Err(e) = result {
+        let _ = tx.send(Operations::Stream(StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() }));
+    }
+    let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter: emitter.clone() }));
+}
+
+// Emits 'response' with the headers, streams the body as 'data', then 'trailers' and 'end'
+async fn receive_response(
+    response: ResponseFuture,
+    emitter: &Rc<RefCell<EventEmitter>>,
+    encoding: &Arc<Mutex<Option<String>>>,
+    tx: &UnboundedSender<Operations>
+) -> Result<(), h2::Error> {
+    let (parts, mut body) = response.await?.into_parts();
+
+    // The body is only read once the 'response' listeners ran, so setEncoding() applies to every chunk
+    let (ready, ready_rx) = oneshot::channel::<()>();
+    let op = HttpOperation::Http2Headers{
+        emitter: emitter.clone(),
+        event: "response",
+        status: Some(parts.status.as_u16()),
+        headers: headers_from_map(&parts.headers),
+        ready: Some(ready),
+    };
+    let _ = tx.send(Operations::Http(op));
+    let _ = ready_rx.await;
+
+    let mut decoder = Utf8Decoder::default();
+    let trailers = receive_body(&mut body, |bytes| {
+        let chunk = match encoding.lock().unwrap().is_some() {
+            true => Chunk::Text(decoder.decode(&bytes)),
+            false => Chunk::Bytes(bytes),
+        };
+        let _ = tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk }));
+    }).await?;
+
+    let rest = decoder.finish();
+    if !rest.is_empty() {
+        let _ = tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Text(rest) }));
+    }
+    if let Some(trailers) = trailers {
+        let op = HttpOperation::Http2Headers{
+            emitter: emitter.clone(),
+            event: "trailers",
+            status: None,
+            headers: headers_from_map(&trailers),
+            ready: None,
+        };
+        let _ = tx.send(Operations::Http(op));
+    }
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "end" }));
+    Ok(())
+}
+
+// Emits 'response' or 'trailers' with a headers object, ':status' is a number like in Node
+pub fn emit_headers(
+    scope: &mut v8::HandleScope,
+    emitter: &Rc<RefCell<EventEmitter>>,
+    event: &str,
+    status: Option<u16>,
+    headers: &Headers
+) {
+    let headers_obj = headers_to_object(scope, headers);
+    if let Some(status) = status {
+        let status_key = v8::String::new(scope, ":status").unwrap();
+        let status_value = v8::Integer::new(scope, status as i32);
+        headers_obj.set(scope, status_key.into(), status_value.into());
+    }
+    emit_event(scope, emitter, event, &[headers_obj.into()]);
+}
+
+// V8 Callbacks
+fn get_http<'a>(scope: &mut v8::HandleScope, http2_obj: v8::Local<v8::Object>) -> &'a Http {
+    let internal_field = http2_obj.get_internal_field(scope, 0).unwrap();
+    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_http.value() as *mut Http) }
+}
+
+fn get_session<'a>(scope: &mut v8::HandleScope, session_obj: v8::Local<v8::Object>) -> &'a mut Http2Session {
+    let internal_field = session_obj.get_internal_field(scope, 0).unwrap();
+    let external_session = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_session.value() as *mut Http2Session) }
+}
+
+fn get_stream<'a>(scope: &mut v8::HandleScope, stream_obj: v8::Local<v8::Object>) -> &'a mut Http2Stream {
+    let internal_field = stream_obj.get_internal_field(scope, 0).unwrap();
+    let external_stream = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_stream.value() as *mut Http2Stream) }
+}
+
+// http2.createServer([options], [handler]), HTTP/2 without TLS (h2c with prior knowledge)
+fn create_http2_server_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(server_obj) = create_server(scope, &args, false, true) {
+        rv.set(server_obj.into());
+    }
+}
+
+// http2.createSecureServer(options, [handler]), options need `key` and `cert`
+fn create_secure_http2_server_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(server_obj) = create_server(scope, &args, true, true) {
+        rv.set(server_obj.into());
+    }
+}
+
+// http2.connect(authority, [options], [listener]), the listener is a 'connect' listener
+fn connect_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let http = get_http(scope, args.this());
+
+    let authority = args.get(0).to_rust_string_lossy(scope);
+    let url = match Url::parse(&authority) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => url,
+        _ => {
+            throw_error(scope, &format!("Invalid authority \"{}\", expected an http: or https: URL", authority));
+            return;
+        }
+    };
+    let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let options = v8::Local::<v8::Object>::try_from(args.get(1)).ok().filter(|_| !args.get(1).is_function());
+    let tls = match (url.scheme(), options) {
+        ("https", Some(options)) => tls_options(scope, options).map(Some),
+        ("https", None) => Ok(Some(TlsOptions::default())),
+        _ => Ok(None),
+    };
+    let tls = match tls {
+        Ok(tls) => tls.map(|tls| tls.for_http2()),
+        Err(e) => {
+            throw_error(scope, &e);
+            return;
+        }
+    };
+    if let Err(e) = http.permissions.check_net(&host, port) {
+        throw_error(scope, &e.to_string());
+        return;
+    }
+
+    let settings = Http2Settings::from_js(scope, options);
+    let session = Http2Session::connect(host, port, tls, settings, http.tx.clone());
+
+    let listener = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok());
+    if let Some(listener) = listener {
+        let listener = v8::Global::new(scope, listener);
+        session.event_emitter.borrow_mut().once("connect".to_string(), listener);
+    }
+
+    let session_obj = create_session_object(scope, Box::new(session));
+    rv.set(session_obj.into());
+}
+
+// request([headers], [options]), options.endStream closes the writable side right away
+fn session_request_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let session = get_session(scope, args.this());
+
+    let mut headers = Headers::new();
+    if let Ok(headers_obj) = v8::Local::<v8::Object>::try_from(args.get(0)) {
+        let names = headers_obj.get_own_property_names(scope).unwrap();
+        for i in 0..names.length() {
+            let name = names.get_index(scope, i).unwrap();
+            let value = headers_obj.get(scope, name).unwrap();
+            let name = name.to_rust_string_lossy(scope);
+            for value in header_values_from_js(scope, value) {
+                headers.append(name.clone(), value);
+            }
+        }
+    }
+    let end_stream = v8::Local::<v8::Object>::try_from(args.get(1)).ok()
+        .and_then(|options| option(scope, options, "endStream"))
+        .map(|end_stream| end_stream.boolean_value(scope));
+
+    match session.request(headers, end_stream) {
+        Ok(stream) => {
+            let stream_obj = create_stream_object(scope, Box::new(stream));
+            rv.set(stream_obj.into());
+        }
+        Err(e) => throw_error(scope, &e),
+    }
+}
+
+// close([callback]), the session ends once its open streams did. The callback is a 'close' listener
+fn session_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let session = get_session(scope, args.this());
+    if let Ok(callback) = v8::Local::<v8::Function>::try_from(args.get(0)) {
+        let callback = v8::Global::new(scope, callback);
+        session.event_emitter.borrow_mut().once("close".to_string(), callback);
+    }
+    session.close();
+}
+
+// destroy(), ends the connection and every stream on it right away
+fn session_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    get_session(scope, args.this()).destroy();
+}
+
+fn session_state_getter(
+    scope: &mut v8::HandleScope,
+    name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let session = get_session(scope, args.this());
+    let value = match name.to_rust_string_lossy(scope).as_str() {
+        "closed" => session.closed,
+        _ => session.destroyed,
+    };
+    rv.set(v8::Boolean::new(scope, value).into());
+}
+
+// write(chunk, [encoding], [callback])
+fn stream_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_stream(scope, args.this());
+    let chunk = value_to_bytes(scope, args.get(0));
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match stream.write(chunk, callback) {
+        Ok(ok) => rv.set(v8::Boolean::new(scope, ok).into()),
+        Err(error_message) => {
+            let error_value = v8::String::new(scope, &error_message).unwrap();
+            emit_event(scope, &stream.event_emitter, "error", &[error_value.into()]);
+            rv.set(v8::Boolean::new(scope, false).into());
+        }
+    }
+}
+
+// end([chunk], [encoding], [callback])
+fn stream_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_stream(scope, args.this());
+    let callback = (0..args.length())
+        .find_map(|i| v8::Local::<v8::Function>::try_from(args.get(i)).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    let mut final_chunk = None;
+    if !args.get(0).is_function() && !args.get(0).is_null_or_undefined() {
+        final_chunk = Some(value_to_bytes(scope, args.get(0)));
+    }
+
+    if let Err(error_message) = stream.end(final_chunk, callback) {
+        let error_value = v8::String::new(scope, &error_message).unwrap();
+        emit_event(scope, &stream.event_emitter, "error", &[error_value.into()]);
+    }
+    rv.set(args.this().into());
+}
+
+// close([code]), resets the stream with an HTTP/2 error code, NGHTTP2_CANCEL (8) by default
+fn stream_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let stream = get_stream(scope, args.this());
+    let code = if args.get(0).is_number() {
+        args.get(0).uint32_value(scope).unwrap_or(8)
+    } else {
+        8
+    };
+    stream.close(code);
+}
+
+// setEncoding([encoding]), 'data' then receives strings instead of Uint8Arrays
+fn stream_set_encoding_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_stream(scope, args.this());
+    let encoding = if args.get(0).is_string() {
+        args.get(0).to_rust_string_lossy(scope)
+    } else {
+        "utf8".to_string()
+    };
+    *stream.encoding.lock().unwrap() = Some(encoding);
+    rv.set(args.this().into());
+}
+
+fn create_session_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    session: Box<Http2Session>
+) -> v8::Local<'s, v8::Object> {
+    let session_template = v8::ObjectTemplate::new(scope);
+    session_template.set_internal_field_count(1);
+    let session_obj = session_template.new_instance(scope).unwrap();
+    attach_event_emitter(scope, session_obj, &session.event_emitter);
+
+    set_function(scope, session_obj, "request", session_request_callback);
+    set_function(scope, session_obj, "close", session_close_callback);
+    set_function(scope, session_obj, "destroy", session_destroy_callback);
+    for name in ["closed", "destroyed"] {
+        let key = v8::String::new(scope, name).unwrap();
+        session_obj.set_accessor(scope, key.into(), session_state_getter);
+    }
+
+    let external_session = v8::External::new(scope, Box::into_raw(session) as *const _ as *mut c_void);
+    session_obj.set_internal_field(0, external_session.into());
+    session_obj
+}
+
+fn create_stream_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    stream: Box<Http2Stream>
+) -> v8::Local<'s, v8::Object> {
+    let stream_template = v8::ObjectTemplate::new(scope);
+    stream_template.set_internal_field_count(1);
+    let stream_obj = stream_template.new_instance(scope).unwrap();
+    attach_event_emitter(scope, stream_obj, &stream.event_emitter);
+
+    set_function(scope, stream_obj, "write", stream_write_callback);
+    set_function(scope, stream_obj, "end", stream_end_callback);
+    set_function(scope, stream_obj, "close", stream_close_callback);
+    set_function(scope, stream_obj, "setEncoding", stream_set_encoding_callback);
+
+    let external_stream = v8::External::new(scope, Box::into_raw(stream) as *const _ as *mut c_void);
+    stream_obj.set_internal_field(0, external_stream.into());
+    stream_obj
+}
+
+// The global http2 object, it shares the Http state with `http`
+pub fn initialize_http2<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    external_http: v8::Local<'s, v8::External>
+) {
+    let http2_template = v8::ObjectTemplate::new(scope);
+    http2_template.set_internal_field_count(1);
+    let http2_obj = http2_template.new_instance(scope).unwrap();
+    http2_obj.set_internal_field(0, external_http.into());
+
+    set_function(scope, http2_obj, "createServer", create_http2_server_callback);
+    set_function(scope, http2_obj, "createSecureServer", create_secure_http2_server_callback);
+    set_function(scope, http2_obj, "connect", connect_callback);
+
+    let global = scope.get_current_context().global(scope);
+    let http2_key = v8::String::new(scope, "http2").unwrap();
+    global.set(scope, http2_key.into(), http2_obj.into());
+}
@@ -0,0 +1,70 @@
+use rusty_v8 as v8;
+
+use crate::client::{create_client_request_object, start_request};
+use crate::helper::set_function;
+use crate::server::create_server;
+
+// https.createServer(options, [handler]), options need `key` and `cert`
+fn create_secure_server_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(server_obj) = create_server(scope, &args, true, false) {
+        rv.set(server_obj.into());
+    }
+}
+
+// https.request(url | options, [options], [callback])
+fn secure_request_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Some(request) = start_request(scope, &args, false, true) else {
+        return;
+    };
+    let request_obj = create_client_request_object(scope, Box::new(request));
+    rv.set(request_obj.into());
+}
+
+// https.get(url | options, [options], [callback])
+fn secure_get_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let Some(mut request) = start_request(scope, &args, true, true) else {
+        return;
+    };
+    let _ = request.end(None, None);
+    let request_obj = create_client_request_object(scope, Box::new(request));
+    rv.set(request_obj.into());
+}
+
+// The global https object. It shares the Http state, Agent and globalAgent with `http`,
+// agents keep TLS connections in pools of their own
+pub fn initialize_https<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    http_obj: v8::Local<'s, v8::Object>,
+    external_http: v8::Local<'s, v8::External>
+) {
+    let https_template = v8::ObjectTemplate::new(scope);
+    https_template.set_internal_field_count(1);
+    let https_obj = https_template.new_instance(scope).unwrap();
+    https_obj.set_internal_field(0, external_http.into());
+
+    set_function(scope, https_obj, "createServer", create_secure_server_callback);
+    set_function(scope, https_obj, "request", secure_request_callback);
+    set_function(scope, https_obj, "get", secure_get_callback);
+
+    for name in ["Agent", "globalAgent"] {
+        let key = v8::String::new(scope, name).unwrap();
+        let value = http_obj.get(scope, key.into()).unwrap();
+        https_obj.set(scope, key.into(), value);
+    }
+
+    let global = scope.get_current_context().global(scope);
+    let https_key = v8::String::new(scope, "https").unwrap();
+    global.set(scope, https_key.into(), https_obj.into());
+}
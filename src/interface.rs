@@ -1,17 +1,31 @@
 use rusty_v8 as v8; 
 use tokio;
 
-use crate::http::IncomingMessage;
+use crate::http::{LentConnection, UpgradedConnection};
+use crate::fs::{FileStats, DirEntry};
+use crate::emitter::EventEmitter;
+use crate::stream::Chunk;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use tokio::sync::oneshot; 
-use std::sync::Mutex;
+use tokio::sync::watch;
 use crate::request::Request;
+use crate::client::ClientResponse;
+use crate::headers::Headers;
+use crate::static_files::StaticLookup;
+use bytes::Bytes;
+use h2::server::SendResponse;
 
 pub enum Operations {
     Timer(TimerOperation),
     Fs(FsOperation),
     Http(HttpOperation),
-    Response(ResponseEvent)
+    Stream(StreamEvent),
+    Watch(WatchEvent),
+    Zlib(ZlibOperation)
 }
 
 pub enum TimerOperation {
@@ -39,25 +53,181 @@ pub enum FsOperation {
         callback: v8::Global<v8::Function>,
         error_message: String,
     },
-
+    StatSuccess {
+        callback: v8::Global<v8::Function>,
+        stats: FileStats,
+    },
+    ReaddirSuccess {
+        callback: v8::Global<v8::Function>,
+        entries: Vec<DirEntry>,
+        with_file_types: bool,
+    },
+    PathSuccess {
+        callback: v8::Global<v8::Function>,
+        path: String,
+    },
+    OpenSuccess {
+        callback: v8::Global<v8::Function>,
+        fd: i32,
+    },
+    // The bytes are copied into `buffer` at `offset` on the main thread
+    ReadSuccess {
+        callback: v8::Global<v8::Function>,
+        buffer: v8::Global<v8::ArrayBufferView>,
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    WriteSuccess {
+        callback: v8::Global<v8::Function>,
+        bytes_written: usize,
+        buffer: v8::Global<v8::Value>,
+    },
+    // Operations that only report completion (mkdir, rm, rename, chmod, ...)
+    Success {
+        callback: v8::Global<v8::Function>,
+    },
+    Error {
+        callback: v8::Global<v8::Function>,
+        error_message: String,
+    },
 }
 
 pub enum HttpOperation {
-    Get(Arc<Mutex<IncomingMessage>>, v8::Global<v8::Function>, oneshot::Sender<bool>),
-    Request(tokio::net::TcpStream, v8::Global<v8::Function>),
-    Listen(Request, tokio::net::TcpStream, v8::Global<v8::Function>)
+    // A request head was read, the connection is returned through `done` once the response
+    // finished if it can serve another request
+    Listen {
+        request: Request,
+        connection: LentConnection,
+        version: u8,
+        keep_alive: bool,
+        // The server's emitter, the request is emitted as 'request'
+        emitter: Rc<RefCell<EventEmitter>>,
+        // Set once the server closes, the response then asks the client to close too
+        closing: watch::Receiver<bool>,
+    },
+    // The head of a client response arrived, emitted as 'response' on the request. The body
+    // is read once `ready` fires, after the listeners had a chance to call setEncoding()
+    ClientResponse {
+        request_emitter: Rc<RefCell<EventEmitter>>,
+        response: ClientResponse,
+        ready: oneshot::Sender<()>,
+    },
+    // A server event about one of its connections: 'connection', 'timeout' or 'drop'
+    Socket {
+        emitter: Rc<RefCell<EventEmitter>>,
+        event: &'static str,
+        remote_address: SocketAddr,
+    },
+    // An https server failed the handshake with a client, emitted as 'tlsClientError'
+    TlsClientError {
+        emitter: Rc<RefCell<EventEmitter>>,
+        error_message: String,
+        remote_address: SocketAddr,
+    },
+    // A request arrived on an HTTP/2 stream, the response is sent on the same stream
+    Http2Request {
+        request: Request,
+        respond: SendResponse<Bytes>,
+        emitter: Rc<RefCell<EventEmitter>>,
+    },
+    // The response headers ('response', with `status`) or trailers ('trailers') of a client
+    // HTTP/2 stream. The body is read once `ready` fires, like for ClientResponse
+    Http2Headers {
+        emitter: Rc<RefCell<EventEmitter>>,
+        event: &'static str,
+        status: Option<u16>,
+        headers: Headers,
+        ready: Option<oneshot::Sender<()>>,
+    },
+    // A request asked to switch protocols, emitted as 'upgrade' with the raw connection
+    Upgrade {
+        request: Request,
+        connection: UpgradedConnection,
+        emitter: Rc<RefCell<EventEmitter>>,
+    },
+    // Something happened on a WebSocket, dispatched to its on<type> property and listeners
+    WebSocket {
+        socket: v8::Global<v8::Object>,
+        event: WebSocketEvent,
+    },
+    // http.serveStatic() looked up the file of a request
+    StaticFile(StaticLookup),
 }
 
-pub enum ResponseEvent {
+pub enum WebSocketEvent {
+    // The handshake completed with the subprotocol the server picked
+    Open {
+        protocol: String,
+    },
+    // Text messages arrive as Chunk::Text, binary ones as Chunk::Bytes
+    Message(Chunk),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Error {
+        error_message: String,
+    },
+    Close {
+        code: u16,
+        reason: String,
+        was_clean: bool,
+    },
+}
+
+// Events for objects backed by a shared EventEmitter (fs streams, ...)
+pub enum StreamEvent {
     Data {
-        res: Arc<Mutex<IncomingMessage>>, 
-        chunk: Vec<u8>,
+        emitter: Rc<RefCell<EventEmitter>>,
+        chunk: Chunk,
     },
-    End {
-        res: Arc<Mutex<IncomingMessage>>, 
+    // Events without arguments such as open, end, finish, drain and close
+    Emit {
+        emitter: Rc<RefCell<EventEmitter>>,
+        event: &'static str,
     },
     Error {
-        res: Arc<Mutex<IncomingMessage>>, 
+        emitter: Rc<RefCell<EventEmitter>>,
         error_message: String,
     },
-}
\ No newline at end of file
+    // The last event of a stream, request or response: 'close', then its listeners are released
+    Close {
+        emitter: Rc<RefCell<EventEmitter>>,
+    },
+    // Completion callback of a single write()/end() call
+    Callback {
+        callback: v8::Global<v8::Function>,
+        error_message: Option<String>,
+    },
+}
+
+// Events from fs.watch (inotify) and fs.watchFile (stat polling)
+pub enum WatchEvent {
+    Change {
+        emitter: Rc<RefCell<EventEmitter>>,
+        // Set once the watcher is closed, events still in the channel are dropped
+        closed: Arc<AtomicBool>,
+        event_type: &'static str,
+        filename: String,
+    },
+    Error {
+        emitter: Rc<RefCell<EventEmitter>>,
+        error_message: String,
+    },
+    Close {
+        emitter: Rc<RefCell<EventEmitter>>,
+    },
+    StatChange {
+        listener: v8::Global<v8::Function>,
+        // Cleared by unwatchFile
+        active: Arc<AtomicBool>,
+        current: FileStats,
+        previous: FileStats,
+    },
+}
+
+// Results of zlib.gzip() and the other callback functions
+pub enum ZlibOperation {
+    Complete {
+        callback: v8::Global<v8::Function>,
+        result: Result<Vec<u8>, String>,
+    },
+}
@@ -1,32 +1,49 @@
 use rusty_v8 as v8;
 use tokio; 
 use tokio::sync::mpsc::UnboundedSender;
-use tokio::io::AsyncReadExt;
 use std::env; 
 use std::ffi::c_void;
-use std::collections::HashMap;
 use std::path::PathBuf;
 
 //Declare internal modules 
 mod console; 
 mod timer;
 mod fs; 
+mod fs_promises;
 mod http;
+mod client;
+mod agent;
+mod tls;
+mod https;
+mod http2;
+mod websocket;
+mod static_files;
+mod fetch;
+mod abort;
+mod server;
 mod request; 
 mod response;
+mod headers;
 mod emitter;
+mod stream;
+mod watch;
+mod zlib;
 
 mod helper; 
 mod interface;
 mod net; 
+mod permissions;
 
 use crate::request::create_request_object;
+use crate::client::create_client_response_object;
+use crate::request::create_socket_object;
+use crate::emitter::emit_event;
 use crate::request::Request;
 use crate::response::create_response_object;
 use crate::response::Response; 
 use crate::fs::initialize_fs;
 use crate::http::initialize_http;
-use crate::http::incoming_message_on_callback;
+use crate::permissions::Permissions;
 
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -36,9 +53,19 @@ async fn main() {
 
     // READ FILE 
     let args: Vec<String> = env::args().collect();
+
+    // Permission flags may appear anywhere after `main`
+    let (permissions, args) = match Permissions::from_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return;
+        }
+    };
+    let permissions = Arc::new(permissions);
     
-    if args.len() < 2 {
-        eprintln!("Usage: cargo run main <filename>");
+    if args.len() < 3 {
+        eprintln!("Usage: cargo run main [--allow-read[=<paths>]] [--allow-write[=<paths>]] [--allow-net[=<hosts>]] [--allow-env] [--allow-run] [--allow-all] <filename>");
         return;
     }
 
@@ -80,10 +107,16 @@ async fn main() {
     assign_callback_to_global(scope, "setInterval", timer::set_interval_callback);
 
     //File Operations
-    initialize_fs(scope, tx);
+    initialize_fs(scope, tx, permissions.clone());
+
+    //AbortController, used by fetch
+    abort::initialize_abort(scope);
+
+    //Compression
+    zlib::initialize_zlib(scope);
 
     //Http Operations
-    initialize_http(scope, tx_http);
+    initialize_http(scope, tx_http, permissions);
 
     // Run the event loop within the LocalSet
     let local = tokio::task::LocalSet::new();
@@ -91,9 +124,18 @@ async fn main() {
     local.run_until(async move {
 
         // Compile and execute the JavaScript code
-        let code = v8::String::new(scope, &file_contents).unwrap();
-        let script = v8::Script::compile(scope, code, None).unwrap();
-        script.run(scope);
+        {
+            let try_catch = &mut v8::TryCatch::new(scope);
+            let code = v8::String::new(try_catch, &file_contents).unwrap();
+            let script = v8::Script::compile(try_catch, code, None).unwrap();
+
+            // Report exceptions such as PermissionDenied instead of silently stopping the script
+            if script.run(try_catch).is_none() {
+                if let Some(exception) = try_catch.exception() {
+                    eprintln!("Uncaught {}", exception.to_rust_string_lossy(try_catch));
+                }
+            }
+        }
 
         // Enter the event loop
         loop {
@@ -106,97 +148,78 @@ async fn main() {
                     match operation {
                         interface::Operations::Http(http_op) => {
                             match http_op {
-                                interface::HttpOperation::Listen(request, socket, callback) => {
-                                    let response = Response {
-                                        status_code: 200, 
-                                        headers: HashMap::new(),
-                                        body: String::new(),
-                                    };
+                                interface::HttpOperation::Listen { request, connection, version, keep_alive, emitter, closing } => {
+                                    // The response hands the connection back through `done` once it ends
+                                    let tx = unsafe { &*helper::retrieve_tx(scope, "http").unwrap() }.clone();
+                                    let response = Response::for_connection(connection, &request.method, version, keep_alive, closing, tx);
 
-                                    let boxed_socket = Box::new(socket);
-                                    let request_obj = create_request_object(scope, Box::new(request), None, None);
-                                    let response_obj = create_response_object(scope, Box::new(response), boxed_socket);
+                                    let request_obj = create_request_object(scope, Box::new(request));
+                                    let response_obj = create_response_object(scope, Box::new(response));
 
                                     let request_value: v8::Local<v8::Value> = request_obj.into();
                                     let response_value: v8::Local<v8::Value> = response_obj.into();
 
-                                    let args = vec![request_value, response_value];
-                                    
-                                    let undefined = v8::undefined(scope).into();
-                                    let callback = callback.open(scope);
-                                    callback.call(scope, undefined, &args).unwrap();
+                                    emit_event(scope, &emitter, "request", &[request_value, response_value]);
                                 }
 
-                                interface::HttpOperation::Get(res, callback, tx) => {
-                                    //let mut incoming_message = res.lock().unwrap();
-                                    let object_template = v8::ObjectTemplate::new(scope);
-                                    object_template.set_internal_field_count(2);
-                                    let incoming_message_obj = object_template.new_instance(scope).unwrap();
+                                interface::HttpOperation::Socket { emitter, event, remote_address } => {
+                                    let socket_obj = create_socket_object(scope, remote_address);
+                                    emit_event(scope, &emitter, event, &[socket_obj.into()]);
+                                }
 
-                                    let on_fn_template = v8::FunctionTemplate::new(scope, incoming_message_on_callback);
-                                    let on_fn = on_fn_template.get_function(scope).unwrap();
-                                    let on_fn_key = v8::String::new(scope, "on").unwrap();
-                                    incoming_message_obj.set(scope, on_fn_key.into(), on_fn.into());
+                                interface::HttpOperation::TlsClientError { emitter, error_message, remote_address } => {
+                                    let error_value = v8::String::new(scope, &error_message).unwrap();
+                                    let socket_obj = create_socket_object(scope, remote_address);
+                                    emit_event(scope, &emitter, "tlsClientError", &[error_value.into(), socket_obj.into()]);
+                                }
 
-                                    let external_incoming_message = v8::External::new(scope, Arc::into_raw(res) as *const _ as *mut c_void);
-                                    incoming_message_obj.set_internal_field(0, external_incoming_message.into());
+                                // Requests on HTTP/2 streams get the same request and response objects
+                                interface::HttpOperation::Http2Request { request, respond, emitter } => {
+                                    let tx = unsafe { &*helper::retrieve_tx(scope, "http").unwrap() }.clone();
+                                    let response = Response::for_http2_stream(respond, &request.method, tx);
 
-                                    let incoming_message_value: v8::Local<v8::Value> = incoming_message_obj.into();
-                                    let args = vec![incoming_message_value];
-                                    
-                                    let undefined = v8::undefined(scope).into();
-                                    let callback = callback.open(scope);
-                                    callback.call(scope, undefined, &args).unwrap();
+                                    let request_obj = create_request_object(scope, Box::new(request));
+                                    let response_obj = create_response_object(scope, Box::new(response));
+                                    emit_event(scope, &emitter, "request", &[request_obj.into(), response_obj.into()]);
+                                }
 
-                                    tx.send(true);
+                                interface::HttpOperation::Http2Headers { emitter, event, status, headers, ready } => {
+                                    http2::emit_headers(scope, &emitter, event, status, &headers);
+                                    if let Some(ready) = ready {
+                                        let _ = ready.send(());
+                                    }
                                 }
 
-                                interface::HttpOperation::Request(mut socket, callback) => {
-                                    //parse response into object from socket
-                                    let response = match parse_http_response(&mut socket).await{
-                                        Ok(response) => response, 
-                                        Err(e) => {
-                                            eprintln!("Failed to parse HTTP response: {}", e);
-                                            return;
-                                        }
-                                    };
+                                // The socket works on its own until acceptWebSocket() takes it over
+                                interface::HttpOperation::Upgrade { request, connection, emitter } => {
+                                    let tx = unsafe { &*helper::retrieve_tx(scope, "http").unwrap() }.clone();
+                                    let head = helper::bytes_to_uint8array(scope, connection.head.clone());
+                                    let socket = websocket::UpgradedSocket::new(connection, request.remote_address, tx);
 
-                                    let boxed_response = Box::new(response);
-                                    let boxed_socket = Box::new(socket);
+                                    let request_obj = create_request_object(scope, Box::new(request));
+                                    let socket_obj = websocket::create_upgraded_socket_object(scope, Box::new(socket));
+                                    emit_event(scope, &emitter, "upgrade", &[request_obj.into(), socket_obj.into(), head.into()]);
+                                }
 
-                                    let response_obj = create_response_object(scope, boxed_response, boxed_socket);
-                                    let response_value: v8::Local<v8::Value> = response_obj.into();
+                                interface::HttpOperation::WebSocket { socket, event } => {
+                                    websocket::handle_websocket_event(scope, socket, event);
+                                }
 
-                                    let args = vec![response_value];
-                                    
-                                    let undefined = v8::undefined(scope).into();
-                                    let callback = callback.open(scope);
-                                    callback.call(scope, undefined, &args).unwrap();
+                                interface::HttpOperation::StaticFile(pending) => {
+                                    static_files::handle_static_lookup(scope, pending);
+                                }
+
+                                interface::HttpOperation::ClientResponse { request_emitter, response, ready } => {
+                                    let response_obj = create_client_response_object(scope, Box::new(response));
+                                    emit_event(scope, &request_emitter, "response", &[response_obj.into()]);
+                                    let _ = ready.send(());
                                 }
                             } 
                         }, 
 
-                        interface::Operations::Response(response_op) => {
-                            match response_op {
-                                interface::ResponseEvent::Data{ res, chunk } => {
-                                    let mut incoming_message = res.lock().unwrap();
-                                    let chunk_str = String::from_utf8_lossy(&chunk);
-                                    let chunk_value = v8::String::new(scope, &chunk_str).unwrap().into();
-                                    incoming_message.event_emitter.emit(scope, "data".to_string(), &[chunk_value]);
-                                },
-
-                                interface::ResponseEvent::End{ res } => {
-                                    let mut incoming_message = res.lock().unwrap();
-                                    incoming_message.event_emitter.emit(scope, "end".to_string(), &[])
-                                },
-
-                                interface::ResponseEvent::Error{ res, error_message } => {
-                                    let mut incoming_message = res.lock().unwrap();
-                                    let error_value = v8::String::new(scope, &error_message).unwrap();
-                                    incoming_message.event_emitter.emit(scope, "error".to_string(), &[error_value.into()])
-                                },
-                            }
-                        },
+                        interface::Operations::Stream(stream_event) => {
+                            stream::handle_stream_event(scope, stream_event);
+                        }
 
                         interface::Operations::Timer(timer_op) => {
                             continue;
@@ -205,6 +228,14 @@ async fn main() {
                         interface::Operations::Fs(fs_op) => {
                             continue;
                         }
+
+                        interface::Operations::Watch(_) => {
+                            continue;
+                        }
+
+                        interface::Operations::Zlib(_) => {
+                            continue;
+                        }
                     }
                 }
 
@@ -270,6 +301,87 @@ async fn main() {
                                         let callback_fn = callback.open(scope);
                                         callback_fn.call(scope, undefined, args).unwrap();
                                     }
+
+                                    // Success for stat/lstat
+                                    interface::FsOperation::StatSuccess { callback, stats } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let stats_obj = fs::create_stats_object(scope, &stats);
+                                        let args = &[null_value, stats_obj.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for readdir
+                                    interface::FsOperation::ReaddirSuccess { callback, entries, with_file_types } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let entries_array = fs::create_readdir_array(scope, &entries, with_file_types);
+                                        let args = &[null_value, entries_array.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for readlink/realpath
+                                    interface::FsOperation::PathSuccess { callback, path } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let path = v8::String::new(scope, &path).unwrap();
+                                        let args = &[null_value, path.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for open, passes the file descriptor
+                                    interface::FsOperation::OpenSuccess { callback, fd } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let fd = v8::Integer::new(scope, fd);
+                                        let args = &[null_value, fd.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for read, fills the caller's buffer
+                                    interface::FsOperation::ReadSuccess { callback, buffer, offset, bytes } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let buffer = v8::Local::new(scope, buffer);
+                                        fs::copy_into_buffer(scope, buffer, offset, &bytes);
+                                        let bytes_read = v8::Integer::new_from_unsigned(scope, bytes.len() as u32);
+                                        let args = &[null_value, bytes_read.into(), buffer.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for write
+                                    interface::FsOperation::WriteSuccess { callback, bytes_written, buffer } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let bytes_written = v8::Integer::new_from_unsigned(scope, bytes_written as u32);
+                                        let buffer = v8::Local::new(scope, buffer);
+                                        let args = &[null_value, bytes_written.into(), buffer];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Success for operations without a result
+                                    interface::FsOperation::Success { callback } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let null_value = v8::null(scope).into();
+                                        let args = &[null_value];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
+
+                                    // Error for any of the above
+                                    interface::FsOperation::Error { callback, error_message } => {
+                                        let undefined = v8::undefined(scope).into();
+                                        let error_message = v8::String::new(scope, &error_message).unwrap();
+                                        let args = &[error_message.into()];
+                                        let callback_fn = callback.open(scope);
+                                        callback_fn.call(scope, undefined, args).unwrap();
+                                    }
                                 }
                             }
                         
@@ -279,9 +391,19 @@ async fn main() {
                             continue;
                         }
 
-                        //Handle Erronous Case
-                        interface::Operations::Response(response_ops) => {
-                            continue;
+                        // Handle stream events (fs.createReadStream/createWriteStream)
+                        interface::Operations::Stream(stream_event) => {
+                            stream::handle_stream_event(scope, stream_event);
+                        }
+
+                        // Handle fs.watch and fs.watchFile notifications
+                        interface::Operations::Watch(watch_event) => {
+                            watch::handle_watch_event(scope, watch_event);
+                        }
+
+                        // Handle the callbacks of zlib.gzip() and friends
+                        interface::Operations::Zlib(zlib_operation) => {
+                            zlib::handle_zlib_operation(scope, zlib_operation);
                         }
                     }
 
@@ -364,51 +486,6 @@ pub fn assign_tx_to_global(
     global.set(scope, key.into(), obj.into());
 }
 
-pub async fn parse_http_response(
-    socket: &mut tokio::net::TcpStream,
-) -> Result<Response, Box<dyn std::error::Error>> {
-    let mut response_data = String::new();
-
-    // Read data from the socket into response_data
-    socket.read_to_string(&mut response_data).await?;
-
-    // Prepare to parse with httparse
-    let mut headers = [httparse::EMPTY_HEADER; 32]; // Fixed-size buffer for headers
-    let mut http_parse_response = httparse::Response::new(&mut headers);
-
-    let response_data_bytes = response_data.as_bytes();
-    let parsed_len = match http_parse_response.parse(response_data_bytes)? {
-        httparse::Status::Complete(len) => len,
-        httparse::Status::Partial => {
-            return Err("Incomplete HTTP response".into());
-        }
-    };
-
-    // Get the status code
-    let status_code = http_parse_response.code.ok_or("Missing status code")?;
-
-    // Convert headers to a HashMap<String, String>
-    let headers_map: HashMap<String, String> = http_parse_response.headers.iter().map(|h| {
-        (
-            h.name.to_string(),
-            String::from_utf8_lossy(h.value).to_string(),
-        )
-    }).collect();
-
-    // Extract the body from the remaining bytes
-    let body = String::from_utf8_lossy(&response_data_bytes[parsed_len..]).to_string();
-
-    // Construct the Response object
-    let response = Response {
-        status_code,
-        headers: headers_map,
-        body,
-    };
-
-    Ok(response)
-}
-
-
 // pub fn retrieve_global_object<'s>(
 //     scope: &mut v8::ContextScope<'s, v8::HandleScope<'_>>, 
 //     name: &str
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+// What a script is allowed to do, granted on the command line Deno style:
+// --allow-read[=<paths>], --allow-write[=<paths>], --allow-net[=<host[:port]>],
+// --allow-env, --allow-run and --allow-all (-A). Everything else is denied.
+#[derive(Default)]
+pub struct Permissions {
+    read: Grant<PathBuf>,
+    write: Grant<PathBuf>,
+    net: Grant<NetAddress>,
+    env: bool,
+    run: bool,
+}
+
+// A flag without a value grants everything, with a value only the listed entries
+#[derive(Default)]
+enum Grant<T> {
+    #[default]
+    Denied,
+    All,
+    Only(Vec<T>),
+}
+
+struct NetAddress {
+    host: String,
+    // Any port when not given
+    port: Option<u16>,
+}
+
+#[derive(Debug)]
+pub struct PermissionDenied {
+    flag: &'static str,
+    description: String,
+}
+
+impl fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PermissionDenied: Requires {}, run again with the --{} flag", self.description, self.flag)
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
+// Lets fs operations report a denial through their usual io::Result paths
+impl From<PermissionDenied> for std::io::Error {
+    fn from(error: PermissionDenied) -> Self {
+        std::io::Error::new(std::io::ErrorKind::PermissionDenied, error)
+    }
+}
+
+impl Permissions {
+    // Consumes the permission flags, returning the remaining arguments
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut permissions = Permissions::default();
+        let mut rest = Vec::new();
+
+        for arg in args {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (arg.as_str(), None),
+            };
+
+            match flag {
+                "--allow-read" => permissions.read = parse_grant(value, |path| Ok(resolve_path(Path::new(path))))?,
+                "--allow-write" => permissions.write = parse_grant(value, |path| Ok(resolve_path(Path::new(path))))?,
+                "--allow-net" => permissions.net = parse_grant(value, parse_net_address)?,
+                "--allow-env" => permissions.env = true,
+                "--allow-run" => permissions.run = true,
+                "--allow-all" | "-A" => permissions = Permissions::allow_all(),
+                _ if flag.starts_with("--allow-") => return Err(format!("Unknown permission flag: {}", arg)),
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        Ok((permissions, rest))
+    }
+
+    pub fn allow_all() -> Self {
+        Permissions {
+            read: Grant::All,
+            write: Grant::All,
+            net: Grant::All,
+            env: true,
+            run: true,
+        }
+    }
+
+    pub fn check_read(&self, path: &Path) -> Result<(), PermissionDenied> {
+        check_path(&self.read, path, "allow-read", "read")
+    }
+
+    pub fn check_write(&self, path: &Path) -> Result<(), PermissionDenied> {
+        check_path(&self.write, path, "allow-write", "write")
+    }
+
+    pub fn check_net(&self, host: &str, port: u16) -> Result<(), PermissionDenied> {
+        let allowed = match &self.net {
+            Grant::Denied => false,
+            Grant::All => true,
+            Grant::Only(addresses) => addresses.iter().any(|address| {
+                address.host.eq_ignore_ascii_case(host) && address.port.is_none_or(|allowed_port| allowed_port == port)
+            }),
+        };
+
+        if allowed {
+            return Ok(());
+        }
+        Err(PermissionDenied {
+            flag: "allow-net",
+            description: format!("net access to \"{}:{}\"", host, port),
+        })
+    }
+
+    // There are no environment or subprocess APIs yet, these guard them once added
+    #[allow(dead_code)]
+    pub fn check_env(&self, name: &str) -> Result<(), PermissionDenied> {
+        if self.env {
+            return Ok(());
+        }
+        Err(PermissionDenied { flag: "allow-env", description: format!("env access to \"{}\"", name) })
+    }
+
+    #[allow(dead_code)]
+    pub fn check_run(&self, command: &str) -> Result<(), PermissionDenied> {
+        if self.run {
+            return Ok(());
+        }
+        Err(PermissionDenied { flag: "allow-run", description: format!("run access to \"{}\"", command) })
+    }
+}
+
+fn parse_grant<T>(
+    value: Option<&str>,
+    parse: impl Fn(&str) -> Result<T, String>
+) -> Result<Grant<T>, String> {
+    let Some(value) = value else {
+        return Ok(Grant::All);
+    };
+
+    let entries = value.split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(parse)
+        .collect::<Result<Vec<T>, String>>()?;
+    Ok(Grant::Only(entries))
+}
+
+fn parse_net_address(value: &str) -> Result<NetAddress, String> {
+    // Bracketed IPv6 hosts contain colons of their own
+    let (host, port) = match value.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') && !port.ends_with(']') => {
+            let port = port.parse::<u16>().map_err(|_| format!("Invalid port in --allow-net: {}", value))?;
+            (host, Some(port))
+        }
+        _ => (value, None),
+    };
+
+    Ok(NetAddress {
+        host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+        port,
+    })
+}
+
+fn check_path(
+    grant: &Grant<PathBuf>,
+    path: &Path,
+    flag: &'static str,
+    access: &str
+) -> Result<(), PermissionDenied> {
+    let allowed = match grant {
+        Grant::Denied => false,
+        Grant::All => true,
+        Grant::Only(allowed_paths) => {
+            let path = resolve_path(path);
+            allowed_paths.iter().any(|allowed_path| path.starts_with(allowed_path))
+        }
+    };
+
+    if allowed {
+        return Ok(());
+    }
+    Err(PermissionDenied {
+        flag,
+        description: format!("{} access to \"{}\"", access, path.display()),
+    })
+}
+
+// Makes the path absolute and resolves it like the OS would, so neither `..` nor symlinks
+// can be used to step outside of a granted directory. The longest existing prefix is
+// canonicalized as written, `..` after a symlink leads out of the symlink's target. Only the
+// part that doesn't exist yet is normalized as text
+fn resolve_path(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+
+    let components: Vec<Component> = absolute.components().collect();
+    for end in (1..=components.len()).rev() {
+        let existing: PathBuf = components[..end].iter().collect();
+        if let Ok(resolved) = existing.canonicalize() {
+            return normalize(resolved, &components[end..]);
+        }
+    }
+    normalize(PathBuf::new(), &components)
+}
+
+fn normalize(mut normalized: PathBuf, components: &[Component]) -> PathBuf {
+    for component in components {
+        match component {
+            Component::ParentDir => { normalized.pop(); }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -1,143 +1,842 @@
 use rusty_v8 as v8;
 use tokio;
-use tokio::net::TcpStream; 
 use tokio::io::AsyncWriteExt;
 use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
 use url::Url;
+use bytes::Bytes;
+use h2::server::SendResponse;
 
-use std::collections::HashMap;
-use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::cell::RefCell;
 use std::ffi::c_void;
+use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use crate::interface::Operations; 
 use crate::interface::HttpOperation; 
 
-use crate::emitter::EventEmitter;
+use crate::emitter::{attach_event_emitter, emit_event, EventEmitter};
+use crate::headers::{header_values_from_js, header_values_to_js, validate_header, Headers};
+use crate::http::{reject_request, LentConnection};
+use crate::http2::{header_map, send_data};
+use crate::helper::{set_function, throw_error, throw_type_error, value_to_bytes};
+use crate::interface::StreamEvent;
+use crate::stream::WRITE_HIGH_WATER_MARK;
 
 pub struct Response {
     pub status_code: u16,                  
-    pub headers: HashMap<String, String>,  
-    pub body: String,                      
-    //event_emitter: EventEmitter,
+    pub headers: Headers,  
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    // Set for served responses, the writer task owns the connection
+    writer: Option<ResponseWriter>,
+    version: u8,
+    keep_alive: bool,
+    // The server's close signal, a closing server sends Connection: close
+    closing: Option<watch::Receiver<bool>>,
+    framing: Framing,
+    // The Content-Length that was sent and the body bytes written against it
+    content_length: Option<usize>,
+    body_written: usize,
+    // Overrides the reason phrase of the status line
+    status_message: Option<String>,
+    // Responses to HEAD requests describe a body without sending it
+    head_request: bool,
+    // Served over HTTP/2, the head and trailers go out as frames of their own
+    http2: bool,
+    // Sent after the body by addTrailers(), chunked HTTP/1.1 responses and HTTP/2 only
+    trailers: Headers,
+    headers_sent: bool,
+    ended: bool,
+}
+
+// How the body is delimited on the wire, decided when the head is sent
+#[derive(Clone, Copy, PartialEq)]
+enum Framing {
+    Length,
+    Chunked,
+    // HTTP/1.0 clients without a Content-Length read until the connection closes
+    Close,
+    // HEAD, 1xx, 204 and 304 responses never carry a body
+    None,
+}
+
+enum ResponseCommand {
+    // Only for HTTP/2 streams, HTTP/1.x heads are written as bytes
+    Head(u16, Headers),
+    Write(Vec<u8>, usize, Option<v8::Global<v8::Function>>),
+    Trailers(Headers),
+    End(bool, Option<v8::Global<v8::Function>>),
+}
+
+struct ResponseWriter {
+    commands: UnboundedSender<ResponseCommand>,
+    // Bytes handed to write() that have not reached the socket yet
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
 }
 
 
 impl Response {
-    pub fn new(status_code: u16, headers: HashMap<String, String>, body: String) -> Self {
+    pub fn new(status_code: u16, headers: Headers) -> Self {
         Response {
             status_code,
             headers,
-            body,
-            //event_emitter: EventEmitter::new(), 
+            event_emitter: Rc::new(RefCell::new(EventEmitter::new())),
+            writer: None,
+            version: 1,
+            keep_alive: false,
+            closing: None,
+            framing: Framing::Length,
+            content_length: None,
+            body_written: 0,
+            status_message: None,
+            head_request: false,
+            http2: false,
+            trailers: Headers::new(),
+            headers_sent: false,
+            ended: false,
         }
     }
 
+    // A response to a served request, written to the connection as it is produced.
+    // The connection is handed back through `done` once the response has finished
+    pub fn for_connection(
+        connection: LentConnection,
+        method: &str,
+        version: u8,
+        keep_alive: bool,
+        closing: watch::Receiver<bool>,
+        tx: UnboundedSender<Operations>
+    ) -> Self {
+        let mut response = Response::new(200, Headers::new());
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<ResponseCommand>();
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let need_drain = Arc::new(AtomicBool::new(false));
+
+        let emitter = response.event_emitter.clone();
+        let task_buffered = buffered.clone();
+        let task_need_drain = need_drain.clone();
+        tokio::task::spawn_local(async move {
+            write_response_chunks(connection, emitter, commands_rx, task_buffered, task_need_drain, tx).await;
+        });
+
+        response.writer = Some(ResponseWriter { commands, buffered, need_drain });
+        response.version = version;
+        response.head_request = method.eq_ignore_ascii_case("HEAD");
+        response.keep_alive = keep_alive;
+        response.closing = Some(closing);
+        response
+    }
+
+    // A response to a request on an HTTP/2 stream, the stream ends with the response
+    pub fn for_http2_stream(
+        respond: SendResponse<Bytes>,
+        method: &str,
+        tx: UnboundedSender<Operations>
+    ) -> Self {
+        let mut response = Response::new(200, Headers::new());
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<ResponseCommand>();
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let need_drain = Arc::new(AtomicBool::new(false));
+
+        let emitter = response.event_emitter.clone();
+        let task_buffered = buffered.clone();
+        let task_need_drain = need_drain.clone();
+        tokio::task::spawn_local(async move {
+            write_http2_response(respond, emitter, commands_rx, task_buffered, task_need_drain, tx).await;
+        });
+
+        response.writer = Some(ResponseWriter { commands, buffered, need_drain });
+        response.version = 1;
+        response.head_request = method.eq_ignore_ascii_case("HEAD");
+        response.http2 = true;
+        response
+    }
+
     pub fn add_header(&mut self, key: String, value: String) {
-        self.headers.insert(key, value);
+        self.headers.set(key, value);
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove(name);
+    }
+
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers.contains(name)
     }
 
     pub fn set_status_code(&mut self, code: u16) {
         self.status_code = code; 
     }
 
-    pub async fn end(&mut self, stream_ptr: *mut TcpStream, data: Option<String>) {
-        if stream_ptr.is_null(){
-            println!("Stream pointer is null");
+    pub fn set_status_message(&mut self, message: Option<String>) {
+        self.status_message = message;
+    }
+
+    pub fn status_message(&self) -> &str {
+        match &self.status_message {
+            Some(message) => message,
+            None => status_reason(self.status_code),
         }
+    }
 
-        // Convert raw pointer to mutable reference
-        let stream = unsafe { &mut *stream_ptr };
+    pub fn headers_sent(&self) -> bool {
+        self.headers_sent
+    }
 
-        // Append any additional data to the body
-        if let Some(additional_data) = data {
-            println!("{}", additional_data);
-            self.body.push_str(&additional_data);
+    // Sends the status line and headers, `body_length` is known when called from end()
+    fn send_head(&mut self, body_length: Option<usize>) -> Result<(), String> {
+        if self.writer.is_none() {
+            return Err("response is not writable".to_string());
         }
+        if self.headers_sent {
+            return Ok(());
+        }
+        self.headers_sent = true;
 
-        // Send the HTTP status line
-        let status_line = format!("HTTP/1.1 {} OK\r\n", self.status_code);
-        if let Err(e) = stream.write_all(status_line.as_bytes()).await {
-            eprintln!("Failed to write status line: {}", e);
-            return;
+        let body_allowed = !matches!(self.status_code, 100..=199 | 204 | 304);
+        if !body_allowed {
+            self.remove_header("Transfer-Encoding");
+            if self.status_code != 304 {
+                self.remove_header("Content-Length");
+            }
         }
 
-        // Send the headers
-        for (key, value) in &self.headers {
-            let header_line = format!("{}: {}\r\n", key, value);
-            if let Err(e) = stream.write_all(header_line.as_bytes()).await {
-                eprintln!("Failed to write header: {}: {}", key, e);
-                return;
+        let mut chunked_requested = self.headers.get_joined("Transfer-Encoding")
+            .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+        // HTTP/1.0 clients don't know chunked, the body is delimited another way
+        if chunked_requested && self.version == 0 {
+            self.remove_header("Transfer-Encoding");
+            chunked_requested = false;
+        }
+        self.framing = if !body_allowed {
+            Framing::None
+        } else if self.head_request {
+            // Announce the length the body would have had, when it is known
+            if let (Some(length), false) = (body_length, self.has_header("Content-Length") || chunked_requested) {
+                self.add_header("Content-Length".to_string(), length.to_string());
             }
+            Framing::None
+        } else if chunked_requested {
+            Framing::Chunked
+        } else if self.has_header("Content-Length") {
+            Framing::Length
+        } else if let Some(length) = body_length {
+            self.add_header("Content-Length".to_string(), length.to_string());
+            Framing::Length
+        } else if self.version >= 1 {
+            self.add_header("Transfer-Encoding".to_string(), "chunked".to_string());
+            Framing::Chunked
+        } else {
+            Framing::Close
+        };
+        if self.framing == Framing::Length {
+            self.content_length = self.headers.get_last("Content-Length").and_then(|value| value.trim().parse().ok());
         }
 
-        // End headers with an empty line
-        if let Err(e) = stream.write_all(b"\r\n").await {
-            eprintln!("Failed to write end of headers: {}", e);
-            return;
+        if !self.has_header("Date") {
+            self.add_header("Date".to_string(), httpdate::fmt_http_date(SystemTime::now()));
         }
 
-        // Send the body
-        if let Err(e) = stream.write_all(self.body.as_bytes()).await {
-            eprintln!("Failed to write body: {}", e);
-            return;
+        // HTTP/2 frames the body itself and forbids connection-specific headers
+        if self.http2 {
+            for name in ["Connection", "Keep-Alive", "Proxy-Connection", "Transfer-Encoding", "Upgrade"] {
+                self.remove_header(name);
+            }
+            if matches!(self.framing, Framing::Chunked | Framing::Close) {
+                self.framing = Framing::Length;
+            }
+            self.queue_command(ResponseCommand::Head(self.status_code, self.headers.clone()));
+            return Ok(());
         }
 
-        // Flush and close the stream
-        if let Err(e) = stream.flush().await {
-            eprintln!("Failed to flush the stream: {}", e);
-            return;
+        // The connection can only be reused when the body is delimited, neither side
+        // asked to close it and the server is not shutting down
+        let close_requested = self.headers.get_all("Connection")
+            .any(|value| value.split(',').any(|token| token.trim().eq_ignore_ascii_case("close")));
+        let server_closing = self.closing.as_ref().is_some_and(|closing| *closing.borrow());
+        self.keep_alive = self.keep_alive && !close_requested && !server_closing && self.framing != Framing::Close;
+        if !self.has_header("Connection") {
+            let connection = if self.keep_alive { "keep-alive" } else { "close" };
+            self.add_header("Connection".to_string(), connection.to_string());
+        }
+
+        let reason = match &self.status_message {
+            Some(message) => message.clone(),
+            None => status_reason(self.status_code).to_string(),
+        };
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status_code, reason);
+        head.push_str(&self.headers.serialize());
+        head.push_str("\r\n");
+
+        self.queue(head.into_bytes(), 0, None);
+        Ok(())
+    }
+
+    // Returns false once the caller should wait for 'drain' before writing more
+    pub fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<bool, String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+        self.send_head(None)?;
+
+        // Writes to a response without a body are dropped
+        if self.framing == Framing::None {
+            return Ok(self.queue(Vec::new(), 0, callback));
+        }
+
+        let length = data.len();
+        if let Some(content_length) = self.content_length {
+            if self.body_written + length > content_length {
+                return Err(format!(
+                    "Response body's content-length of {} byte(s) does not match the content-length of {} byte(s) set in header",
+                    self.body_written + length,
+                    content_length
+                ));
+            }
+        }
+        self.body_written += length;
+
+        let frame = match self.framing {
+            // An empty chunk would terminate the body
+            Framing::Chunked if length == 0 => Vec::new(),
+            Framing::Chunked => {
+                let mut frame = format!("{:x}\r\n", length).into_bytes();
+                frame.extend_from_slice(&data);
+                frame.extend_from_slice(b"\r\n");
+                frame
+            }
+            _ => data,
+        };
+        Ok(self.queue(frame, length, callback))
+    }
+
+    pub fn end(&mut self, data: Option<Vec<u8>>, callback: Option<v8::Global<v8::Function>>) -> Result<(), String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+
+        let data = data.unwrap_or_default();
+        if !self.headers_sent {
+            self.send_head(Some(data.len()))?;
+        }
+        if !data.is_empty() && self.framing != Framing::None {
+            if let Err(e) = self.write(data, None) {
+                // The body overran its Content-Length, the connection can't be trusted anymore
+                self.ended = true;
+                self.queue_command(ResponseCommand::End(false, None));
+                return Err(e);
+            }
+        }
+        if self.framing == Framing::Chunked {
+            let last_chunk = format!("0\r\n{}\r\n", self.trailers.serialize());
+            self.queue(last_chunk.into_bytes(), 0, None);
+        }
+        if self.http2 && self.framing != Framing::None && self.trailers.iter().next().is_some() {
+            self.queue_command(ResponseCommand::Trailers(self.trailers.clone()));
+        }
+
+        // A body shorter than its Content-Length can only be ended by closing the connection
+        if self.content_length.is_some_and(|content_length| self.body_written < content_length) {
+            self.keep_alive = false;
+        }
+
+        self.ended = true;
+        self.queue_command(ResponseCommand::End(self.keep_alive, callback));
+        Ok(())
+    }
+
+    pub fn add_trailers(&mut self, trailers: Headers) {
+        for (name, value) in trailers.iter() {
+            self.trailers.append(name.to_string(), value.to_string());
         }
+    }
 
-        if let Err(e) = stream.shutdown().await {
-            eprintln!("Failed to shutdown the stream: {}", e);
+    fn queue_command(&self, command: ResponseCommand) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.commands.send(command);
         }
     }
+
+    fn queue(&mut self, frame: Vec<u8>, length: usize, callback: Option<v8::Global<v8::Function>>) -> bool {
+        let Some(writer) = &self.writer else {
+            return false;
+        };
+
+        let buffered = writer.buffered.fetch_add(length, Ordering::SeqCst) + length;
+        let _ = writer.commands.send(ResponseCommand::Write(frame, length, callback));
+
+        let ok = buffered < WRITE_HIGH_WATER_MARK;
+        if !ok {
+            writer.need_drain.store(true, Ordering::SeqCst);
+        }
+        ok
+    }
+}
+
+// Reason phrases of the status line, from the IANA HTTP status code registry
+pub fn status_reason(status_code: u16) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        102 => "Processing",
+        103 => "Early Hints",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        203 => "Non-Authoritative Information",
+        204 => "No Content",
+        205 => "Reset Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        208 => "Already Reported",
+        226 => "IM Used",
+        300 => "Multiple Choices",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        305 => "Use Proxy",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        402 => "Payment Required",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        407 => "Proxy Authentication Required",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        418 => "I'm a Teapot",
+        421 => "Misdirected Request",
+        422 => "Unprocessable Entity",
+        423 => "Locked",
+        424 => "Failed Dependency",
+        425 => "Too Early",
+        426 => "Upgrade Required",
+        428 => "Precondition Required",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        451 => "Unavailable For Legal Reasons",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        506 => "Variant Also Negotiates",
+        507 => "Insufficient Storage",
+        508 => "Loop Detected",
+        510 => "Not Extended",
+        511 => "Network Authentication Required",
+        _ => "Unknown",
+    }
+}
+
+async fn write_response_chunks(
+    connection: LentConnection,
+    emitter: Rc<RefCell<EventEmitter>>,
+    mut commands: UnboundedReceiver<ResponseCommand>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    tx: UnboundedSender<Operations>
+) {
+    let LentConnection { mut writer, done, mut rejected } = connection;
+    let mut can_reject = true;
+    let mut started = false;
+    loop {
+        let command = tokio::select! {
+            biased;
+            status = &mut rejected, if can_reject => {
+                can_reject = false;
+                let Ok(status) = status else { continue };
+                // The request body was malformed, answer it unless the response is already on the wire
+                if started {
+                    let _ = writer.shutdown().await;
+                } else {
+                    reject_request(&mut writer, status).await;
+                }
+                break;
+            }
+            command = commands.recv() => match command {
+                Some(command) => command,
+                None => break,
+            },
+        };
+        match command {
+            ResponseCommand::Write(frame, length, callback) => {
+                started = true;
+                // Flush every write, streamed responses such as server-sent events rely on it
+                let result = match writer.write_all(&frame).await {
+                    Ok(()) => writer.flush().await,
+                    Err(e) => Err(e),
+                };
+                let remaining = buffered.fetch_sub(length, Ordering::SeqCst) - length;
+
+                if let Some(callback) = callback {
+                    let error_message = result.as_ref().err().map(|e| e.to_string());
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                }
+                if let Err(e) = result {
+                    // Usually the client went away, the connection is not reused
+                    eprintln!("Failed to write response: {}", e);
+                    break;
+                }
+
+                if remaining == 0 && need_drain.swap(false, Ordering::SeqCst) {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "drain" }));
+                }
+            }
+
+            ResponseCommand::End(keep_alive, callback) => {
+                if !keep_alive {
+                    if let Err(e) = writer.shutdown().await {
+                        eprintln!("Failed to shutdown the stream: {}", e);
+                    }
+                }
+
+                let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "finish" }));
+                if let Some(callback) = callback {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message: None }));
+                }
+                let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+
+                let _ = done.send(if keep_alive { Some(writer) } else { None });
+                return;
+            }
+
+            // The head and trailers of HTTP/1.x responses are part of the byte stream
+            ResponseCommand::Head(..) | ResponseCommand::Trailers(_) => {}
+        }
+    }
+
+    // The connection failed, the request was rejected or the response was dropped without end()
+    let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+    let _ = done.send(None);
+}
+
+// The writer task of a response on an HTTP/2 stream. Writes wait for the client's flow
+// control window, end() closes the stream with the trailers when there are any
+async fn write_http2_response(
+    mut respond: SendResponse<Bytes>,
+    emitter: Rc<RefCell<EventEmitter>>,
+    mut commands: UnboundedReceiver<ResponseCommand>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    tx: UnboundedSender<Operations>
+) {
+    let mut stream = None;
+    let mut trailers = None;
+
+    while let Some(command) = commands.recv().await {
+        match command {
+            ResponseCommand::Head(status_code, headers) => {
+                let mut head = http::Response::new(());
+                *head.status_mut() = http::StatusCode::from_u16(status_code).unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+                *head.headers_mut() = header_map(&headers);
+                match respond.send_response(head, false) {
+                    Ok(send_stream) => stream = Some(send_stream),
+                    Err(e) => {
+                        eprintln!("Failed to write response: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            ResponseCommand::Write(data, length, callback) => {
+                let result = match stream.as_mut() {
+                    Some(stream) => send_data(stream, data, false).await,
+                    None => Ok(()),
+                };
+                let remaining = buffered.fetch_sub(length, Ordering::SeqCst) - length;
+
+                if let Some(callback) = callback {
+                    let error_message = result.as_ref().err().map(|e| e.to_string());
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                }
+                if let Err(e) = result {
+                    // Usually the client reset the stream
+                    eprintln!("Failed to write response: {}", e);
+                    break;
+                }
+
+                if remaining == 0 && need_drain.swap(false, Ordering::SeqCst) {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "drain" }));
+                }
+            }
+
+            ResponseCommand::Trailers(headers) => trailers = Some(header_map(&headers)),
+
+            ResponseCommand::End(_, callback) => {
+                let result = match (stream.as_mut(), trailers.take()) {
+                    (Some(stream), Some(trailers)) => stream.send_trailers(trailers),
+                    (Some(stream), None) => stream.send_data(Bytes::new(), true),
+                    (None, _) => Ok(()),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to end response: {}", e);
+                }
+
+                let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "finish" }));
+                if let Some(callback) = callback {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message: None }));
+                }
+                let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+                return;
+            }
+        }
+    }
+
+    // The stream failed or the response was dropped without end(), the client sees a reset
+    if let Some(mut stream) = stream {
+        stream.send_reset(h2::Reason::INTERNAL_ERROR);
+    }
+    let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
 }
 
 // Response Methods
-pub fn response_set_status_code_callback(
+fn response_status_code_getter(
     scope: &mut v8::HandleScope,
-    args: v8::FunctionCallbackArguments,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
     mut rv: v8::ReturnValue,
 ){
-    // Retrieve the JS object (the "this" object in JavaScript)
-    let js_response_obj = args.this();
+    let response = get_response(scope, args.this());
+    rv.set(v8::Integer::new(scope, response.status_code as i32).into());
+}
 
-    // Get the internal field (the Rust Response struct)
-    let internal_field = js_response_obj.get_internal_field(scope, 0).unwrap();
+// res.statusCode = 404, used for the status line when the head is sent
+fn response_status_code_setter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    value: v8::Local<v8::Value>,
+    args: v8::PropertyCallbackArguments,
+){
+    let response = get_response(scope, args.this());
+    let status_code = value.number_value(scope).unwrap_or(f64::NAN);
+    if status_code.fract() != 0.0 || !(100.0..=999.0).contains(&status_code) {
+        let message = format!("Invalid status code: {}", value.to_rust_string_lossy(scope));
+        throw_error(scope, &message);
+        return;
+    }
+    response.set_status_code(status_code as u16);
+}
+
+pub fn get_response<'a>(scope: &mut v8::HandleScope, response_obj: v8::Local<v8::Object>) -> &'a mut Response {
+    let internal_field = response_obj.get_internal_field(scope, 0).unwrap();
     let external_response = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_response.value() as *mut Response) }
+}
 
-    // Cast the external pointer back to the Rust Response object
-    let response = unsafe { &mut *(external_response.value() as *mut Response) };
+// Reports a failed write/end as an 'error' event like writable streams do
+pub fn emit_response_error(scope: &mut v8::HandleScope, response: &Response, error_message: &str) {
+    let error_value = v8::String::new(scope, error_message).unwrap();
+    emit_event(scope, &response.event_emitter, "error", &[error_value.into()]);
+}
 
-    let status_code = args.get(0).to_rust_string_lossy(scope);
+pub fn response_set_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    if response.headers_sent() {
+        throw_error(scope, "Cannot set headers after they are sent to the client");
+        return;
+    }
+
+    // Set a header in the Rust Response object, an array sets one line per value (Set-Cookie)
+    let key = args.get(0).to_rust_string_lossy(scope);
+    let values = header_values_from_js(scope, args.get(1));
+    if let Err(e) = validate_header(&key, &values) {
+        throw_type_error(scope, &e);
+        return;
+    }
+
+    response.headers.set_all(key, values);
 
-    response.set_status_code(status_code.parse::<u16>().unwrap_or(400));
+    rv.set(args.this().into());
+}
+
+pub fn response_get_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+
+    let values: Vec<&str> = response.headers.get_all(&name).collect();
+    rv.set(header_values_to_js(scope, &values));
+}
+
+pub fn response_remove_header_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    if response.headers_sent() {
+        throw_error(scope, "Cannot remove headers after they are sent to the client");
+        return;
+    }
+
+    let name = args.get(0).to_rust_string_lossy(scope);
+    response.remove_header(&name);
     rv.set(v8::undefined(scope).into());
 }
 
-pub fn response_set_header_callback(
+pub fn response_has_header_callback(
     scope: &mut v8::HandleScope,
     args: v8::FunctionCallbackArguments,
     mut rv: v8::ReturnValue,
 ){
-    // Retrieve the JS object (the "this" object in JavaScript)
-    let js_response_obj = args.this();
+    let response = get_response(scope, args.this());
+    let name = args.get(0).to_rust_string_lossy(scope);
+    rv.set(v8::Boolean::new(scope, response.has_header(&name)).into());
+}
 
-    // Get the internal field (the Rust Response struct)
-    let internal_field = js_response_obj.get_internal_field(scope, 0).unwrap();
-    let external_response = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+fn response_headers_sent_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    rv.set(v8::Boolean::new(scope, response.headers_sent()).into());
+}
 
-    // Cast the external pointer back to the Rust Response object
-    let response = unsafe { &mut *(external_response.value() as *mut Response) };
+fn response_status_message_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    rv.set(v8::String::new(scope, response.status_message()).unwrap().into());
+}
 
-    // Set a header in the Rust Response object
-    let key = args.get(0).to_rust_string_lossy(scope);
-    let value = args.get(1).to_rust_string_lossy(scope);
+fn response_status_message_setter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    value: v8::Local<v8::Value>,
+    args: v8::PropertyCallbackArguments,
+){
+    let response = get_response(scope, args.this());
+    if value.is_null_or_undefined() {
+        response.set_status_message(None);
+        return;
+    }
+    let message = value.to_rust_string_lossy(scope);
+    if !is_valid_status_message(&message) {
+        throw_type_error(scope, "Invalid character in statusMessage");
+        return;
+    }
+    response.set_status_message(Some(message));
+}
 
-    response.add_header(key, value);
+// The reason phrase must stay on the status line
+fn is_valid_status_message(message: &str) -> bool {
+    !message.bytes().any(|byte| byte == b'\r' || byte == b'\n' || byte == 0)
+}
 
-    rv.set(v8::undefined(scope).into());
+// writeHead(statusCode, [statusMessage], [headers]), the head is sent right away
+pub fn response_write_head_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    if response.headers_sent() {
+        throw_error(scope, "Cannot write headers after they are sent to the client");
+        return;
+    }
+
+    let status_code = args.get(0).integer_value(scope).unwrap_or(200);
+    if !(100..=999).contains(&status_code) {
+        throw_error(scope, &format!("Invalid status code: {}", status_code));
+        return;
+    }
+    let status_message = if args.get(1).is_string() { Some(args.get(1).to_rust_string_lossy(scope)) } else { None };
+    if !status_message.as_deref().is_none_or(is_valid_status_message) {
+        throw_type_error(scope, "Invalid character in statusMessage");
+        return;
+    }
+    response.set_status_code(status_code as u16);
+
+    // writeHead(statusCode, [statusMessage], [headers])
+    if status_message.is_some() {
+        response.set_status_message(status_message);
+    }
+    let headers = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| if value.is_string() { None } else { v8::Local::<v8::Object>::try_from(value).ok() });
+    if let Some(headers) = headers {
+        // Nothing is set when one of the headers is invalid
+        let mut entries = Vec::new();
+        let names = headers.get_own_property_names(scope).unwrap();
+        for i in 0..names.length() {
+            let name = names.get_index(scope, i).unwrap();
+            let value = headers.get(scope, name).unwrap();
+            let name = name.to_rust_string_lossy(scope);
+            let values = header_values_from_js(scope, value);
+            if let Err(e) = validate_header(&name, &values) {
+                throw_type_error(scope, &e);
+                return;
+            }
+            entries.push((name, values));
+        }
+        for (name, values) in entries {
+            response.headers.set_all(name, values);
+        }
+    }
+
+    if let Err(error_message) = response.send_head(None) {
+        emit_response_error(scope, response, &error_message);
+    }
+    rv.set(args.this().into());
+}
+
+// addTrailers(headers), sent after the body of chunked and HTTP/2 responses
+fn response_add_trailers_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    let Ok(trailers_obj) = v8::Local::<v8::Object>::try_from(args.get(0)) else {
+        throw_error(scope, "The \"headers\" argument must be an object");
+        return;
+    };
+
+    let mut trailers = Headers::new();
+    let names = trailers_obj.get_own_property_names(scope).unwrap();
+    for i in 0..names.length() {
+        let name = names.get_index(scope, i).unwrap();
+        let value = trailers_obj.get(scope, name).unwrap();
+        let name = name.to_rust_string_lossy(scope);
+        let values = header_values_from_js(scope, value);
+        if let Err(e) = validate_header(&name, &values) {
+            throw_type_error(scope, &e);
+            return;
+        }
+        for value in values {
+            trailers.append(name.clone(), value);
+        }
+    }
+    response.add_trailers(trailers);
 }
 
 pub fn response_end_callback(
@@ -145,36 +844,46 @@ pub fn response_end_callback(
     args: v8::FunctionCallbackArguments,
     mut rv: v8::ReturnValue,
 ){
-    // Retrieve the JS object (the "this" object in JavaScript)
-    let js_response_obj = args.this();
-
-    // Get the internal field (the Rust Response struct)
-    let internal_field = js_response_obj.get_internal_field(scope, 0).unwrap();
-    let external_response = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    let response = get_response(scope, args.this());
 
-    // Cast the external pointer back to the Rust Response object
-    let response_ptr = unsafe { &mut *(external_response.value() as *mut Response) };
+    // end([chunk], [encoding], [callback])
+    let callback = (0..args.length())
+        .find_map(|i| v8::Local::<v8::Function>::try_from(args.get(i)).ok())
+        .map(|callback| v8::Global::new(scope, callback));
 
     // Optional: Get the final data to be appended to the body (if provided)
-    let mut final_chunk = String::from("");
-    if args.length() > 0 && args.get(0).is_string() {
-        final_chunk = args.get(0).to_rust_string_lossy(scope);
+    let mut final_chunk = None;
+    if !args.get(0).is_function() && !args.get(0).is_null_or_undefined() {
+        final_chunk = Some(value_to_bytes(scope, args.get(0)));
     }
 
-    // Get the internal field (the Tokio TcpStream Socket)
-    let internal_field_socket = js_response_obj.get_internal_field(scope, 1).unwrap();
-    let external_socket = v8::Local::<v8::External>::try_from(internal_field_socket).unwrap();
-    let socket_ptr = unsafe { external_socket.value() as *mut tokio::net::TcpStream };
-
-    tokio::task::spawn_local(async move {
-        let socket = unsafe { &mut *socket_ptr };
-        let response = unsafe { &mut *response_ptr };
+    if let Err(error_message) = response.end(final_chunk, callback) {
+        emit_response_error(scope, response, &error_message);
+    }
 
-        response.end(socket, Some(final_chunk)).await;
-        //send_response(socket, response);
-    });
+    rv.set(args.this().into());
+}
 
-    rv.set(v8::undefined(scope).into());
+pub fn response_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+){
+    let response = get_response(scope, args.this());
+    let chunk = value_to_bytes(scope, args.get(0));
+
+    // write(chunk, [encoding], [callback])
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match response.write(chunk, callback) {
+        Ok(ok) => rv.set(v8::Boolean::new(scope, ok).into()),
+        Err(error_message) => {
+            emit_response_error(scope, response, &error_message);
+            rv.set(v8::Boolean::new(scope, false).into());
+        }
+    }
 }
 
 // pub fn response_on_callback(
@@ -204,41 +913,48 @@ pub fn response_end_callback(
 
 pub fn create_response_object<'s>(
     scope: &mut v8::HandleScope<'s>,
-    response: Box<Response>, 
-    socket: Box<tokio::net::TcpStream>
+    response: Box<Response>
 ) -> v8::Local<'s, v8::Object> {
     // Create the Response object template
     let response_template = v8::ObjectTemplate::new(scope);
-    response_template.set_internal_field_count(2); // Store the Rust Response struct and socket internally
+    response_template.set_internal_field_count(1); // Store the Rust Response struct internally
     let response_obj = response_template.new_instance(scope).unwrap();
 
-    let status_code_fn_template = v8::FunctionTemplate::new(scope, response_set_status_code_callback);
     let set_header_fn_template = v8::FunctionTemplate::new(scope, response_set_header_callback);
     //let on_fn_template = v8::FunctionTemplate::new(scope, response_on_callback);
     let set_end_fn_template = v8::FunctionTemplate::new(scope, response_end_callback);
 
-    let status_fn = status_code_fn_template.get_function(scope).unwrap();
     let set_header_fn = set_header_fn_template.get_function(scope).unwrap();
     //let on_fn = on_fn_template.get_function(scope).unwrap();
     let end_fn = set_end_fn_template.get_function(scope).unwrap();
 
-    let status_key = v8::String::new(scope, "statusCode").unwrap();
     let set_header_key = v8::String::new(scope, "setHeader").unwrap();
     //let on_key = v8::String::new(scope, "on").unwrap();
     let end_key = v8::String::new(scope, "end").unwrap(); 
 
-    response_obj.set(scope, status_key.into(), status_fn.into());
     response_obj.set(scope, set_header_key.into(), set_header_fn.into());
     //response_obj.set(scope, on_key.into(), on_fn.into());
     response_obj.set(scope, end_key.into(), end_fn.into());
+    set_function(scope, response_obj, "write", response_write_callback);
+    set_function(scope, response_obj, "writeHead", response_write_head_callback);
+    set_function(scope, response_obj, "getHeader", response_get_header_callback);
+    set_function(scope, response_obj, "removeHeader", response_remove_header_callback);
+    set_function(scope, response_obj, "hasHeader", response_has_header_callback);
+    set_function(scope, response_obj, "addTrailers", response_add_trailers_callback);
+    attach_event_emitter(scope, response_obj, &response.event_emitter);
+
+    let headers_sent_key = v8::String::new(scope, "headersSent").unwrap();
+    response_obj.set_accessor(scope, headers_sent_key.into(), response_headers_sent_getter);
+    let status_code_key = v8::String::new(scope, "statusCode").unwrap();
+    response_obj.set_accessor_with_setter(scope, status_code_key.into(), response_status_code_getter, response_status_code_setter);
+    let status_message_key = v8::String::new(scope, "statusMessage").unwrap();
+    response_obj.set_accessor_with_setter(scope, status_message_key.into(), response_status_message_getter, response_status_message_setter);
 
     // Create a Rust Response object and wrap it in External
     let external_response = v8::External::new(scope, Box::into_raw(response) as *const _ as *mut c_void);
-    let external_socket = v8::External::new(scope, Box::into_raw(socket) as *const _ as *mut c_void);
 
     // Set the Rust Response object as an internal field of the JS object
     response_obj.set_internal_field(0, external_response.into());
-    response_obj.set_internal_field(1, external_socket.into());
 
     response_obj
 }
\ No newline at end of file
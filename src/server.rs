@@ -0,0 +1,762 @@
+use rusty_v8 as v8;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::emitter::{attach_event_emitter, EventEmitter};
+use crate::helper::{set_function, throw_error};
+use crate::http::{serve_connection, Http};
+use crate::http2::{serve_http2_connection, Http2Options, Http2Settings};
+use crate::interface::{HttpOperation, Operations, StreamEvent};
+use crate::permissions::{PermissionDenied, Permissions};
+use crate::client::pem_bundle;
+use crate::tls::{certified_key, ServerTls};
+
+// Node's defaults, a timeout of 0 disables it
+pub const KEEP_ALIVE_TIMEOUT_MS: u64 = 5000;
+pub const HEADERS_TIMEOUT_MS: u64 = 60_000;
+pub const REQUEST_TIMEOUT_MS: u64 = 300_000;
+pub const MAX_HEADER_SIZE: usize = 16 * 1024;
+// How long SIGTERM waits for active requests before the remaining connections are cut
+pub const SHUTDOWN_TIMEOUT_MS: u64 = 10_000;
+
+// Shared by a server and the tasks serving its connections
+pub struct ServerState {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    pub tx: UnboundedSender<Operations>,
+    active_connections: watch::Sender<usize>,
+    connections: RefCell<HashMap<u64, Rc<ConnectionHandle>>>,
+    next_connection_id: Cell<u64>,
+    // Set by close(), idle connections end and busy ones after their current response
+    closing: watch::Sender<bool>,
+    close_emitted: Cell<bool>,
+    // Limits that protect against slow or hostile clients, changes apply to open connections too
+    headers_timeout: Cell<u64>,
+    request_timeout: Cell<u64>,
+    keep_alive_timeout: Cell<u64>,
+    // Socket inactivity (server.timeout), off by default
+    socket_timeout: Cell<u64>,
+    // 0 accepts any number of connections
+    max_connections: Cell<usize>,
+    pub max_header_size: usize,
+    shutdown_timeout: Cell<u64>,
+}
+
+// Lets the server end a connection whichever task currently owns its halves
+pub struct ConnectionHandle {
+    stream: std::net::TcpStream,
+    // Waiting for the next request, nothing is lost by closing it
+    idle: Cell<bool>,
+}
+
+impl ConnectionHandle {
+    pub fn set_idle(&self, idle: bool) {
+        self.idle.set(idle);
+    }
+
+    // Reads see the end of the stream and writes fail, so both halves wind down
+    pub fn destroy(&self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn timeout(ms: &Cell<u64>) -> Option<Duration> {
+    match ms.get() {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+impl ServerState {
+    // Time allowed from the first byte of a request until its head is complete
+    pub fn headers_timeout(&self) -> Option<Duration> {
+        timeout(&self.headers_timeout)
+    }
+
+    // Time allowed from the first byte of a request until its body is complete
+    pub fn request_timeout(&self) -> Option<Duration> {
+        timeout(&self.request_timeout)
+    }
+
+    pub fn keep_alive_timeout(&self) -> Option<Duration> {
+        timeout(&self.keep_alive_timeout)
+    }
+
+    pub fn socket_timeout(&self) -> Option<Duration> {
+        timeout(&self.socket_timeout)
+    }
+
+    fn at_connection_limit(&self) -> bool {
+        let max_connections = self.max_connections.get();
+        max_connections > 0 && *self.active_connections.borrow() >= max_connections
+    }
+
+    // Emits one of the server's per-connection events with a socket object
+    pub fn emit_socket_event(&self, event: &'static str, remote_address: Option<SocketAddr>) {
+        if let Some(remote_address) = remote_address {
+            let op = HttpOperation::Socket{ emitter: self.event_emitter.clone(), event, remote_address };
+            let _ = self.tx.send(Operations::Http(op));
+        }
+    }
+
+    pub fn is_closing(&self) -> bool {
+        *self.closing.borrow()
+    }
+
+    pub fn closing_signal(&self) -> watch::Receiver<bool> {
+        self.closing.subscribe()
+    }
+
+    // Tracks an accepted socket, keeping a duplicate of it to close it from outside its task
+    fn connection_opened(&self, socket: tokio::net::TcpStream) -> std::io::Result<(u64, Rc<ConnectionHandle>, tokio::net::TcpStream)> {
+        let socket = socket.into_std()?;
+        let handle = Rc::new(ConnectionHandle { stream: socket.try_clone()?, idle: Cell::new(false) });
+        let socket = tokio::net::TcpStream::from_std(socket)?;
+
+        let id = self.next_connection_id.replace(self.next_connection_id.get() + 1);
+        self.connections.borrow_mut().insert(id, handle.clone());
+        self.active_connections.send_modify(|count| *count += 1);
+        Ok((id, handle, socket))
+    }
+
+    fn connection_closed(&self, id: u64) {
+        self.connections.borrow_mut().remove(&id);
+        self.active_connections.send_modify(|count| *count -= 1);
+        if *self.active_connections.borrow() == 0 && self.is_closing() {
+            self.emit_close();
+        }
+    }
+
+    pub fn close_idle_connections(&self) {
+        for connection in self.connections.borrow().values() {
+            if connection.idle.get() {
+                connection.destroy();
+            }
+        }
+    }
+
+    pub fn close_all_connections(&self) {
+        for connection in self.connections.borrow().values() {
+            connection.destroy();
+        }
+    }
+
+    // Stops accepting and ends idle connections, busy ones close after their response
+    fn begin_close(&self) {
+        self.closing.send_replace(true);
+        self.close_idle_connections();
+        if *self.active_connections.borrow() == 0 {
+            self.emit_close();
+        }
+    }
+
+    // Waits for the busy connections of a closing server, cutting them at the deadline
+    async fn drain(&self, deadline: tokio::time::Instant) {
+        let mut active_connections = self.active_connections.subscribe();
+        let drained = active_connections.wait_for(|count| *count == 0);
+        if tokio::time::timeout_at(deadline, drained).await.is_err() {
+            self.close_all_connections();
+        }
+    }
+
+    // 'close' fires once the server stopped listening and the last connection ended
+    fn emit_close(&self) {
+        if !self.close_emitted.replace(true) {
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: self.event_emitter.clone(), event: "close" }));
+        }
+    }
+
+    fn emit_error(&self, error_message: String) {
+        let _ = self.tx.send(Operations::Stream(StreamEvent::Error{ emitter: self.event_emitter.clone(), error_message }));
+    }
+
+    // A TLS handshake failed, the connection is closed without reaching 'request'
+    fn emit_tls_client_error(&self, error_message: String, remote_address: SocketAddr) {
+        let op = HttpOperation::TlsClientError{ emitter: self.event_emitter.clone(), error_message, remote_address };
+        let _ = self.tx.send(Operations::Http(op));
+    }
+}
+
+pub struct Server {
+    pub state: Rc<ServerState>,
+    // Set for https servers, connections are served once their handshake completed
+    pub tls: Option<ServerTls>,
+    // Set for http2 servers
+    http2: Option<Http2Options>,
+    permissions: Arc<Permissions>,
+    accept_task: Option<JoinHandle<()>>,
+    local_address: Option<SocketAddr>,
+}
+
+impl Server {
+    pub fn new(
+        tx: UnboundedSender<Operations>,
+        permissions: Arc<Permissions>,
+        max_header_size: usize,
+        tls: Option<ServerTls>,
+        http2: Option<Http2Options>
+    ) -> Self {
+        let (closing, _) = watch::channel(false);
+        let state = ServerState {
+            event_emitter: Rc::new(RefCell::new(EventEmitter::new())),
+            tx,
+            active_connections: watch::channel(0).0,
+            connections: RefCell::new(HashMap::new()),
+            next_connection_id: Cell::new(0),
+            closing,
+            close_emitted: Cell::new(false),
+            headers_timeout: Cell::new(HEADERS_TIMEOUT_MS),
+            request_timeout: Cell::new(REQUEST_TIMEOUT_MS),
+            keep_alive_timeout: Cell::new(KEEP_ALIVE_TIMEOUT_MS),
+            socket_timeout: Cell::new(0),
+            max_connections: Cell::new(0),
+            max_header_size,
+            shutdown_timeout: Cell::new(SHUTDOWN_TIMEOUT_MS),
+        };
+
+        Server {
+            state: Rc::new(state),
+            tls,
+            http2,
+            permissions,
+            accept_task: None,
+            local_address: None,
+        }
+    }
+
+    // A SIGTERM shutdown stops the server without close()
+    pub fn is_listening(&self) -> bool {
+        self.accept_task.is_some() && !self.state.is_closing()
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        if self.is_listening() { self.local_address } else { None }
+    }
+
+    // Binds right away so address() works after listen(), bind failures are emitted as 'error'
+    pub fn listen(&mut self, host: String, port: u16) -> Result<(), PermissionDenied> {
+        self.permissions.check_net(&host, port)?;
+
+        if self.is_listening() {
+            self.state.emit_error("Listen method has been called more than once without closing".to_string());
+            return Ok(());
+        }
+
+        let listener = std::net::TcpListener::bind((host.as_str(), port))
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                tokio::net::TcpListener::from_std(listener)
+            });
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.state.emit_error(format!("listen {}: {}:{}", e, host, port));
+                return Ok(());
+            }
+        };
+
+        // A server may listen again after it was closed
+        self.state.closing.send_replace(false);
+        self.state.close_emitted.set(false);
+        self.local_address = listener.local_addr().ok();
+
+        let state = self.state.clone();
+        let _ = state.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: state.event_emitter.clone(), event: "listening" }));
+        register_for_shutdown(&state);
+
+        let mut closing = state.closing_signal();
+        let acceptor = self.tls.as_ref().map(|tls| tls.acceptor.clone());
+        let http2 = self.http2;
+        self.accept_task = Some(tokio::task::spawn_local(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = closing.wait_for(|closing| *closing) => return,
+                };
+                match accepted {
+                    Ok((socket, remote_address)) => {
+                        // Past maxConnections new sockets are closed right away
+                        if state.at_connection_limit() {
+                            drop(socket);
+                            state.emit_socket_event("drop", Some(remote_address));
+                            continue;
+                        }
+
+                        let (id, handle, socket) = match state.connection_opened(socket) {
+                            Ok(opened) => opened,
+                            Err(e) => {
+                                eprintln!("Failed to accept connection: {}", e);
+                                continue;
+                            }
+                        };
+                        state.emit_socket_event("connection", Some(remote_address));
+
+                        // Each connection is served on its own task so slow clients don't block others
+                        let connection_state = state.clone();
+                        let acceptor = acceptor.clone();
+                        tokio::task::spawn_local(async move {
+                            let remote = Some(remote_address);
+                            match acceptor {
+                                None => {
+                                    let settings = http2.map(|http2| http2.settings);
+                                    serve_protocol(socket, settings, remote, connection_state.clone(), handle).await
+                                }
+                                // The handshake has as long as a request head would
+                                Some(acceptor) => {
+                                    let handshake = acceptor.accept(socket);
+                                    let handshake = match connection_state.headers_timeout() {
+                                        Some(timeout) => tokio::time::timeout(timeout, handshake).await
+                                            .unwrap_or_else(|_| Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "TLS handshake timeout"))),
+                                        None => handshake.await,
+                                    };
+                                    match handshake {
+                                        // ALPN picks the protocol, http2 servers only speak HTTP/1.1 with allowHTTP1
+                                        Ok(socket) => {
+                                            let h2 = socket.get_ref().1.alpn_protocol() == Some(&b"h2"[..]);
+                                            let settings = http2.filter(|http2| h2 || !http2.allow_http1).map(|http2| http2.settings);
+                                            serve_protocol(socket, settings, remote, connection_state.clone(), handle).await
+                                        }
+                                        Err(e) => connection_state.emit_tls_client_error(e.to_string(), remote_address),
+                                    }
+                                }
+                            }
+                            connection_state.connection_closed(id);
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to accept connection: {}", e);
+                    }
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    // Stops accepting connections, 'close' follows once the open ones have ended
+    pub fn close(&mut self) -> Result<(), String> {
+        if !self.is_listening() {
+            return Err("Server is not running".to_string());
+        }
+        if let Some(accept_task) = self.accept_task.take() {
+            accept_task.abort();
+        }
+        self.local_address = None;
+        self.state.begin_close();
+        Ok(())
+    }
+}
+
+// Serves a connection with HTTP/2 when it has HTTP/2 settings, with HTTP/1.x otherwise
+async fn serve_protocol<S: AsyncRead + AsyncWrite + Unpin + 'static>(
+    socket: S,
+    http2: Option<Http2Settings>,
+    remote_address: Option<SocketAddr>,
+    server: Rc<ServerState>,
+    handle: Rc<ConnectionHandle>
+) {
+    match http2 {
+        Some(settings) => serve_http2_connection(socket, remote_address, server, handle, settings).await,
+        None => serve_connection(socket, remote_address, server, handle).await,
+    }
+}
+
+thread_local! {
+    // Listening servers, closed gracefully when the process receives SIGTERM
+    static SHUTDOWN_SERVERS: std::cell::RefCell<Option<Vec<Weak<ServerState>>>> = const { std::cell::RefCell::new(None) };
+}
+
+// The signal handler is installed with the first server, so scripts without one keep
+// the default SIGTERM behaviour
+fn register_for_shutdown(state: &Rc<ServerState>) {
+    let first = SHUTDOWN_SERVERS.with(|servers| {
+        let mut servers = servers.borrow_mut();
+        let first = servers.is_none();
+        let servers = servers.get_or_insert_with(Vec::new);
+        servers.retain(|server| server.strong_count() > 0);
+        if !servers.iter().any(|server| server.as_ptr() == Rc::as_ptr(state)) {
+            servers.push(Rc::downgrade(state));
+        }
+        first
+    });
+
+    if first {
+        tokio::task::spawn_local(shutdown_on_sigterm());
+    }
+}
+
+// Stops accepting, closes idle keep-alive connections and tells busy ones to close after
+// their response, waits for them up to each server's shutdownTimeout, then exits
+async fn shutdown_on_sigterm() {
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            eprintln!("Failed to install SIGTERM handler: {}", e);
+            return;
+        }
+    };
+    sigterm.recv().await;
+
+    let servers: Vec<Rc<ServerState>> = SHUTDOWN_SERVERS.with(|servers| {
+        servers.borrow().iter().flatten().filter_map(Weak::upgrade).collect()
+    });
+
+    let now = tokio::time::Instant::now();
+    let mut draining = Vec::new();
+    for server in &servers {
+        server.begin_close();
+        let timeout = Duration::from_millis(server.shutdown_timeout.get());
+        draining.push(server.drain(now + timeout));
+    }
+    for drained in draining {
+        drained.await;
+    }
+
+    std::process::exit(0);
+}
+
+// V8 Callbacks
+fn get_server<'a>(scope: &mut v8::HandleScope, server_obj: v8::Local<v8::Object>) -> &'a mut Server {
+    let internal_field = server_obj.get_internal_field(scope, 0).unwrap();
+    let external_server = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_server.value() as *mut Server) }
+}
+
+pub fn create_server_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if let Some(server_obj) = create_server(scope, &args, false, false) {
+        rv.set(server_obj.into());
+    }
+}
+
+// The `key` and `cert` of an https server, or of one of its SNI contexts
+fn server_tls_option(scope: &mut v8::HandleScope, options: v8::Local<v8::Object>, name: &str) -> Result<Vec<u8>, String> {
+    let key = v8::String::new(scope, name).unwrap();
+    match options.get(scope, key.into()).filter(|value| !value.is_null_or_undefined()) {
+        Some(value) => Ok(pem_bundle(scope, value)),
+        None => Err(format!("The \"options.{}\" property is required", name)),
+    }
+}
+
+fn server_tls(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, alpn: &[&[u8]]) -> Result<ServerTls, String> {
+    let options = options.ok_or_else(|| "The \"options\" argument with key and cert is required".to_string())?;
+    let key = server_tls_option(scope, options, "key")?;
+    let cert = server_tls_option(scope, options, "cert")?;
+    ServerTls::new(&key, &cert, alpn)
+}
+
+// createServer([options], [handler]). Only maxHeaderSize is read from the options, for
+// https servers `key` and `cert` and for http2 servers `settings` and `allowHTTP1`.
+// Throws and returns None when they are invalid
+pub fn create_server<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments,
+    secure: bool,
+    http2: bool
+) -> Option<v8::Local<'s, v8::Object>> {
+    // Retrieve pointer to Rust Http Struct
+    let js_http_obj = args.this();
+    let internal_field = js_http_obj.get_internal_field(scope, 0).unwrap();
+    let external_http = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    let http_ptr = unsafe { &*(external_http.value() as *mut Http) };
+
+    let (options, handler) = match v8::Local::<v8::Object>::try_from(args.get(0)) {
+        Ok(options) if !args.get(0).is_function() => (Some(options), args.get(1)),
+        _ => (None, args.get(0)),
+    };
+    // allowHTTP1 is negotiated through ALPN, so only secure servers can offer both
+    let http2 = match http2 {
+        true => Some(Http2Options::from_js(scope, options, secure)),
+        false => None,
+    };
+    let alpn: &[&[u8]] = match http2 {
+        Some(Http2Options { allow_http1: true, .. }) => &[b"h2", b"http/1.1"],
+        Some(_) => &[b"h2"],
+        None => &[b"http/1.1"],
+    };
+    let tls = match secure {
+        true => match server_tls(scope, options, alpn) {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                throw_error(scope, &e);
+                return None;
+            }
+        },
+        false => None,
+    };
+    let max_header_size = options
+        .and_then(|options| {
+            let key = v8::String::new(scope, "maxHeaderSize").unwrap();
+            options.get(scope, key.into())
+        })
+        .filter(|value| value.is_number())
+        .and_then(|value| value.integer_value(scope))
+        .map(|size| size.max(0) as usize)
+        .unwrap_or(MAX_HEADER_SIZE);
+
+    let server = Box::new(Server::new(http_ptr.tx.clone(), http_ptr.permissions.clone(), max_header_size, tls, http2));
+
+    // The handler passed to createServer is a 'request' listener
+    if let Ok(handler) = v8::Local::<v8::Function>::try_from(handler) {
+        let handler = v8::Global::new(scope, handler);
+        server.state.event_emitter.borrow_mut().on("request".to_string(), handler);
+    }
+
+    let object_template = v8::ObjectTemplate::new(scope);
+    object_template.set_internal_field_count(1);
+    let server_obj = object_template.new_instance(scope).unwrap();
+    attach_event_emitter(scope, server_obj, &server.state.event_emitter);
+
+    set_function(scope, server_obj, "listen", server_listen_callback);
+    set_function(scope, server_obj, "close", server_close_callback);
+    set_function(scope, server_obj, "address", server_address_callback);
+    set_function(scope, server_obj, "setTimeout", server_set_timeout_callback);
+    set_function(scope, server_obj, "closeIdleConnections", server_close_idle_connections_callback);
+    set_function(scope, server_obj, "closeAllConnections", server_close_all_connections_callback);
+    if secure {
+        set_function(scope, server_obj, "addContext", server_add_context_callback);
+    }
+
+    let listening_key = v8::String::new(scope, "listening").unwrap();
+    server_obj.set_accessor(scope, listening_key.into(), server_listening_getter);
+
+    for name in ["headersTimeout", "requestTimeout", "keepAliveTimeout", "timeout", "maxConnections", "maxHeaderSize", "shutdownTimeout"] {
+        let key = v8::String::new(scope, name).unwrap();
+        server_obj.set_accessor_with_setter(scope, key.into(), server_limit_getter, server_limit_setter);
+    }
+
+    // Store the Rust Server within the server object
+    let external_server = v8::External::new(scope, Box::into_raw(server) as *const _ as *mut c_void);
+    server_obj.set_internal_field(0, external_server.into());
+
+    Some(server_obj)
+}
+
+// addContext(hostname, { key, cert }), the certificate for clients asking for `hostname`
+// through SNI. A leading "*." matches one label
+fn server_add_context_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let server = get_server(scope, args.this());
+    let hostname = args.get(0).to_rust_string_lossy(scope);
+    let Ok(context) = v8::Local::<v8::Object>::try_from(args.get(1)) else {
+        throw_error(scope, "The \"context\" argument must be an object with key and cert");
+        return;
+    };
+
+    let key = server_tls_option(scope, context, "key")
+        .and_then(|key| Ok((key, server_tls_option(scope, context, "cert")?)))
+        .and_then(|(key, cert)| certified_key(&key, &cert));
+    match (key, &server.tls) {
+        (Ok(key), Some(tls)) => tls.resolver.add_context(&hostname, key),
+        (Err(e), _) => throw_error(scope, &e),
+        (Ok(_), None) => {}
+    }
+}
+
+// listen([port], [host], [callback]), port 0 picks a free port
+fn server_listen_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let js_server_obj = args.this();
+    let server = get_server(scope, js_server_obj);
+
+    // Parse arguements (with defaults)
+    let port = if args.get(0).is_number() {
+        args.get(0).integer_value(scope).unwrap_or(0)
+    } else {
+        8000
+    };
+    if !(0..=65535).contains(&port) {
+        throw_error(scope, &format!("Port should be >= 0 and < 65536. Received {}", port));
+        return;
+    }
+
+    let host = if args.get(1).is_string() {
+        args.get(1).to_rust_string_lossy(scope)
+    } else {
+        "127.0.0.1".to_string()
+    };
+
+    // The callback is a 'listening' listener
+    let callback = (0..args.length())
+        .find_map(|i| v8::Local::<v8::Function>::try_from(args.get(i)).ok());
+    if let Some(callback) = callback {
+        let callback = v8::Global::new(scope, callback);
+        server.state.event_emitter.borrow_mut().once("listening".to_string(), callback);
+    }
+
+    if let Err(e) = server.listen(host, port as u16) {
+        throw_error(scope, &e.to_string());
+        return;
+    }
+
+    rv.set(js_server_obj.into());
+}
+
+fn server_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let server = get_server(scope, args.this());
+    let callback = v8::Local::<v8::Function>::try_from(args.get(0)).ok()
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match server.close() {
+        Ok(()) => {
+            if let Some(callback) = callback {
+                server.state.event_emitter.borrow_mut().once("close".to_string(), callback);
+            }
+        }
+        // Like Node the error only goes to the callback
+        Err(error_message) => {
+            if let Some(callback) = callback {
+                let op = StreamEvent::Callback{ callback, error_message: Some(error_message) };
+                let _ = server.state.tx.send(Operations::Stream(op));
+            }
+        }
+    }
+
+    rv.set(args.this().into());
+}
+
+// Returns { address, family, port }, or null when the server is not listening
+fn server_address_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let server = get_server(scope, args.this());
+    let Some(local_address) = server.address() else {
+        rv.set(v8::null(scope).into());
+        return;
+    };
+
+    let address_obj = v8::Object::new(scope);
+    let family = if local_address.is_ipv4() { "IPv4" } else { "IPv6" };
+
+    let address_key = v8::String::new(scope, "address").unwrap();
+    let address_value = v8::String::new(scope, &local_address.ip().to_string()).unwrap();
+    address_obj.set(scope, address_key.into(), address_value.into());
+
+    let family_key = v8::String::new(scope, "family").unwrap();
+    let family_value = v8::String::new(scope, family).unwrap();
+    address_obj.set(scope, family_key.into(), family_value.into());
+
+    let port_key = v8::String::new(scope, "port").unwrap();
+    let port_value = v8::Integer::new(scope, local_address.port() as i32);
+    address_obj.set(scope, port_key.into(), port_value.into());
+
+    rv.set(address_obj.into());
+}
+
+fn server_listening_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let server = get_server(scope, args.this());
+    rv.set(v8::Boolean::new(scope, server.is_listening()).into());
+}
+
+// Closes connections waiting for their next request, busy ones are left alone
+fn server_close_idle_connections_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    get_server(scope, args.this()).state.close_idle_connections();
+}
+
+// Closes every connection, requests still in progress see 'aborted'
+fn server_close_all_connections_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    get_server(scope, args.this()).state.close_all_connections();
+}
+
+// setTimeout(ms, [callback]) sets the socket inactivity timeout, the callback is a 'timeout' listener
+fn server_set_timeout_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let server = get_server(scope, args.this());
+    let ms = args.get(0).integer_value(scope).unwrap_or(0).max(0) as u64;
+    server.state.socket_timeout.set(ms);
+
+    if let Ok(callback) = v8::Local::<v8::Function>::try_from(args.get(1)) {
+        let callback = v8::Global::new(scope, callback);
+        server.state.event_emitter.borrow_mut().on("timeout".to_string(), callback);
+    }
+
+    rv.set(args.this().into());
+}
+
+// The timeouts and limits share one getter and setter, picked by property name
+fn server_limit_getter(
+    scope: &mut v8::HandleScope,
+    name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let state = &get_server(scope, args.this()).state;
+    let value = match name.to_rust_string_lossy(scope).as_str() {
+        "headersTimeout" => state.headers_timeout.get() as f64,
+        "requestTimeout" => state.request_timeout.get() as f64,
+        "keepAliveTimeout" => state.keep_alive_timeout.get() as f64,
+        "timeout" => state.socket_timeout.get() as f64,
+        "maxHeaderSize" => state.max_header_size as f64,
+        "shutdownTimeout" => state.shutdown_timeout.get() as f64,
+        _ => match state.max_connections.get() {
+            0 => f64::INFINITY,
+            max_connections => max_connections as f64,
+        },
+    };
+    rv.set(v8::Number::new(scope, value).into());
+}
+
+fn server_limit_setter(
+    scope: &mut v8::HandleScope,
+    name: v8::Local<v8::Name>,
+    value: v8::Local<v8::Value>,
+    args: v8::PropertyCallbackArguments,
+) {
+    let state = &get_server(scope, args.this()).state;
+    let number = value.number_value(scope).unwrap_or(f64::NAN);
+    // Negative or invalid values turn a timeout off
+    let ms = if number.is_finite() && number > 0.0 { number as u64 } else { 0 };
+
+    match name.to_rust_string_lossy(scope).as_str() {
+        "headersTimeout" => state.headers_timeout.set(ms),
+        "requestTimeout" => state.request_timeout.set(ms),
+        "keepAliveTimeout" => state.keep_alive_timeout.set(ms),
+        "timeout" => state.socket_timeout.set(ms),
+        "shutdownTimeout" => state.shutdown_timeout.set(ms),
+        // Fixed when the server is created
+        "maxHeaderSize" => throw_error(scope, "maxHeaderSize can only be set through createServer options"),
+        // Infinity or anything that is not a positive number removes the limit
+        _ => state.max_connections.set(if number.is_finite() && number >= 1.0 { number as usize } else { 0 }),
+    }
+}
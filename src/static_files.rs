@@ -0,0 +1,567 @@
+use rusty_v8 as v8;
+use tokio::sync::mpsc::UnboundedSender;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+use std::ffi::c_void;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::headers::Headers;
+use crate::helper::{call_method, retrieve_tx, throw_error, throw_type_error};
+use crate::http::Http;
+use crate::interface::{HttpOperation, Operations};
+use crate::permissions::Permissions;
+use crate::request::get_request;
+use crate::response::{emit_response_error, get_response, status_reason, Response};
+use crate::stream::{create_read_stream_object, FileReadStream, ReadStreamOptions, READ_HIGH_WATER_MARK};
+
+// Characters escaped when a decoded path segment goes back into a URL
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'/').add(b'<').add(b'>').add(b'?')
+    .add(b'\\').add(b'^').add(b'`').add(b'{').add(b'|').add(b'}');
+
+// What happens to paths with a segment starting with a dot, like serve-static
+#[derive(Clone, Copy, PartialEq)]
+enum Dotfiles {
+    Allow,
+    Deny,
+    Ignore,
+}
+
+// The handler returned by http.serveStatic(), shared by every request it serves
+pub struct StaticFiles {
+    // Canonical, so resolved paths can be checked against it
+    root: PathBuf,
+    index: Vec<String>,
+    dotfiles: Dotfiles,
+    // Seconds for Cache-Control
+    max_age: u64,
+    etag: bool,
+    last_modified: bool,
+    // Redirect directories requested without a trailing slash
+    redirect: bool,
+    permissions: Arc<Permissions>,
+    tx: UnboundedSender<Operations>,
+    // Lookups finish on the http channel
+    http_tx: UnboundedSender<Operations>,
+}
+
+enum Lookup {
+    File(PathBuf, Metadata),
+    Redirect(String),
+    Status(u16),
+}
+
+// A request waiting for the file system, answered once the lookup is done
+pub struct StaticLookup {
+    static_files: Rc<StaticFiles>,
+    lookup: Lookup,
+    request: v8::Global<v8::Object>,
+    response: v8::Global<v8::Object>,
+    next: Option<v8::Global<v8::Function>>,
+}
+
+impl StaticFiles {
+    // Maps a request URL to a file below the root. `..` segments are refused outright and
+    // symlinks may not lead outside the root either
+    async fn lookup(&self, url: &str) -> Lookup {
+        let (path, query) = match url.find(['?', '#']) {
+            Some(index) => url.split_at(index),
+            None => (url, ""),
+        };
+        if !path.starts_with('/') {
+            return Lookup::Status(400);
+        }
+        let Ok(decoded) = percent_decode_str(path).decode_utf8() else {
+            return Lookup::Status(400);
+        };
+        if decoded.contains('\0') {
+            return Lookup::Status(400);
+        }
+
+        let segments: Vec<&str> = decoded.split('/').filter(|segment| !segment.is_empty() && *segment != ".").collect();
+        if segments.iter().any(|segment| *segment == ".." || segment.contains('\\')) {
+            return Lookup::Status(403);
+        }
+        if segments.iter().any(|segment| segment.starts_with('.')) {
+            if let Some(status_code) = self.dotfile_status() {
+                return Lookup::Status(status_code);
+            }
+        }
+
+        let mut file_path = self.root.clone();
+        file_path.extend(&segments);
+        let Ok(mut file_path) = tokio::fs::canonicalize(&file_path).await else {
+            return Lookup::Status(404);
+        };
+        if let Some(status_code) = self.refused(&file_path) {
+            return Lookup::Status(status_code);
+        }
+        let Ok(mut metadata) = tokio::fs::metadata(&file_path).await else {
+            return Lookup::Status(404);
+        };
+
+        if metadata.is_dir() {
+            if !path.ends_with('/') {
+                // Built from the normalized segments, so the Location can't name another host
+                let mut location = String::from("/");
+                for segment in &segments {
+                    location.extend(utf8_percent_encode(segment, PATH_SEGMENT));
+                    location.push('/');
+                }
+                location.push_str(query);
+                return match self.redirect {
+                    true => Lookup::Redirect(location),
+                    false => Lookup::Status(404),
+                };
+            }
+            let mut index = None;
+            for name in &self.index {
+                // The index may be a symlink too, its target is held to the same rules
+                let Ok(index_path) = tokio::fs::canonicalize(file_path.join(name)).await else {
+                    continue;
+                };
+                if let Some(status_code) = self.refused(&index_path) {
+                    return Lookup::Status(status_code);
+                }
+                if let Ok(index_metadata) = tokio::fs::metadata(&index_path).await {
+                    if index_metadata.is_file() {
+                        index = Some((index_path, index_metadata));
+                        break;
+                    }
+                }
+            }
+            match index {
+                Some(index) => (file_path, metadata) = index,
+                None => return Lookup::Status(404),
+            }
+        }
+        if !metadata.is_file() {
+            return Lookup::Status(404);
+        }
+        if self.permissions.check_read(&file_path).is_err() {
+            return Lookup::Status(403);
+        }
+        Lookup::File(file_path, metadata)
+    }
+
+    // The status for a canonical path that leaves the root, or that reaches a dotfile
+    // through a symlink
+    fn refused(&self, path: &Path) -> Option<u16> {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return Some(403);
+        };
+        if relative.components().any(|component| component.as_os_str().to_string_lossy().starts_with('.')) {
+            return self.dotfile_status();
+        }
+        None
+    }
+
+    fn dotfile_status(&self) -> Option<u16> {
+        match self.dotfiles {
+            Dotfiles::Allow => None,
+            Dotfiles::Deny => Some(403),
+            Dotfiles::Ignore => Some(404),
+        }
+    }
+}
+
+// Weak, file contents are not hashed. Size and modification time change with them
+fn file_etag(metadata: &Metadata) -> String {
+    let modified = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_millis());
+    format!("W/\"{:x}-{:x}\"", metadata.len(), modified)
+}
+
+fn etag_matches(list: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    list.split(',')
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+// HTTP dates have whole seconds
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+fn not_modified_since(value: &str, modified: Option<SystemTime>) -> bool {
+    match (httpdate::parse_http_date(value), modified) {
+        (Ok(since), Some(modified)) => unix_seconds(modified) <= unix_seconds(since),
+        _ => false,
+    }
+}
+
+// If-None-Match wins over If-Modified-Since when both are sent
+fn is_not_modified(headers: &Headers, etag: Option<&str>, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get_joined("If-None-Match") {
+        return etag.is_some_and(|etag| etag_matches(&if_none_match, etag));
+    }
+    match headers.get("If-Modified-Since") {
+        Some(since) => not_modified_since(since, modified),
+        None => false,
+    }
+}
+
+// The byte range of a `Range: bytes=...` header, both ends inclusive. None when the header
+// is ignored and the whole file sent, which includes multiple ranges. Err when no byte of
+// the file is in range
+fn parse_range(header: &str, size: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // bytes=-500 is the last 500 bytes
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return Some(Err(()));
+            }
+            (size.saturating_sub(suffix), size.saturating_sub(1))
+        }
+        (start, "") => (start.parse().ok()?, size.saturating_sub(1)),
+        (start, end) => {
+            let (start, end): (u64, u64) = (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.min(size.saturating_sub(1)))
+        }
+    };
+    if size == 0 || start >= size {
+        return Some(Err(()));
+    }
+    Some(Ok((start, end)))
+}
+
+fn content_type(path: &Path) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let textual = mime.type_() == mime_guess::mime::TEXT
+        || matches!(mime.subtype().as_str(), "javascript" | "json" | "xml");
+    match textual {
+        true => format!("{}; charset=utf-8", mime.essence_str()),
+        false => mime.essence_str().to_string(),
+    }
+}
+
+// Answers with the reason phrase as a small text body
+fn end_with_status(response: &mut Response, status_code: u16) -> Result<(), String> {
+    response.set_status_code(status_code);
+    response.add_header("Content-Type".to_string(), "text/plain; charset=utf-8".to_string());
+    if status_code == 405 {
+        response.add_header("Allow".to_string(), "GET, HEAD".to_string());
+    }
+    response.end(Some(status_reason(status_code).as_bytes().to_vec()), None)
+}
+
+fn get_option<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    options: Option<v8::Local<v8::Object>>,
+    name: &str
+) -> Option<v8::Local<'s, v8::Value>> {
+    let key = v8::String::new(scope, name).unwrap();
+    options?.get(scope, key.into()).filter(|value| !value.is_undefined())
+}
+
+fn parse_options(
+    scope: &mut v8::HandleScope,
+    root: PathBuf,
+    options: Option<v8::Local<v8::Object>>,
+    http: &Http
+) -> Result<StaticFiles, String> {
+    let index = match get_option(scope, options, "index") {
+        None => vec!["index.html".to_string()],
+        Some(index) if index.is_false() => Vec::new(),
+        Some(index) => match v8::Local::<v8::Array>::try_from(index) {
+            Ok(names) => (0..names.length())
+                .map(|i| names.get_index(scope, i).unwrap().to_rust_string_lossy(scope))
+                .collect(),
+            Err(_) => vec![index.to_rust_string_lossy(scope)],
+        },
+    };
+    let dotfiles = match get_option(scope, options, "dotfiles").map(|value| value.to_rust_string_lossy(scope)) {
+        None => Dotfiles::Ignore,
+        Some(value) => match value.as_str() {
+            "allow" => Dotfiles::Allow,
+            "deny" => Dotfiles::Deny,
+            "ignore" => Dotfiles::Ignore,
+            _ => return Err(format!("The \"options.dotfiles\" must be 'allow', 'deny' or 'ignore'. Received '{}'", value)),
+        },
+    };
+    let max_age = match get_option(scope, options, "maxAge").map(|value| value.number_value(scope).unwrap_or(f64::NAN)) {
+        None => 0,
+        Some(max_age) if max_age >= 0.0 => (max_age / 1000.0) as u64,
+        Some(max_age) => return Err(format!("The \"options.maxAge\" must be a non-negative number. Received {}", max_age)),
+    };
+    let flag = |scope: &mut v8::HandleScope, name: &str| get_option(scope, options, name)
+        .is_none_or(|value| value.boolean_value(scope));
+
+    Ok(StaticFiles {
+        root,
+        index,
+        dotfiles,
+        max_age,
+        etag: flag(scope, "etag"),
+        last_modified: flag(scope, "lastModified"),
+        redirect: flag(scope, "redirect"),
+        permissions: http.permissions.clone(),
+        tx: unsafe { &*retrieve_tx(scope, "channel").unwrap() }.clone(),
+        http_tx: http.tx.clone(),
+    })
+}
+
+// http.serveStatic(root, [options]), returns a (req, res, [next]) handler
+fn serve_static_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let external_http = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let http = unsafe { &*(external_http.value() as *const Http) };
+
+    if !args.get(0).is_string() {
+        throw_type_error(scope, "The \"root\" argument must be of type string");
+        return;
+    }
+    let root = PathBuf::from(args.get(0).to_rust_string_lossy(scope));
+    if let Err(e) = http.permissions.check_read(&root) {
+        throw_error(scope, &e.to_string());
+        return;
+    }
+    let root = match root.canonicalize() {
+        Ok(root) if root.is_dir() => root,
+        Ok(_) => {
+            throw_error(scope, &format!("ENOTDIR: not a directory, serveStatic '{}'", root.display()));
+            return;
+        }
+        Err(e) => {
+            throw_error(scope, &format!("{}, serveStatic '{}'", e, root.display()));
+            return;
+        }
+    };
+
+    let options = v8::Local::<v8::Object>::try_from(args.get(1)).ok();
+    let static_files = match parse_options(scope, root, options, http) {
+        Ok(static_files) => static_files,
+        Err(e) => {
+            throw_type_error(scope, &e);
+            return;
+        }
+    };
+
+    let external_static = v8::External::new(scope, Box::into_raw(Box::new(Rc::new(static_files))) as *mut c_void);
+    let handler = v8::Function::builder(static_handler_callback)
+        .data(external_static.into())
+        .build(scope)
+        .unwrap();
+    rv.set(handler.into());
+}
+
+// handler(req, res, [next]). Requests it can't answer with a file go to `next` when given,
+// 404 and 405 are sent otherwise. The file is looked up off the JS thread
+fn static_handler_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let external_static = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let static_files = unsafe { &*(external_static.value() as *const Rc<StaticFiles>) }.clone();
+
+    let objects = (v8::Local::<v8::Object>::try_from(args.get(0)), v8::Local::<v8::Object>::try_from(args.get(1)));
+    let (Ok(request_obj), Ok(response_obj)) = objects else {
+        throw_type_error(scope, "The handler must be called with a request and a response");
+        return;
+    };
+    if request_obj.internal_field_count() == 0 || response_obj.internal_field_count() == 0 {
+        throw_type_error(scope, "The handler must be called with a request and a response");
+        return;
+    }
+    let request = get_request(scope, request_obj);
+    let response = get_response(scope, response_obj);
+    if response.headers_sent() {
+        throw_error(scope, "Cannot serve a file after the headers were sent");
+        return;
+    }
+
+    let readable = request.method.eq_ignore_ascii_case("GET") || request.method.eq_ignore_ascii_case("HEAD");
+    let url = request.url.clone();
+    let mut pending = StaticLookup {
+        static_files,
+        lookup: Lookup::Status(405),
+        request: v8::Global::new(scope, request_obj),
+        response: v8::Global::new(scope, response_obj),
+        next: v8::Local::<v8::Function>::try_from(args.get(2)).ok().map(|next| v8::Global::new(scope, next)),
+    };
+    if !readable {
+        handle_static_lookup(scope, pending);
+        return;
+    }
+
+    tokio::task::spawn_local(async move {
+        pending.lookup = pending.static_files.lookup(&url).await;
+        let http_tx = pending.static_files.http_tx.clone();
+        let _ = http_tx.send(Operations::Http(HttpOperation::StaticFile(pending)));
+    });
+}
+
+// Answers a request once its file was looked up
+pub fn handle_static_lookup(scope: &mut v8::HandleScope, pending: StaticLookup) {
+    let StaticLookup { static_files, lookup, request, response, next } = pending;
+    let request_obj = v8::Local::new(scope, request);
+    let response_obj = v8::Local::new(scope, response);
+    let request = get_request(scope, request_obj);
+    let response = get_response(scope, response_obj);
+    // The application answered the request in the meantime
+    if response.headers_sent() {
+        return;
+    }
+    let next = next.map(|next| v8::Local::new(scope, next));
+
+    let head = request.method.eq_ignore_ascii_case("HEAD");
+    let (path, metadata) = match lookup {
+        Lookup::File(path, metadata) => (path, metadata),
+        Lookup::Status(404 | 405) if next.is_some() => {
+            let undefined = v8::undefined(scope).into();
+            next.unwrap().call(scope, undefined, &[]);
+            return;
+        }
+        Lookup::Status(status_code) => {
+            if let Err(e) = end_with_status(response, status_code) {
+                emit_response_error(scope, response, &e);
+            }
+            return;
+        }
+        Lookup::Redirect(location) => {
+            response.add_header("Location".to_string(), location);
+            if let Err(e) = end_with_status(response, 301) {
+                emit_response_error(scope, response, &e);
+            }
+            return;
+        }
+    };
+
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = static_files.etag.then(|| file_etag(&metadata));
+    if let Some(etag) = &etag {
+        response.add_header("ETag".to_string(), etag.clone());
+    }
+    if let Some(modified) = modified.filter(|_| static_files.last_modified) {
+        response.add_header("Last-Modified".to_string(), httpdate::fmt_http_date(modified));
+    }
+    if !response.has_header("Cache-Control") {
+        response.add_header("Cache-Control".to_string(), format!("public, max-age={}", static_files.max_age));
+    }
+    response.add_header("Accept-Ranges".to_string(), "bytes".to_string());
+
+    let headers = &request.headers;
+    let validators_modified = modified.filter(|_| static_files.last_modified);
+    if is_not_modified(headers, etag.as_deref(), validators_modified) {
+        response.set_status_code(304);
+        if let Err(e) = response.end(None, None) {
+            emit_response_error(scope, response, &e);
+        }
+        return;
+    }
+
+    if !response.has_header("Content-Type") {
+        response.add_header("Content-Type".to_string(), content_type(&path));
+    }
+
+    // A Range only applies while If-Range still names this version of the file
+    let range_applies = match headers.get("If-Range") {
+        None => true,
+        Some(if_range) if if_range.trim().starts_with('"') || if_range.trim().starts_with("W/") => {
+            etag.as_deref().is_some_and(|etag| etag_matches(if_range, etag))
+        }
+        Some(if_range) => not_modified_since(if_range, validators_modified),
+    };
+    let range = headers.get("Range")
+        .filter(|_| range_applies)
+        .and_then(|range| parse_range(range, size));
+    let (start, end) = match range {
+        None => {
+            response.set_status_code(200);
+            (0, size.saturating_sub(1))
+        }
+        Some(Ok((start, end))) => {
+            response.set_status_code(206);
+            response.add_header("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end, size));
+            (start, end)
+        }
+        Some(Err(())) => {
+            response.add_header("Content-Range".to_string(), format!("bytes */{}", size));
+            if let Err(e) = end_with_status(response, 416) {
+                emit_response_error(scope, response, &e);
+            }
+            return;
+        }
+    };
+    let length = if size == 0 { 0 } else { end - start + 1 };
+    response.add_header("Content-Length".to_string(), length.to_string());
+
+    if head || length == 0 {
+        if let Err(e) = response.end(None, None) {
+            emit_response_error(scope, response, &e);
+        }
+        return;
+    }
+
+    let options = ReadStreamOptions {
+        start,
+        end: Some(end),
+        high_water_mark: READ_HIGH_WATER_MARK,
+        encoding: None,
+    };
+    let stream = FileReadStream::open(path, options, static_files.permissions.clone(), static_files.tx.clone());
+    let stream_obj = create_read_stream_object(scope, Box::new(stream));
+
+    // The file was there a moment ago, if reading it fails after all the response ends short
+    let on_error = v8::Function::builder(static_stream_error_callback)
+        .data(response_obj.into())
+        .build(scope)
+        .unwrap();
+    let error_key = v8::String::new(scope, "error").unwrap();
+    call_method(scope, stream_obj, "once", &[error_key.into(), on_error.into()]);
+    call_method(scope, stream_obj, "pipe", &[response_obj.into()]);
+}
+
+fn static_stream_error_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let response_obj = args.data().unwrap().to_object(scope).unwrap();
+    let response = get_response(scope, response_obj);
+    let result = match response.headers_sent() {
+        true => response.end(None, None),
+        false => {
+            for name in ["Content-Length", "Content-Range", "ETag", "Last-Modified"] {
+                response.remove_header(name);
+            }
+            end_with_status(response, 500)
+        }
+    };
+    if let Err(error_message) = result {
+        eprintln!("Error: {}", error_message);
+    }
+}
+
+pub fn initialize_static<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    http_obj: v8::Local<'s, v8::Object>,
+    external_http: v8::Local<'s, v8::External>
+) {
+    let serve_static_fn = v8::Function::builder(serve_static_callback)
+        .data(external_http.into())
+        .build(scope)
+        .unwrap();
+    let serve_static_key = v8::String::new(scope, "serveStatic").unwrap();
+    http_obj.set(scope, serve_static_key.into(), serve_static_fn.into());
+}
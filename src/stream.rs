@@ -0,0 +1,587 @@
+use rusty_v8 as v8;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::emitter::{attach_event_emitter, emit_event, EventEmitter};
+use crate::fs::{check_open_permissions, get_fs_instance, open_options_from_flags};
+use crate::permissions::Permissions;
+use crate::helper::{bytes_to_uint8array, call_method, retrieve_tx, set_function, value_to_bytes};
+use crate::interface::{Operations, StreamEvent};
+
+// Node's defaults for fs streams, http responses share the write one
+pub const READ_HIGH_WATER_MARK: usize = 64 * 1024;
+pub const WRITE_HIGH_WATER_MARK: usize = 16 * 1024;
+
+// A chunk emitted by a 'data' event, text when the stream has an encoding
+pub enum Chunk {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl Chunk {
+    pub fn into_value<'s>(self, scope: &mut v8::HandleScope<'s>) -> v8::Local<'s, v8::Value> {
+        match self {
+            Chunk::Bytes(bytes) => bytes_to_uint8array(scope, bytes).into(),
+            Chunk::Text(text) => v8::String::new(scope, &text).unwrap().into(),
+        }
+    }
+}
+
+// Decodes UTF-8 across chunk boundaries, keeping an incomplete trailing character for the next chunk
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+
+        let complete_len = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+
+        let rest = self.pending.split_off(complete_len);
+        let text = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending = rest;
+        text
+    }
+
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending.clear();
+        text
+    }
+}
+
+pub struct ReadStreamOptions {
+    pub start: u64,
+    pub end: Option<u64>,
+    pub high_water_mark: usize,
+    pub encoding: Option<String>,
+}
+
+pub struct FileReadStream {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    paused: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl FileReadStream {
+    pub fn open(path: PathBuf, options: ReadStreamOptions, permissions: Arc<Permissions>, tx: UnboundedSender<Operations>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let (paused, paused_rx) = watch::channel(false);
+
+        let emitter = event_emitter.clone();
+        let task = tokio::task::spawn_local(async move {
+            let result = match permissions.check_read(&path) {
+                Ok(()) => read_file_chunks(path, options, emitter.clone(), paused_rx, tx.clone()).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                let op = StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() };
+                let _ = tx.send(Operations::Stream(op));
+            }
+            let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+        });
+
+        Self {
+            event_emitter,
+            paused,
+            task,
+        }
+    }
+
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn destroy(&self) {
+        self.task.abort();
+    }
+}
+
+async fn read_file_chunks(
+    path: PathBuf,
+    options: ReadStreamOptions,
+    emitter: Rc<RefCell<EventEmitter>>,
+    mut paused: watch::Receiver<bool>,
+    tx: UnboundedSender<Operations>
+) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::open(&path).await?;
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "open" }));
+
+    if options.start > 0 {
+        file.seek(SeekFrom::Start(options.start)).await?;
+    }
+
+    // `end` is inclusive, like in Node
+    let mut remaining = match options.end {
+        Some(end) => (end + 1).saturating_sub(options.start),
+        None => u64::MAX,
+    };
+    let mut decoder = options.encoding.as_ref().map(|_| Utf8Decoder::default());
+
+    while remaining > 0 {
+        while *paused.borrow() {
+            if paused.changed().await.is_err() {
+                return Ok(());
+            }
+        }
+
+        let mut buffer = vec![0u8; remaining.min(options.high_water_mark as u64) as usize];
+        let n = file.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+
+        buffer.truncate(n);
+        remaining -= n as u64;
+
+        let chunk = match decoder.as_mut() {
+            Some(decoder) => Chunk::Text(decoder.decode(&buffer)),
+            None => Chunk::Bytes(buffer),
+        };
+        let _ = tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk }));
+    }
+
+    if let Some(text) = decoder.as_mut().map(|decoder| decoder.finish()).filter(|text| !text.is_empty()) {
+        let _ = tx.send(Operations::Stream(StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Text(text) }));
+    }
+
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter, event: "end" }));
+    Ok(())
+}
+
+enum WriteCommand {
+    Write(Vec<u8>, Option<v8::Global<v8::Function>>),
+    End(Option<v8::Global<v8::Function>>),
+}
+
+pub struct FileWriteStream {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    commands: UnboundedSender<WriteCommand>,
+    // Bytes handed to write() that have not reached the file yet
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    high_water_mark: usize,
+    ended: bool,
+}
+
+impl FileWriteStream {
+    pub fn open(path: PathBuf, flags: String, permissions: Arc<Permissions>, tx: UnboundedSender<Operations>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let (commands, commands_rx) = tokio::sync::mpsc::unbounded_channel::<WriteCommand>();
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let need_drain = Arc::new(AtomicBool::new(false));
+
+        let emitter = event_emitter.clone();
+        let task_buffered = buffered.clone();
+        let task_need_drain = need_drain.clone();
+        tokio::task::spawn_local(async move {
+            let result = match check_open_permissions(&permissions, &path, &flags) {
+                Ok(()) => write_file_chunks(path, flags, emitter.clone(), commands_rx, task_buffered, task_need_drain, tx.clone()).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                let op = StreamEvent::Error{ emitter: emitter.clone(), error_message: e.to_string() };
+                let _ = tx.send(Operations::Stream(op));
+            }
+            let _ = tx.send(Operations::Stream(StreamEvent::Close{ emitter }));
+        });
+
+        Self {
+            event_emitter,
+            commands,
+            buffered,
+            need_drain,
+            high_water_mark: WRITE_HIGH_WATER_MARK,
+            ended: false,
+        }
+    }
+
+    // Returns false once the caller should wait for 'drain' before writing more
+    pub fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<bool, String> {
+        if self.ended {
+            return Err("write after end".to_string());
+        }
+
+        let buffered = self.buffered.fetch_add(data.len(), Ordering::SeqCst) + data.len();
+        let _ = self.commands.send(WriteCommand::Write(data, callback));
+
+        let ok = buffered < self.high_water_mark;
+        if !ok {
+            self.need_drain.store(true, Ordering::SeqCst);
+        }
+        Ok(ok)
+    }
+
+    pub fn end(&mut self, callback: Option<v8::Global<v8::Function>>) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let _ = self.commands.send(WriteCommand::End(callback));
+    }
+}
+
+async fn write_file_chunks(
+    path: PathBuf,
+    flags: String,
+    emitter: Rc<RefCell<EventEmitter>>,
+    mut commands: tokio::sync::mpsc::UnboundedReceiver<WriteCommand>,
+    buffered: Arc<AtomicUsize>,
+    need_drain: Arc<AtomicBool>,
+    tx: UnboundedSender<Operations>
+) -> std::io::Result<()> {
+    let Some(open_options) = open_options_from_flags(&flags) else {
+        let message = format!("Unknown file open flag: {}", flags);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+    };
+
+    let mut file = open_options.open(&path).await?;
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "open" }));
+
+    while let Some(command) = commands.recv().await {
+        match command {
+            WriteCommand::Write(data, callback) => {
+                let result = file.write_all(&data).await;
+                let remaining = buffered.fetch_sub(data.len(), Ordering::SeqCst) - data.len();
+
+                if let Some(callback) = callback {
+                    let error_message = result.as_ref().err().map(|e| e.to_string());
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+                }
+                result?;
+
+                if remaining == 0 && need_drain.swap(false, Ordering::SeqCst) {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "drain" }));
+                }
+            }
+
+            WriteCommand::End(callback) => {
+                file.flush().await?;
+                file.sync_all().await?;
+
+                let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "finish" }));
+                if let Some(callback) = callback {
+                    let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message: None }));
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Dispatch from the event loop
+pub fn handle_stream_event(scope: &mut v8::HandleScope, event: StreamEvent) {
+    match event {
+        StreamEvent::Data { emitter, chunk } => {
+            let chunk_value = chunk.into_value(scope);
+            emit_event(scope, &emitter, "data", &[chunk_value]);
+        }
+
+        StreamEvent::Emit { emitter, event } => {
+            emit_event(scope, &emitter, event, &[]);
+        }
+
+        StreamEvent::Error { emitter, error_message } => {
+            let error_value = v8::String::new(scope, &error_message).unwrap();
+            emit_event(scope, &emitter, "error", &[error_value.into()]);
+        }
+
+        StreamEvent::Close { emitter } => {
+            emit_event(scope, &emitter, "close", &[]);
+            emitter.borrow_mut().release();
+        }
+
+        StreamEvent::Callback { callback, error_message } => {
+            let undefined = v8::undefined(scope).into();
+            let error_value = match error_message {
+                Some(error_message) => v8::String::new(scope, &error_message).unwrap().into(),
+                None => v8::null(scope).into(),
+            };
+            let callback_fn = callback.open(scope);
+            callback_fn.call(scope, undefined, &[error_value]);
+        }
+    }
+}
+
+// Generic readable.pipe(destination), works with anything that has write/end/once
+pub fn stream_pipe_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let source = v8::Local::new(scope, args.this());
+    let Ok(destination) = v8::Local::<v8::Object>::try_from(args.get(0)) else {
+        eprintln!("Error: pipe() expects a writable destination");
+        return;
+    };
+    let destination = v8::Local::new(scope, destination);
+
+    let pair = v8::Array::new_with_elements(scope, &[source.into(), destination.into()]);
+    let on_data = v8::Function::builder(pipe_data_callback).data(pair.into()).build(scope).unwrap();
+    let on_end = v8::Function::builder(pipe_end_callback).data(destination.into()).build(scope).unwrap();
+
+    let data_key = v8::String::new(scope, "data").unwrap();
+    let end_key = v8::String::new(scope, "end").unwrap();
+    call_method(scope, source, "on", &[data_key.into(), on_data.into()]);
+    call_method(scope, source, "on", &[end_key.into(), on_end.into()]);
+
+    rv.set(destination.into());
+}
+
+fn pipe_data_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let pair = v8::Local::<v8::Array>::try_from(args.data().unwrap()).unwrap();
+    let source = pair.get_index(scope, 0).unwrap().to_object(scope).unwrap();
+    let destination = pair.get_index(scope, 1).unwrap().to_object(scope).unwrap();
+
+    // Stop reading until the destination has flushed its buffer
+    let accepted = call_method(scope, destination, "write", &[args.get(0)]);
+    if accepted.map(|value| value.is_false()).unwrap_or(false) {
+        call_method(scope, source, "pause", &[]);
+
+        let on_drain = v8::Function::builder(pipe_drain_callback).data(source.into()).build(scope).unwrap();
+        let drain_key = v8::String::new(scope, "drain").unwrap();
+        call_method(scope, destination, "once", &[drain_key.into(), on_drain.into()]);
+    }
+}
+
+fn pipe_drain_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let source = args.data().unwrap().to_object(scope).unwrap();
+    call_method(scope, source, "resume", &[]);
+}
+
+fn pipe_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let destination = args.data().unwrap().to_object(scope).unwrap();
+    call_method(scope, destination, "end", &[]);
+}
+
+// V8 Callbacks
+fn get_read_stream<'a>(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> &'a FileReadStream {
+    let internal_field = args.this().get_internal_field(scope, 0).unwrap();
+    let external_stream = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_stream.value() as *const FileReadStream) }
+}
+
+fn get_write_stream<'a>(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> &'a mut FileWriteStream {
+    let internal_field = args.this().get_internal_field(scope, 0).unwrap();
+    let external_stream = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_stream.value() as *mut FileWriteStream) }
+}
+
+fn get_number_option(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, name: &str) -> Option<f64> {
+    let options = options?;
+    let key = v8::String::new(scope, name).unwrap();
+    let value = options.get(scope, key.into())?;
+    if value.is_number() { value.number_value(scope) } else { None }
+}
+
+fn get_string_option(scope: &mut v8::HandleScope, options: Option<v8::Local<v8::Object>>, name: &str) -> Option<String> {
+    let options = options?;
+    let key = v8::String::new(scope, name).unwrap();
+    let value = options.get(scope, key.into())?;
+    if value.is_string() { Some(value.to_rust_string_lossy(scope)) } else { None }
+}
+
+fn read_stream_pause_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    get_read_stream(scope, &args).pause();
+    rv.set(args.this().into());
+}
+
+fn read_stream_resume_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    get_read_stream(scope, &args).resume();
+    rv.set(args.this().into());
+}
+
+fn read_stream_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_read_stream(scope, &args);
+    stream.destroy();
+
+    // The reading task no longer runs, so 'close' is emitted from here
+    emit_event(scope, &stream.event_emitter, "close", &[]);
+    stream.event_emitter.borrow_mut().release();
+    rv.set(args.this().into());
+}
+
+fn write_stream_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_write_stream(scope, &args);
+    let data = value_to_bytes(scope, args.get(0));
+
+    // write(chunk, [encoding], [callback])
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match stream.write(data, callback) {
+        Ok(ok) => rv.set(v8::Boolean::new(scope, ok).into()),
+        Err(error_message) => {
+            let error_value = v8::String::new(scope, &error_message).unwrap();
+            emit_event(scope, &stream.event_emitter, "error", &[error_value.into()]);
+            rv.set(v8::Boolean::new(scope, false).into());
+        }
+    }
+}
+
+fn write_stream_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_write_stream(scope, &args);
+
+    // end([chunk], [encoding], [callback])
+    let mut callback = None;
+    for i in 0..args.length() {
+        if let Ok(function) = v8::Local::<v8::Function>::try_from(args.get(i)) {
+            callback = Some(v8::Global::new(scope, function));
+            break;
+        }
+    }
+
+    let chunk = args.get(0);
+    if !chunk.is_function() && !chunk.is_null_or_undefined() {
+        let data = value_to_bytes(scope, chunk);
+        let _ = stream.write(data, None);
+    }
+
+    stream.end(callback);
+    rv.set(args.this().into());
+}
+
+pub fn create_read_stream_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    stream: Box<FileReadStream>
+) -> v8::Local<'s, v8::Object> {
+    let stream_template = v8::ObjectTemplate::new(scope);
+    stream_template.set_internal_field_count(1); // Store the Rust FileReadStream struct internally
+    let stream_obj = stream_template.new_instance(scope).unwrap();
+
+    attach_event_emitter(scope, stream_obj, &stream.event_emitter);
+    set_function(scope, stream_obj, "pause", read_stream_pause_callback);
+    set_function(scope, stream_obj, "resume", read_stream_resume_callback);
+    set_function(scope, stream_obj, "destroy", read_stream_destroy_callback);
+    set_function(scope, stream_obj, "close", read_stream_destroy_callback);
+    set_function(scope, stream_obj, "pipe", stream_pipe_callback);
+
+    let external_stream = v8::External::new(scope, Box::into_raw(stream) as *const _ as *mut c_void);
+    stream_obj.set_internal_field(0, external_stream.into());
+
+    stream_obj
+}
+
+pub fn create_write_stream_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    stream: Box<FileWriteStream>
+) -> v8::Local<'s, v8::Object> {
+    let stream_template = v8::ObjectTemplate::new(scope);
+    stream_template.set_internal_field_count(1); // Store the Rust FileWriteStream struct internally
+    let stream_obj = stream_template.new_instance(scope).unwrap();
+
+    attach_event_emitter(scope, stream_obj, &stream.event_emitter);
+    set_function(scope, stream_obj, "write", write_stream_write_callback);
+    set_function(scope, stream_obj, "end", write_stream_end_callback);
+
+    let external_stream = v8::External::new(scope, Box::into_raw(stream) as *const _ as *mut c_void);
+    stream_obj.set_internal_field(0, external_stream.into());
+
+    stream_obj
+}
+
+pub fn fs_create_read_stream_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let raw_ptr = retrieve_tx(scope, "channel").unwrap();
+    let tx = unsafe { &*raw_ptr };
+
+    let path = args.get(0).to_rust_string_lossy(scope);
+
+    // The second argument is either an options object or an encoding
+    let (options, encoding) = if args.get(1).is_string() {
+        (None, Some(args.get(1).to_rust_string_lossy(scope)))
+    } else {
+        let options = v8::Local::<v8::Object>::try_from(args.get(1)).ok();
+        (options, get_string_option(scope, options, "encoding"))
+    };
+
+    let options = ReadStreamOptions {
+        start: get_number_option(scope, options, "start").unwrap_or(0.0).max(0.0) as u64,
+        end: get_number_option(scope, options, "end").map(|end| end.max(0.0) as u64),
+        high_water_mark: get_number_option(scope, options, "highWaterMark")
+            .map(|size| size.max(1.0) as usize)
+            .unwrap_or(READ_HIGH_WATER_MARK),
+        encoding,
+    };
+
+    let permissions = get_fs_instance(scope, &args).permissions.clone();
+    let stream = FileReadStream::open(PathBuf::from(path), options, permissions, tx.clone());
+    let stream_obj = create_read_stream_object(scope, Box::new(stream));
+    rv.set(stream_obj.into());
+}
+
+pub fn fs_create_write_stream_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let raw_ptr = retrieve_tx(scope, "channel").unwrap();
+    let tx = unsafe { &*raw_ptr };
+
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let options = v8::Local::<v8::Object>::try_from(args.get(1)).ok();
+    let flags = get_string_option(scope, options, "flags").unwrap_or_else(|| "w".to_string());
+
+    let permissions = get_fs_instance(scope, &args).permissions.clone();
+    let stream = FileWriteStream::open(PathBuf::from(path), flags, permissions, tx.clone());
+    let stream_obj = create_write_stream_object(scope, Box::new(stream));
+    rv.set(stream_obj.into());
+}
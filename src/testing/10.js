@@ -0,0 +1,21 @@
+let dir = "src/testing/temp_dir"
+
+fs.mkdir(dir + "/nested", { recursive: true }, (err) => {
+    fs.writeFile(dir + "/nested/file.txt", "contents", (err) => {
+        fs.stat(dir + "/nested/file.txt", (err, stats) => {
+            console.log("Size: " + stats.size)
+            console.log("Is File: " + stats.isFile())
+            console.log("Is Directory: " + stats.isDirectory())
+        })
+
+        fs.readdir(dir, { recursive: true, withFileTypes: true }, (err, entries) => {
+            for (const entry of entries) {
+                console.log(entry.name + " " + entry.isDirectory())
+            }
+
+            fs.rm(dir, { recursive: true, force: true }, (err) => {
+                console.log("Removed: " + (err === null))
+            })
+        })
+    })
+})
@@ -0,0 +1,46 @@
+// Stress test: every call gets its own path, results must never cross over
+let dir = "src/testing/temp_stress"
+let total = 5000
+let written = 0
+let verified = 0
+let failures = 0
+
+function finish() {
+    console.log("Verified " + verified + " of " + total + " files, " + failures + " failures")
+    fs.rm(dir, { recursive: true, force: true }, (err) => {
+        console.log("Cleaned up: " + (err === null))
+    })
+}
+
+function readAll() {
+    for (let i = 0; i < total; i++) {
+        fs.readFile(dir + "/file_" + i + ".txt", (err, data) => {
+            if (err !== null || data !== "contents of " + i) {
+                failures++
+                console.log("Mismatch for file " + i + ": " + (err !== null ? err : data))
+            }
+            verified++
+            if (verified === total) finish()
+        })
+    }
+}
+
+fs.mkdir(dir, { recursive: true }, (err) => {
+    for (let i = 0; i < total; i++) {
+        fs.writeFile(dir + "/file_" + i + ".txt", "contents of " + i, (err) => {
+            if (err !== null) {
+                failures++
+                console.log("Failed to write file " + i + ": " + err)
+            }
+            written++
+            if (written === total) readAll()
+        })
+    }
+
+    // Interleave unrelated operations while the writes are in flight
+    for (let i = 0; i < 100; i++) {
+        fs.stat(dir, (err, stats) => {
+            if (err !== null || !stats.isDirectory()) failures++
+        })
+    }
+})
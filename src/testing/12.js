@@ -0,0 +1,32 @@
+let source = "src/testing/temp_read_file.txt"
+let copy = "src/testing/temp_stream_copy.txt"
+
+// Read a slice of the file as text
+const reader = fs.createReadStream(source, { start: 0, end: 15, highWaterMark: 4, encoding: "utf8" })
+
+reader.on('data', (chunk) => {
+    console.log("Chunk: " + chunk)
+})
+
+reader.on('end', () => {
+    console.log("Read stream ended")
+})
+
+// Copy the whole file through a write stream
+const writer = fs.createWriteStream(copy, { flags: "w" })
+fs.createReadStream(source).pipe(writer)
+
+writer.on('finish', () => {
+    fs.readFile(copy, (err, data) => {
+        console.log("Copied: " + data)
+        fs.unlink(copy, (err) => {})
+    })
+})
+
+// Stream a file into an http response
+const server = http.createServer((req, res) => {
+    res.setHeader('Content-Type', 'text/plain')
+    fs.createReadStream(source).pipe(res)
+})
+
+server.listen(8000, '127.0.0.1')
@@ -0,0 +1,48 @@
+let path = "src/testing/temp_fd_file.txt"
+
+fs.open(path, "w+", (err, fd) => {
+    fs.write(fd, "hello file descriptors", 0, (err, bytesWritten) => {
+        console.log("Bytes written: " + bytesWritten)
+
+        let buffer = new Uint8Array(4)
+        fs.read(fd, buffer, 0, 4, 6, (err, bytesRead, buffer) => {
+            console.log("Bytes read: " + bytesRead + " " + buffer)
+
+            fs.fstat(fd, (err, stats) => {
+                console.log("Size: " + stats.size)
+
+                fs.close(fd, (err) => {
+                    console.log("Closed: " + (err === null))
+
+                    fs.close(fd, (err) => {
+                        console.log("Closing twice: " + err)
+                        usePromises()
+                    })
+                })
+            })
+        })
+    })
+})
+
+async function usePromises() {
+    const handle = await fs.promises.open(path, "r+")
+    await handle.write("HELLO", 0)
+
+    const { bytesRead, buffer } = await handle.read(new Uint8Array(5), 0, 5, 0)
+    console.log("Promise read: " + bytesRead + " " + buffer)
+
+    await handle.truncate(5)
+    const stats = await handle.stat()
+    console.log("Truncated size: " + stats.size)
+    await handle.close()
+
+    try {
+        await fs.promises.open("src/testing/missing/file.txt")
+    } catch (err) {
+        console.log("Rejected: " + err.message)
+    }
+
+    fs.unlink(path, (err) => {
+        console.log("Removed: " + (err === null))
+    })
+}
@@ -0,0 +1,26 @@
+let dir = "src/testing/temp_watch_dir"
+let file = dir + "/config.json"
+
+fs.mkdir(dir + "/nested", { recursive: true }, (err) => {
+    fs.writeFile(file, "{}", (err) => {
+        const watcher = fs.watch(dir, { recursive: true }, (eventType, filename) => {
+            console.log("watch: " + eventType + " " + filename)
+        })
+        watcher.on("close", () => console.log("watcher closed"))
+
+        fs.watchFile(file, { interval: 100 }, (curr, prev) => {
+            console.log("watchFile: size " + prev.size + " -> " + curr.size)
+            fs.unwatchFile(file)
+            watcher.close()
+
+            fs.rm(dir, { recursive: true, force: true }, (err) => {
+                console.log("Removed: " + (err === null))
+            })
+        })
+
+        setTimeout(() => {
+            fs.writeFile(dir + "/nested/other.txt", "nested change", (err) => {})
+            fs.writeFile(file, "{\"reload\": true}", (err) => {})
+        }, 200)
+    })
+})
@@ -0,0 +1,28 @@
+// Run with: cargo run main --allow-read=src/testing src/testing/15.js
+
+fs.readFile("src/testing/temp_read_file.txt", (err, data) => {
+    console.log("Allowed read: " + (err === null))
+})
+
+fs.readFile("src/testing/../../Cargo.toml", (err, data) => {
+    console.log("Read outside the grant: " + err)
+})
+
+// link_to_src points at src/, so the OS reads ../Cargo.toml next to it even though the text stays inside the grant
+fs.readFile("src/testing/link_to_src/../Cargo.toml", (err, data) => {
+    console.log("Read through a symlink and ..: " + err)
+})
+
+fs.writeFile("src/testing/temp_denied.txt", "nope", (err) => {
+    console.log("Write without --allow-write: " + err)
+})
+
+fs.promises.open("src/testing/temp_denied.txt", "w").catch((err) => {
+    console.log("Rejected: " + err.message)
+})
+
+try {
+    http.createServer((req, res) => res.end("unreachable")).listen(8000)
+} catch (err) {
+    console.log("Listen without --allow-net: " + err.message)
+}
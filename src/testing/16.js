@@ -0,0 +1,19 @@
+// Run with: cargo run main --allow-net src/testing/16.js
+// then: curl --data-binary @Cargo.lock http://127.0.0.1:8000/upload
+//       curl -H "Transfer-Encoding: chunked" --data-binary @README.md http://127.0.0.1:8000/upload
+
+const server = http.createServer((req, res) => {
+    let received = 0
+
+    req.on('data', (chunk) => {
+        received += chunk.length
+    })
+
+    req.on('end', () => {
+        console.log("Received " + received + " bytes")
+        res.setHeader('Content-Type', 'text/plain')
+        res.end("Received " + received + " bytes\n")
+    })
+})
+
+server.listen(8000, '127.0.0.1')
@@ -0,0 +1,13 @@
+// Run with: cargo run main --allow-net src/testing/17.js
+// then: curl -v http://127.0.0.1:8000/first http://127.0.0.1:8000/second
+//   (curl reports "Re-using existing connection" for the second request)
+// pipelined: printf 'GET /a HTTP/1.1\r\nHost: x\r\n\r\nGET /b HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n' | nc 127.0.0.1 8000
+
+const server = http.createServer((req, res) => {
+    console.log(req.method + " " + req.url)
+    res.setHeader('Content-Type', 'text/plain')
+    res.end("You requested " + req.url + "\n")
+})
+
+server.keepAliveTimeout = 2000
+server.listen(8000, '127.0.0.1')
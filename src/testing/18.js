@@ -0,0 +1,43 @@
+// Run with: cargo run main --allow-net src/testing/18.js
+// then: curl -N http://127.0.0.1:8000/events    (server-sent events, one per second)
+//       curl -s http://127.0.0.1:8000/large | wc -c    (8 MiB streamed with backpressure)
+
+const server = http.createServer((req, res) => {
+    if (req.url === '/events') {
+        res.writeHead(200, { 'Content-Type': 'text/event-stream', 'Cache-Control': 'no-cache' })
+        console.log("headersSent: " + res.headersSent)
+
+        let count = 0
+        const send = () => {
+            count += 1
+            res.write("data: event " + count + "\n\n")
+            if (count < 5) {
+                setTimeout(send, 1000)
+            } else {
+                res.end()
+            }
+        }
+        send()
+        return
+    }
+
+    const chunk = "x".repeat(64 * 1024)
+    let remaining = 128
+    res.setHeader('Content-Type', 'text/plain')
+    console.log("Content-Type: " + res.getHeader('content-type'))
+
+    const writeMore = () => {
+        while (remaining > 0) {
+            remaining -= 1
+            if (!res.write(chunk)) {
+                res.once('drain', writeMore)
+                return
+            }
+        }
+        res.end()
+    }
+    res.on('finish', () => console.log("Response finished"))
+    writeMore()
+})
+
+server.listen(8000, '127.0.0.1')
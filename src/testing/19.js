@@ -0,0 +1,25 @@
+// Run with: cargo run main --allow-net src/testing/19.js
+// then: curl -i http://127.0.0.1:8000/missing     (HTTP/1.1 404 Not Found, Date and Content-Length)
+//       curl -I http://127.0.0.1:8000/             (HEAD, Content-Length without a body)
+//       curl -i http://127.0.0.1:8000/empty        (204 No Content)
+//       curl -i http://127.0.0.1:8000/teapot       (custom reason phrase)
+
+const server = http.createServer((req, res) => {
+    if (req.url === '/missing') {
+        res.statusCode = 404
+        res.end("Nothing here\n")
+    } else if (req.url === '/empty') {
+        res.statusCode = 204
+        res.end("ignored")
+    } else if (req.url === '/teapot') {
+        res.statusCode = 418
+        res.statusMessage = "Short And Stout"
+        console.log("statusMessage: " + res.statusMessage)
+        res.end()
+    } else {
+        res.setHeader('Content-Type', 'text/plain')
+        res.end("Hello from " + req.method + "\n")
+    }
+})
+
+server.listen(8000, '127.0.0.1')
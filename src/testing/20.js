@@ -0,0 +1,17 @@
+// Run with: cargo run main --allow-net src/testing/20.js
+// then: curl -i -H "X-Trace: one" -H "x-trace: two" -H "Cookie: a=1" -H "Cookie: b=2" http://127.0.0.1:8000/
+
+const server = http.createServer((req, res) => {
+    const headers = req.headers
+    console.log("x-trace: " + headers['x-trace'])
+    console.log("cookie: " + headers['cookie'])
+    console.log("rawHeaders: " + JSON.stringify(req.rawHeaders))
+
+    res.setHeader('Set-Cookie', ['session=abc; HttpOnly', 'theme=dark'])
+    res.setHeader('content-type', 'text/plain')
+    console.log("Content-Type: " + res.getHeader('Content-Type'))
+    console.log("Set-Cookie: " + JSON.stringify(res.getHeader('set-cookie')))
+    res.end("Two cookies set\n")
+})
+
+server.listen(8000, '127.0.0.1')
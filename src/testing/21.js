@@ -0,0 +1,20 @@
+// Run with: cargo run main --allow-net src/testing/21.js
+// then: curl --data "hello" http://127.0.0.1:8000/echo?x=1
+// abort: curl -H "Content-Length: 1000" --data "short" --max-time 1 http://127.0.0.1:8000/
+
+const server = http.createServer((req, res) => {
+    console.log(req.method + " " + req.url + " HTTP/" + req.httpVersion)
+    console.log("from " + req.socket.remoteAddress + ":" + req.socket.remotePort)
+    console.log("user-agent: " + req.headers['user-agent'])
+
+    let body = ""
+    req.on('data', (chunk) => { body += chunk.length + " bytes " })
+    req.on('aborted', () => console.log("Client aborted, complete: " + req.complete))
+    req.on('close', () => console.log("Request closed"))
+    req.on('end', () => {
+        console.log("complete: " + req.complete)
+        res.end("Received " + body + "\n")
+    })
+})
+
+server.listen(8000, '127.0.0.1')
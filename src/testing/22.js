@@ -0,0 +1,36 @@
+// Run with: cargo run main --allow-net src/testing/22.js
+// Starts a server on a free port, sends it one request with curl-like http.get, then closes it
+
+const server = http.createServer()
+
+server.on('connection', (socket) => {
+    console.log("Connection from " + socket.remoteAddress + ":" + socket.remotePort)
+})
+
+server.on('request', (req, res) => {
+    res.end("Hello on port " + server.address().port + "\n")
+})
+
+server.on('error', (error) => console.log("Server error: " + error))
+
+server.on('close', () => {
+    console.log("Server closed, listening: " + server.listening)
+})
+
+server.listen(0, '127.0.0.1', () => {
+    const address = server.address()
+    console.log("Listening on " + address.address + ":" + address.port + " (" + address.family + ")")
+
+    // Binding the same port a second time fails with an 'error' event
+    const other = http.createServer()
+    other.on('error', (error) => console.log("Second server: " + error))
+    other.listen(address.port, '127.0.0.1')
+
+    http.get("http://127.0.0.1:" + address.port + "/", (res) => {
+        res.on('data', (chunk) => console.log("Received " + chunk.length + " bytes"))
+        res.on('end', () => {
+            server.close(() => console.log("close callback"))
+            server.close((error) => console.log("Closing twice: " + error))
+        })
+    })
+})
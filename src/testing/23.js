@@ -0,0 +1,30 @@
+// Run with: cargo run main --allow-net src/testing/23.js
+// Server limits: try them with a slow or oversized client, for example
+//   (printf 'GET / HTTP/1.1\r\n'; sleep 3) | nc 127.0.0.1 8000               -> 408 after headersTimeout
+//   curl -H "X-Big: $(head -c 5000 /dev/zero | tr '\0' a)" 127.0.0.1:8000   -> 431
+//   nc 127.0.0.1 8000 (and send nothing)                                      -> 'timeout' after 4s
+//   three parallel idle nc sessions                                           -> the third emits 'drop'
+
+const server = http.createServer({ maxHeaderSize: 4096 }, (req, res) => {
+    req.on('error', (error) => console.log("Request error: " + error))
+    req.on('end', () => res.end("Hello\n"))
+})
+
+server.headersTimeout = 2000
+server.requestTimeout = 5000
+server.keepAliveTimeout = 1000
+server.maxConnections = 2
+
+server.setTimeout(4000, (socket) => {
+    console.log("Idle socket timed out: " + socket.remoteAddress + ":" + socket.remotePort)
+})
+
+server.on('drop', (socket) => {
+    console.log("Dropped connection from " + socket.remoteAddress + ", over maxConnections")
+})
+
+console.log("headersTimeout " + server.headersTimeout + ", requestTimeout " + server.requestTimeout +
+    ", keepAliveTimeout " + server.keepAliveTimeout + ", timeout " + server.timeout)
+console.log("maxConnections " + server.maxConnections + ", maxHeaderSize " + server.maxHeaderSize)
+
+server.listen(8000, '127.0.0.1', () => console.log("Listening on 127.0.0.1:8000"))
@@ -0,0 +1,28 @@
+// Run with: cargo run main --allow-net src/testing/24.js
+// Graceful shutdown: start a slow request, then send SIGTERM while it is in flight
+//   curl -i 127.0.0.1:8000/slow & sleep 1; kill -TERM <pid>
+// The slow response still completes, with Connection: close, before the process exits.
+// /idle closes idle keep-alive connections, /all cuts every connection
+
+const server = http.createServer((req, res) => {
+    if (req.url == "/slow") {
+        res.write("started\n")
+        setTimeout(() => res.end("finished\n"), 3000)
+    } else if (req.url == "/idle") {
+        res.end("closing idle connections\n")
+        server.closeIdleConnections()
+    } else if (req.url == "/all") {
+        res.end("closing every connection\n")
+        server.closeAllConnections()
+    } else {
+        res.end("Hello\n")
+    }
+})
+
+server.shutdownTimeout = 5000
+
+server.on('close', () => console.log("Server closed"))
+
+server.listen(8000, '127.0.0.1', () => {
+    console.log("Listening on 127.0.0.1:8000, shutdownTimeout " + server.shutdownTimeout)
+})
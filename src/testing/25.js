@@ -0,0 +1,60 @@
+// Run with: cargo run main --allow-net src/testing/25.js
+// http.request against a local echo server: headers, a chunked body and a Content-Length body
+
+const server = http.createServer((req, res) => {
+    let body = ""
+    req.on('data', (chunk) => body += chunk)
+    req.on('end', () => {
+        res.setHeader('Content-Type', 'text/plain')
+        res.setHeader('X-Received-Length', String(body.length))
+        res.end(req.method + " " + req.url + " " + (req.headers['transfer-encoding'] || "length " + req.headers['content-length']) +
+            " x-token=" + req.headers['x-token'] + "\n" + body + "\n")
+    })
+})
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+
+    // Streamed body, sent chunked
+    const req = http.request({ hostname: '127.0.0.1', port, path: '/upload?kind=stream', method: 'post' }, (res) => {
+        console.log(res.statusCode + " " + res.statusMessage + " HTTP/" + res.httpVersion)
+        console.log("content-type: " + res.headers['content-type'] + ", x-received-length: " + res.headers['x-received-length'])
+        res.setEncoding('utf8')
+        res.on('data', (chunk) => console.log(chunk))
+        res.on('end', () => {
+            console.log("complete: " + res.complete)
+            sendJson(port)
+        })
+    })
+    req.setHeader('X-Token', 'abc123')
+    req.write("first line\n")
+    req.write("second line")
+    req.end()
+})
+
+// Whole body in end(), sent with a Content-Length
+function sendJson(port) {
+    const body = JSON.stringify({ hello: "world" })
+    const req = http.request("http://127.0.0.1:" + port + "/json", { method: 'PUT', headers: { 'Content-Type': 'application/json', 'X-Token': 'xyz' } })
+    req.on('response', (res) => {
+        res.setEncoding()
+        res.on('data', (chunk) => console.log(chunk))
+        res.on('end', () => refusedConnection())
+    })
+    req.end(body)
+}
+
+// Connection errors and invalid options
+function refusedConnection() {
+    const req = http.request({ hostname: '127.0.0.1', port: 1, path: '/' })
+    req.on('error', (error) => console.log("Request error: " + error))
+    req.on('close', () => {
+        try {
+            http.request({ hostname: '127.0.0.1', port: 80, path: '/has space' })
+        } catch (error) {
+            console.log("Invalid path: " + error.message)
+        }
+        server.close()
+    })
+    req.end()
+}
@@ -0,0 +1,42 @@
+// Run with: cargo run main --allow-net src/testing/26.js
+// http.get parses the status line and headers, and 'data' only carries the decoded body
+
+const server = http.createServer((req, res) => {
+    if (req.url == "/chunked") {
+        // Streamed without a length, sent with Transfer-Encoding: chunked
+        res.setHeader('Content-Type', 'text/plain')
+        res.write("one ")
+        res.write("two ")
+        res.end("three")
+    } else if (req.url == "/missing") {
+        res.writeHead(404, "Nothing Here", { 'Set-Cookie': ['a=1', 'b=2'] })
+        res.end()
+    } else {
+        res.end("fixed length body")
+    }
+})
+
+function get(port, path, next) {
+    http.get("http://127.0.0.1:" + port + path, (res) => {
+        console.log(path + ": " + res.statusCode + " " + res.statusMessage +
+            ", transfer-encoding " + res.headers['transfer-encoding'] + ", content-length " + res.headers['content-length'])
+        if (res.headers['set-cookie']) {
+            console.log("set-cookie: " + res.headers['set-cookie'].join(" | "))
+        }
+
+        let body = ""
+        res.setEncoding('utf8')
+        res.on('data', (chunk) => body += chunk)
+        res.on('end', () => {
+            console.log("body: '" + body + "', complete: " + res.complete)
+            next()
+        })
+    })
+}
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+    get(port, "/chunked", () =>
+        get(port, "/fixed", () =>
+            get(port, "/missing", () => server.close())))
+})
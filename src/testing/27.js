@@ -0,0 +1,78 @@
+// Run with: cargo run main --allow-net src/testing/27.js
+// fetch() with Request, Response and Headers, redirects, request bodies and AbortController
+
+const server = http.createServer((req, res) => {
+    if (req.url == "/json") {
+        res.setHeader('Content-Type', 'application/json')
+        res.end(JSON.stringify({ hello: "world", method: req.method }))
+    } else if (req.url == "/echo") {
+        let body = ""
+        req.on('data', (chunk) => body += chunk)
+        req.on('end', () => res.end(req.method + " " + req.headers['content-type'] + ": " + body))
+    } else if (req.url == "/moved") {
+        res.writeHead(302, { 'Location': '/json' })
+        res.end()
+    } else if (req.url == "/stream") {
+        res.write("first ")
+        setTimeout(() => res.end("second"), 50)
+    } else if (req.url == "/slow") {
+        setTimeout(() => res.end("too late"), 2000)
+    } else {
+        res.writeHead(404)
+        res.end("not found")
+    }
+})
+
+async function main(base) {
+    const json = await fetch(base + "/json")
+    console.log("json: " + json.status + " " + json.ok + " " + json.headers.get('content-type'))
+    console.log(JSON.stringify(await json.json()) + ", bodyUsed " + json.bodyUsed)
+
+    const posted = await fetch(base + "/echo", { method: "post", body: "some text" })
+    console.log("echo: " + await posted.text())
+
+    const headers = new Headers({ 'Content-Type': 'application/json' })
+    headers.append('X-Tag', 'a')
+    headers.append('x-tag', 'b')
+    const request = new Request(base + "/echo", { method: "PUT", headers, body: JSON.stringify([1, 2]) })
+    console.log("x-tag: " + request.headers.get('x-tag'))
+    console.log("request: " + await (await fetch(request)).text())
+
+    const moved = await fetch(base + "/moved")
+    console.log("redirected: " + moved.redirected + " to " + moved.url + ", status " + moved.status)
+
+    const manual = await fetch(base + "/moved", { redirect: "manual" })
+    console.log("manual: " + manual.status + " location " + manual.headers.get('location'))
+
+    const missing = await fetch(base + "/missing")
+    console.log("missing: " + missing.status + " ok " + missing.ok)
+
+    const reader = (await fetch(base + "/stream")).body.getReader()
+    for (let result = await reader.read(); !result.done; result = await reader.read()) {
+        console.log("chunk of " + result.value.length + " bytes")
+    }
+
+    const controller = new AbortController()
+    setTimeout(() => controller.abort(), 100)
+    try {
+        await fetch(base + "/slow", { signal: controller.signal })
+    } catch (e) {
+        console.log("aborted: " + e.name + " " + controller.signal.aborted)
+    }
+
+    try {
+        await fetch("http://127.0.0.1:1/")
+    } catch (e) {
+        console.log(e.message + ": " + e.cause.message)
+    }
+
+    const local = new Response("made locally", { status: 201, headers: [['x-local', 'yes']] })
+    console.log("local: " + local.status + " " + local.headers.get('x-local') + " " + await local.text())
+    console.log("Response.json: " + await Response.json({ n: 1 }).text())
+}
+
+server.listen(0, '127.0.0.1', () => {
+    main("http://127.0.0.1:" + server.address().port)
+        .catch((e) => console.log("failed: " + e))
+        .then(() => server.close())
+})
@@ -0,0 +1,51 @@
+// Run with: cargo run main --allow-net src/testing/28.js
+// Requests through a keep-alive agent reuse one connection, maxSockets queues the rest
+
+let connections = 0
+const server = http.createServer((req, res) => {
+    res.end("served " + req.url)
+})
+server.on('connection', () => connections++)
+
+function get(port, path, agent, next) {
+    http.get({ host: '127.0.0.1', port, path, agent }, (res) => {
+        let body = ""
+        res.setEncoding('utf8')
+        res.on('data', (chunk) => body += chunk)
+        res.on('end', () => {
+            console.log(path + ": '" + body + "'")
+            next()
+        })
+    })
+}
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+    const agent = new http.Agent({ keepAlive: true, maxSockets: 1 })
+    console.log("pool: " + agent.getName({ host: '127.0.0.1', port }))
+
+    // One after the other over the same connection
+    get(port, "/first", agent, () =>
+        get(port, "/second", agent, () => {
+            console.log("connections after two sequential requests: " + connections)
+            console.log("free sockets: " + JSON.stringify(agent.freeSockets))
+
+            // Three at once, maxSockets: 1 makes two of them wait their turn
+            let pending = 3
+            for (const path of ["/a", "/b", "/c"]) {
+                get(port, path, agent, () => {
+                    if (--pending > 0) return
+                    console.log("connections after three parallel requests: " + connections)
+
+                    // agent: false never reuses a connection
+                    get(port, "/own", false, () => {
+                        console.log("connections after agent: false: " + connections)
+                        agent.destroy()
+                        server.close()
+                    })
+                })
+            }
+            console.log("waiting requests: " + JSON.stringify(agent.requests))
+        })
+    )
+})
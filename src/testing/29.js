@@ -0,0 +1,70 @@
+// Run with: cargo run main --allow-net src/testing/29.js
+// maxRedirects follows redirects, timeout gives up on a silent server, destroy() stops a request
+
+const server = http.createServer((req, res) => {
+    let body = ""
+    req.on('data', (chunk) => body += chunk)
+    req.on('end', () => {
+        if (req.url == "/old") {
+            res.writeHead(301, { 'Location': '/new' })
+            res.end()
+        } else if (req.url == "/see-other") {
+            res.writeHead(303, { 'Location': '/new' })
+            res.end()
+        } else if (req.url == "/temporary") {
+            res.writeHead(307, { 'Location': '/new' })
+            res.end()
+        } else if (req.url == "/loop") {
+            res.writeHead(302, { 'Location': '/loop' })
+            res.end()
+        } else if (req.url == "/hang") {
+            // Never answers
+        } else {
+            res.end(req.method + " " + req.url + " with body '" + body + "'")
+        }
+    })
+})
+
+function post(port, path, next) {
+    const req = http.request({ host: '127.0.0.1', port, path, method: 'POST', maxRedirects: 5 }, (res) => {
+        let body = ""
+        res.setEncoding('utf8')
+        res.on('data', (chunk) => body += chunk)
+        res.on('end', () => {
+            console.log(path + " -> " + res.responseUrl + ": " + res.statusCode + " " + body)
+            next()
+        })
+    })
+    req.on('error', (error) => {
+        console.log(path + " failed: " + error)
+        next()
+    })
+    req.end("payload")
+}
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+
+    // 301 and 303 continue as a GET, 307 keeps the method and the body
+    post(port, "/old", () =>
+        post(port, "/see-other", () =>
+            post(port, "/temporary", () =>
+                post(port, "/loop", () => {
+                    const req = http.get({ host: '127.0.0.1', port, path: '/hang', timeout: 200 })
+                    req.on('timeout', () => console.log("/hang timed out"))
+                    req.on('error', (error) => console.log("/hang failed: " + error))
+                    req.on('close', () => {
+                        const destroyed = http.get({ host: '127.0.0.1', port, path: '/hang' })
+                        destroyed.on('error', (error) => console.log("destroyed: " + error))
+                        destroyed.on('close', () => {
+                            console.log("destroyed is " + destroyed.destroyed)
+                            server.closeAllConnections()
+                            server.close()
+                        })
+                        setTimeout(() => destroyed.destroy(), 50)
+                    })
+                })
+            )
+        )
+    )
+})
@@ -0,0 +1,61 @@
+// Run with: cargo run main --allow-read --allow-net src/testing/30.js
+// An https server with a certificate from a test CA, and clients trusting that CA through `ca`
+
+const dir = "src/testing/certs/"
+
+function read(name) {
+    return new Promise((resolve, reject) => {
+        fs.readFile(dir + name, (err, data) => err ? reject(err) : resolve(data))
+    })
+}
+
+function get(options, label) {
+    return new Promise((resolve) => {
+        const req = https.get(options, (res) => {
+            let body = ""
+            res.setEncoding('utf8')
+            res.on('data', (chunk) => body += chunk)
+            res.on('end', () => {
+                console.log(label + ": " + res.statusCode + " '" + body + "'")
+                resolve()
+            })
+        })
+        req.on('error', (error) => {
+            console.log(label + " failed: " + error)
+            resolve()
+        })
+    })
+}
+
+async function main() {
+    const [ca, key, cert, apiKey, apiCert] = await Promise.all([
+        read("ca.pem"), read("localhost-key.pem"), read("localhost-cert.pem"),
+        read("api.example.test-key.pem"), read("api.example.test-cert.pem"),
+    ])
+
+    const server = https.createServer({ key, cert }, (req, res) => {
+        res.end("secure hello from " + req.headers.host)
+    })
+    // Clients asking for api.example.test get its own certificate
+    server.addContext("api.example.test", { key: apiKey, cert: apiCert })
+    server.on('tlsClientError', (error) => console.log("server: handshake failed: " + error))
+
+    server.listen(0, '127.0.0.1', async () => {
+        const port = server.address().port
+        const base = { host: '127.0.0.1', port, path: '/' }
+
+        await get({ ...base, ca }, "trusted CA")
+        await get({ ...base }, "bundled roots")
+        await get({ ...base, rejectUnauthorized: false }, "rejectUnauthorized: false")
+        await get({ ...base, ca, servername: 'api.example.test' }, "SNI api.example.test")
+        await get({ ...base, ca, servername: 'other.example.test' }, "SNI other.example.test")
+
+        const response = await fetch("https://localhost:" + port + "/").catch((error) => error)
+        console.log("fetch without the CA: " + response + " (" + (response.cause || "") + ")")
+
+        https.globalAgent.destroy()
+        server.close()
+    })
+}
+
+main()
@@ -0,0 +1,76 @@
+// Run with: cargo run main --allow-read --allow-net src/testing/31.js
+// HTTP/2 without TLS (prior knowledge) and over TLS through ALPN, with trailers like gRPC uses
+
+const server = http2.createServer((req, res) => {
+    let body = ""
+    req.on('data', (chunk) => body += chunk)
+    req.on('end', () => {
+        console.log("server: " + req.method + " " + req.url + " over HTTP/" + req.httpVersion +
+            " authority " + req.headers[':authority'] + ", trailers " + JSON.stringify(req.trailers))
+        res.setHeader('content-type', 'text/plain')
+        res.addTrailers({ 'grpc-status': '0' })
+        res.end("echo '" + body + "'")
+    })
+})
+
+function request(session, headers, body) {
+    return new Promise((resolve) => {
+        const stream = session.request(headers)
+        let text = ""
+        stream.setEncoding('utf8')
+        stream.on('response', (headers) => console.log("client: status " + headers[':status'] + ", " + headers['content-type']))
+        stream.on('data', (chunk) => text += chunk)
+        stream.on('trailers', (trailers) => console.log("client: trailers " + JSON.stringify(trailers)))
+        stream.on('end', () => console.log("client: body " + text))
+        stream.on('error', (error) => console.log("client: stream failed: " + error))
+        stream.on('close', resolve)
+        if (body !== undefined) {
+            stream.end(body)
+        }
+    })
+}
+
+function read(name) {
+    return new Promise((resolve, reject) => {
+        fs.readFile("src/testing/certs/" + name, (err, data) => err ? reject(err) : resolve(data))
+    })
+}
+
+async function secure() {
+    const [ca, key, cert] = await Promise.all([read("ca.pem"), read("localhost-key.pem"), read("localhost-cert.pem")])
+
+    // allowHTTP1 serves https clients on the same port
+    const server = http2.createSecureServer({ key, cert, allowHTTP1: true }, (req, res) => {
+        res.end("hello over HTTP/" + req.httpVersion)
+    })
+    server.listen(0, '127.0.0.1', async () => {
+        const port = server.address().port
+
+        const session = http2.connect("https://localhost:" + port, { ca })
+        await request(session, { ':path': '/secure' })
+        session.close()
+
+        https.get({ host: '127.0.0.1', port, path: '/', ca, servername: 'localhost' }, (res) => {
+            res.setEncoding('utf8')
+            res.on('data', (chunk) => console.log("https client: " + chunk))
+            res.on('end', () => server.close())
+        })
+    })
+}
+
+server.listen(0, '127.0.0.1', async () => {
+    const port = server.address().port
+    const session = http2.connect("http://127.0.0.1:" + port, () => console.log("client: connected"))
+
+    // Both streams share the connection
+    await Promise.all([
+        request(session, { ':path': '/hello' }),
+        request(session, { ':method': 'POST', ':path': '/echo', 'content-type': 'text/plain' }, "some payload"),
+    ])
+
+    session.close(() => {
+        console.log("client: session closed")
+        server.close()
+        secure()
+    })
+})
@@ -0,0 +1,66 @@
+// Run with: cargo run main --allow-net src/testing/32.js
+// A WebSocket echo server on the 'upgrade' event and the global WebSocket client
+
+const server = http.createServer((req, res) => {
+    res.end("plain HTTP still works")
+})
+
+server.on('upgrade', (req, socket, head) => {
+    if (req.url !== '/echo') {
+        socket.end("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+        return
+    }
+
+    const offered = (req.headers['sec-websocket-protocol'] || "").split(',').map((protocol) => protocol.trim())
+    const ws = http.acceptWebSocket(req, socket, { protocol: offered.includes('echo') ? 'echo' : undefined })
+    ws.binaryType = 'nodebuffer'
+    ws.onmessage = (event) => {
+        if (typeof event.data === 'string') {
+            ws.send("echo: " + event.data)
+        } else {
+            console.log("server: binary message of " + event.data.length + " bytes")
+            ws.send(event.data)
+        }
+    }
+    ws.onclose = (event) => console.log("server: closed with " + event.code + " " + event.reason)
+})
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+
+    const ws = new WebSocket("ws://127.0.0.1:" + port + "/echo", ['echo', 'chat'])
+    console.log("client: readyState " + ws.readyState + " (CONNECTING is " + WebSocket.CONNECTING + ")")
+
+    ws.onopen = () => {
+        console.log("client: open, protocol " + ws.protocol)
+        ws.send("hello")
+        ws.send(new Uint8Array([1, 2, 3, 4]))
+        ws.ping("are you there")
+    }
+    ws.addEventListener('pong', (event) => {
+        console.log("client: pong with " + event.data.byteLength + " bytes")
+    })
+
+    let received = 0
+    ws.onmessage = (event) => {
+        if (event.data instanceof ArrayBuffer) {
+            console.log("client: binary " + Array.from(new Uint8Array(event.data)).join(","))
+        } else {
+            console.log("client: " + event.data)
+        }
+        if (++received === 2) {
+            ws.close(1000, "done")
+        }
+    }
+    ws.onclose = (event) => {
+        console.log("client: closed with " + event.code + " '" + event.reason + "', clean " + event.wasClean)
+
+        // Paths the server refuses fail the handshake
+        const refused = new WebSocket("ws://127.0.0.1:" + port + "/missing")
+        refused.onerror = (event) => console.log("client: " + event.message)
+        refused.onclose = (event) => {
+            console.log("client: refused socket closed with " + event.code)
+            server.close()
+        }
+    }
+})
@@ -0,0 +1,66 @@
+// Run with: cargo run main --allow-net src/testing/33.js
+// gzip/deflate/brotli round trips, a server compressing by Accept-Encoding and a client decoding it
+
+const text = "The quick brown fox jumps over the lazy dog. ".repeat(200)
+const ascii = (bytes) => String.fromCharCode(...bytes)
+
+const gzipped = zlib.gzipSync(text, { level: zlib.constants.Z_BEST_COMPRESSION })
+console.log("gzip: " + text.length + " -> " + gzipped.length + " bytes")
+console.log("gunzip matches: " + (ascii(zlib.gunzipSync(gzipped)) === text))
+
+try {
+    zlib.inflateSync("not deflate data")
+} catch (e) {
+    console.log("inflate of garbage throws: " + e.message)
+}
+
+zlib.brotliCompress(text, { params: { [zlib.constants.BROTLI_PARAM_QUALITY]: 5 } }, (err, compressed) => {
+    console.log("brotli: " + text.length + " -> " + compressed.length + " bytes")
+    zlib.brotliDecompress(compressed, (err, result) => {
+        console.log("brotli round trip matches: " + (ascii(result) === text))
+    })
+})
+
+const server = http.createServer((req, res) => {
+    const accepted = req.headers['accept-encoding'] || ""
+    res.setHeader('Content-Type', 'text/plain')
+    if (/\bbr\b/.test(accepted) && req.url === '/br') {
+        res.setHeader('Content-Encoding', 'br')
+        res.end(zlib.brotliCompressSync(text))
+    } else if (/\bgzip\b/.test(accepted)) {
+        res.setHeader('Content-Encoding', 'gzip')
+        const gzip = zlib.createGzip()
+        gzip.pipe(res)
+        gzip.write(text.slice(0, 4000))
+        gzip.end(text.slice(4000))
+    } else {
+        res.end(text)
+    }
+})
+
+server.listen(0, '127.0.0.1', () => {
+    const port = server.address().port
+
+    http.get("http://127.0.0.1:" + port + "/gzip", (res) => {
+        res.setEncoding('utf8')
+        let body = ""
+        res.on('data', (chunk) => body += chunk)
+        res.on('end', () => {
+            console.log("http.get: content-encoding " + res.headers['content-encoding'] + ", decoded " + body.length + " chars, matches " + (body === text))
+
+            // Without decompress the bytes arrive as sent
+            http.get("http://127.0.0.1:" + port + "/gzip", { decompress: false, headers: { 'accept-encoding': 'gzip' } }, (raw) => {
+                let length = 0
+                raw.on('data', (chunk) => length += chunk.length)
+                raw.on('end', async () => {
+                    console.log("decompress false: " + length + " compressed bytes")
+
+                    const response = await fetch("http://127.0.0.1:" + port + "/br")
+                    const decoded = await response.text()
+                    console.log("fetch: content-encoding " + response.headers.get('content-encoding') + ", matches " + (decoded === text))
+                    server.close()
+                })
+            })
+        })
+    })
+})
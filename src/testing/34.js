@@ -0,0 +1,70 @@
+// Run with: cargo run main --allow-read --allow-net src/testing/34.js
+// http.serveStatic with content types, conditional requests, ranges, index files and refused paths
+
+const assets = http.serveStatic("src/testing/public", { maxAge: 60000 })
+
+const server = http.createServer((req, res) => {
+    assets(req, res, () => {
+        res.writeHead(404, { 'Content-Type': 'text/plain' })
+        res.end("custom not found for " + req.url)
+    })
+})
+
+function get(path, headers) {
+    return new Promise((resolve, reject) => {
+        const port = server.address().port
+        const req = http.request({ hostname: '127.0.0.1', port, path, headers: headers || {} }, (res) => {
+            let body = ""
+            res.setEncoding('utf8')
+            res.on('data', (chunk) => body += chunk)
+            res.on('end', () => resolve({ res, body }))
+        })
+        req.on('error', reject)
+        req.end()
+    })
+}
+
+server.listen(0, '127.0.0.1', async () => {
+    const page = await get("/")
+    console.log("/ -> " + page.res.statusCode + " " + page.res.headers['content-type'] + ", " + page.body.length + " chars")
+    console.log("  etag " + page.res.headers['etag'] + ", last-modified " + page.res.headers['last-modified'])
+    console.log("  cache-control " + page.res.headers['cache-control'])
+
+    const css = await get("/style.css")
+    console.log("/style.css -> " + css.res.headers['content-type'])
+
+    // Revalidation with either validator
+    const byEtag = await get("/", { 'If-None-Match': page.res.headers['etag'] })
+    console.log("If-None-Match -> " + byEtag.res.statusCode + ", body '" + byEtag.body + "'")
+    const byDate = await get("/", { 'If-Modified-Since': page.res.headers['last-modified'] })
+    console.log("If-Modified-Since -> " + byDate.res.statusCode)
+
+    // Ranges
+    const first = await get("/style.css", { Range: 'bytes=0-3' })
+    console.log("bytes=0-3 -> " + first.res.statusCode + " " + first.res.headers['content-range'] + " '" + first.body + "'")
+    const last = await get("/style.css", { Range: 'bytes=-2' })
+    console.log("bytes=-2 -> " + last.res.statusCode + " " + last.res.headers['content-range'] + " " + JSON.stringify(last.body))
+    const outside = await get("/style.css", { Range: 'bytes=5000-' })
+    console.log("bytes=5000- -> " + outside.res.statusCode + " " + outside.res.headers['content-range'])
+
+    // Directories
+    const redirect = await get("/docs")
+    console.log("/docs -> " + redirect.res.statusCode + " to " + redirect.res.headers['location'])
+    const docs = await get("/docs/")
+    console.log("/docs/ -> " + docs.res.statusCode + ", " + docs.body.trim().split("\n").length + " lines")
+
+    // Paths that must not be served
+    const traversal = await get("/../34.js")
+    console.log("/../34.js -> " + traversal.res.statusCode)
+    const encoded = await get("/%2e%2e/34.js")
+    console.log("/%2e%2e/34.js -> " + encoded.res.statusCode)
+    const dotfile = await get("/.env")
+    console.log("/.env -> " + dotfile.res.statusCode + " '" + dotfile.body + "'")
+    // escape/index.html is a symlink to the Cargo.toml outside the root
+    const escape = await get("/escape/")
+    console.log("/escape/ -> " + escape.res.statusCode)
+    const missing = await get("/missing.txt")
+    console.log("/missing.txt -> " + missing.res.statusCode + " '" + missing.body + "'")
+
+    server.close()
+})
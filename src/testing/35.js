@@ -0,0 +1,40 @@
+// Run with: cargo run main --allow-net src/testing/35.js
+// A body that stops short is answered with 408 once requestTimeout passed, and its connection is closed
+
+const server = http.createServer((req, res) => {
+    req.on('error', (error) => console.log("server: request error " + error))
+    res.on('close', () => console.log("server: response closed"))
+    req.on('end', () => res.end("got the whole body\n"))
+})
+
+server.requestTimeout = 1000
+server.maxConnections = 1
+
+server.on('drop', () => console.log("server: dropped a connection, the slow one is still counted"))
+
+function post(body, options) {
+    return new Promise((resolve, reject) => {
+        const port = server.address().port
+        const req = http.request({ hostname: '127.0.0.1', port, method: 'POST', agent: false, headers: { 'Content-Length': 100 } }, (res) => {
+            let text = ""
+            res.setEncoding('utf8')
+            res.on('data', (chunk) => text += chunk)
+            res.on('end', () => resolve({ res, text }))
+        })
+        req.on('error', reject)
+        req.write(body)
+        // Stop sending until the server gave up on the rest of the body
+        setTimeout(() => req.end(options.rest), options.delay)
+    })
+}
+
+server.listen(0, '127.0.0.1', async () => {
+    const slow = await post("ten bytes.", { delay: 1500, rest: undefined })
+    console.log("partial body -> " + slow.res.statusCode + ", connection: " + slow.res.headers['connection'])
+
+    // maxConnections is 1, so this only gets through once the slow connection is gone
+    const whole = await post("x".repeat(10), { delay: 0, rest: "x".repeat(90) })
+    console.log("whole body -> " + whole.res.statusCode + " " + whole.text.trim())
+
+    server.close(() => console.log("server closed, no connection left open"))
+})
@@ -0,0 +1,276 @@
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme};
+use tokio_rustls::{client, TlsAcceptor, TlsConnector};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+// Certificates from PEM text, a chain starts with the server's own
+pub fn load_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certificates = rustls_pemfile::certs(&mut &pem[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate: {}", e))?;
+    if certificates.is_empty() {
+        return Err("No certificate found in PEM data".to_string());
+    }
+    Ok(certificates)
+}
+
+// The first PKCS#8, PKCS#1 or SEC1 private key in PEM text
+pub fn load_private_key(pem: &[u8]) -> Result<PrivateKeyDer<'static>, String> {
+    rustls_pemfile::private_key(&mut &pem[..])
+        .map_err(|e| format!("Invalid private key: {}", e))?
+        .ok_or_else(|| "No private key found in PEM data".to_string())
+}
+
+// A certificate chain with its key, refused when they don't belong together
+pub fn certified_key(key: &[u8], cert: &[u8]) -> Result<Arc<CertifiedKey>, String> {
+    let certificates = load_certificates(cert)?;
+    let private_key = load_private_key(key)?;
+    CertifiedKey::from_der(certificates, private_key, &ring::default_provider())
+        .map(Arc::new)
+        .map_err(|e| format!("Invalid key or certificate: {}", e))
+}
+
+// Picks the certificate for the name the client asked for (SNI), the default one otherwise.
+// Contexts added later win, like Node's server.addContext()
+#[derive(Debug)]
+pub struct SniResolver {
+    default: Arc<CertifiedKey>,
+    contexts: Mutex<Vec<(String, Arc<CertifiedKey>)>>,
+}
+
+impl SniResolver {
+    pub fn add_context(&self, hostname: &str, key: Arc<CertifiedKey>) {
+        self.contexts.lock().unwrap().push((hostname.to_string(), key));
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        if let Some(name) = client_hello.server_name() {
+            let contexts = self.contexts.lock().unwrap();
+            if let Some((_, key)) = contexts.iter().rev().find(|(hostname, _)| matches_hostname(hostname, name)) {
+                return Some(key.clone());
+            }
+        }
+        Some(self.default.clone())
+    }
+}
+
+// "*.example.com" stands for exactly one label in front of example.com
+fn matches_hostname(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => name.split_once('.').is_some_and(|(_, rest)| rest.eq_ignore_ascii_case(suffix)),
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+// The TLS side of a server. The resolver stays reachable to add SNI contexts later.
+// `alpn` lists the protocols offered to clients, most preferred first
+pub struct ServerTls {
+    pub acceptor: TlsAcceptor,
+    pub resolver: Arc<SniResolver>,
+}
+
+impl ServerTls {
+    pub fn new(key: &[u8], cert: &[u8], alpn: &[&[u8]]) -> Result<Self, String> {
+        let resolver = Arc::new(SniResolver { default: certified_key(key, cert)?, contexts: Mutex::new(Vec::new()) });
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(resolver.clone());
+        config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+
+        Ok(ServerTls { acceptor: TlsAcceptor::from(Arc::new(config)), resolver })
+    }
+}
+
+// rejectUnauthorized: false, any certificate is accepted but the handshake is still checked
+#[derive(Debug)]
+struct AcceptAnyCertificate {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+fn client_config(roots: RootCertStore, reject_unauthorized: bool) -> Arc<ClientConfig> {
+    let builder = ClientConfig::builder();
+    let mut config = match reject_unauthorized {
+        true => builder.with_root_certificates(roots).with_no_client_auth(),
+        false => {
+            let verifier = AcceptAnyCertificate { algorithms: ring::default_provider().signature_verification_algorithms };
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(verifier)).with_no_client_auth()
+        }
+    };
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Arc::new(config)
+}
+
+thread_local! {
+    // Mozilla's roots, shared by every request without its own `ca`
+    static DEFAULT_CLIENT_CONFIG: Arc<ClientConfig> = {
+        let roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+        client_config(roots, true)
+    };
+}
+
+// How a client request talks TLS, from the `ca`, `rejectUnauthorized` and `servername` options
+#[derive(Clone)]
+pub struct TlsOptions {
+    config: Arc<ClientConfig>,
+    // Sent as SNI and checked against the certificate instead of the host
+    servername: Option<String>,
+    // Connections are only pooled with requests that trust the same certificates
+    trust: String,
+}
+
+impl Default for TlsOptions {
+    fn default() -> Self {
+        TlsOptions {
+            config: DEFAULT_CLIENT_CONFIG.with(|config| config.clone()),
+            servername: None,
+            trust: String::new(),
+        }
+    }
+}
+
+impl TlsOptions {
+    // `ca` replaces the bundled roots
+    pub fn new(ca: Option<&[u8]>, reject_unauthorized: bool, servername: Option<String>) -> Result<Self, String> {
+        if ca.is_none() && reject_unauthorized {
+            return Ok(TlsOptions { servername, ..TlsOptions::default() });
+        }
+
+        let mut roots = RootCertStore::empty();
+        let mut hasher = DefaultHasher::new();
+        match ca {
+            Some(ca) => {
+                for certificate in load_certificates(ca)? {
+                    roots.add(certificate).map_err(|e| format!("Invalid CA certificate: {}", e))?;
+                }
+                ca.hash(&mut hasher);
+            }
+            None => roots.roots = webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        }
+
+        Ok(TlsOptions {
+            config: client_config(roots, reject_unauthorized),
+            servername,
+            trust: format!("{}:{:x}", reject_unauthorized, hasher.finish()),
+        })
+    }
+
+    // The same trust for another server, after a redirect
+    pub fn without_servername(&self) -> Self {
+        TlsOptions { servername: None, ..self.clone() }
+    }
+
+    // The same trust for an HTTP/2 session, which has to agree on h2 through ALPN
+    pub fn for_http2(&self) -> Self {
+        let mut config = (*self.config).clone();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        TlsOptions { config: Arc::new(config), ..self.clone() }
+    }
+
+    // Appended to the agent's pool name
+    pub fn pool_name(&self) -> String {
+        format!("{}:{}", self.servername.as_deref().unwrap_or_default(), self.trust)
+    }
+
+    pub async fn connect(&self, host: &str, socket: TcpStream) -> io::Result<client::TlsStream<TcpStream>> {
+        let name = self.servername.as_deref().unwrap_or(host);
+        let server_name = ServerName::try_from(name.to_string())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid server name {}", name)))?;
+        TlsConnector::from(self.config.clone()).connect(server_name, socket).await
+    }
+}
+
+// A client connection, plain or over TLS
+pub enum Socket {
+    Plain(TcpStream),
+    Tls(Box<client::TlsStream<TcpStream>>),
+}
+
+impl Socket {
+    pub fn tcp(&self) -> &TcpStream {
+        match self {
+            Socket::Plain(socket) => socket,
+            Socket::Tls(socket) => socket.get_ref().0,
+        }
+    }
+}
+
+impl AsyncRead for Socket {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Socket::Plain(socket) => Pin::new(socket).poll_read(cx, buf),
+            Socket::Tls(socket) => Pin::new(socket).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Socket {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Socket::Plain(socket) => Pin::new(socket).poll_write(cx, buf),
+            Socket::Tls(socket) => Pin::new(socket).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Socket::Plain(socket) => Pin::new(socket).poll_flush(cx),
+            Socket::Tls(socket) => Pin::new(socket).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Socket::Plain(socket) => Pin::new(socket).poll_shutdown(cx),
+            Socket::Tls(socket) => Pin::new(socket).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use rusty_v8 as v8;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use futures::StreamExt;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::emitter::{attach_event_emitter, emit_event, EventEmitter};
+use crate::fs::{create_stats_object, get_fs_instance, read_dir_entries, FileStats};
+use crate::helper::{retrieve_tx, set_function, throw_error};
+use crate::permissions::Permissions;
+use crate::interface::{Operations, WatchEvent};
+
+// Node's default polling interval for fs.watchFile
+const STAT_POLL_INTERVAL_MS: u64 = 5007;
+
+// The same events Node's inotify backend listens to
+fn watch_mask() -> WatchMask {
+    WatchMask::ATTRIB
+        | WatchMask::CREATE
+        | WatchMask::MODIFY
+        | WatchMask::DELETE
+        | WatchMask::DELETE_SELF
+        | WatchMask::MOVE_SELF
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+}
+
+pub struct FsWatcher {
+    event_emitter: Rc<RefCell<EventEmitter>>,
+    closed: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+    tx: UnboundedSender<Operations>,
+}
+
+impl FsWatcher {
+    pub fn watch(path: PathBuf, recursive: bool, permissions: Arc<Permissions>, tx: UnboundedSender<Operations>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let task = tokio::task::spawn_local({
+            let emitter = event_emitter.clone();
+            let closed = closed.clone();
+            let tx = tx.clone();
+            async move {
+                let result = match permissions.check_read(&path) {
+                    Ok(()) => watch_path(&path, recursive, &emitter, &closed, &tx).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = result {
+                    let error_message = format!("Failed to watch {}: {}", path.display(), e);
+                    let _ = tx.send(Operations::Watch(WatchEvent::Error { emitter, error_message }));
+                }
+            }
+        });
+
+        FsWatcher { event_emitter, closed, task, tx }
+    }
+
+    // Dropping the inotify stream with the task releases its file descriptor
+    pub fn close(&self) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.task.abort();
+        let _ = self.tx.send(Operations::Watch(WatchEvent::Close { emitter: self.event_emitter.clone() }));
+    }
+}
+
+// The watched directory first, followed by its subdirectories when recursive,
+// all relative to root
+async fn watched_directories(root: &Path, recursive: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut directories = vec![PathBuf::new()];
+    if recursive {
+        let entries = read_dir_entries(root, true).await?;
+        directories.extend(entries.into_iter()
+            .filter(|entry| entry.kind.is_directory)
+            .map(|entry| PathBuf::from(entry.name)));
+    }
+    Ok(directories)
+}
+
+async fn watch_path(
+    path: &Path,
+    recursive: bool,
+    emitter: &Rc<RefCell<EventEmitter>>,
+    closed: &Arc<AtomicBool>,
+    tx: &UnboundedSender<Operations>
+) -> std::io::Result<()> {
+    let is_directory = tokio::fs::metadata(path).await?.is_dir();
+    let recursive = recursive && is_directory;
+    let base_name = path.file_name().map(PathBuf::from).unwrap_or_default();
+
+    let inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+
+    // Directory each watch descriptor reports on, relative to the watched path
+    let mut directories: HashMap<WatchDescriptor, PathBuf> = HashMap::new();
+    for relative_dir in watched_directories(path, recursive).await? {
+        let wd = watches.add(path.join(&relative_dir), watch_mask())?;
+        directories.insert(wd, relative_dir);
+    }
+
+    let mut events = inotify.into_event_stream(vec![0u8; 4096])?;
+    while let Some(event) = events.next().await {
+        let event = event?;
+        if event.mask.contains(EventMask::IGNORED) {
+            directories.remove(&event.wd);
+            continue;
+        }
+        let Some(directory) = directories.get(&event.wd) else { continue };
+
+        // Events about the watched path itself carry no name, Node reports its base name
+        let filename = match &event.name {
+            Some(name) => directory.join(name),
+            None if directory.as_os_str().is_empty() => base_name.clone(),
+            None => directory.clone(),
+        };
+
+        // New subdirectories are watched as well, they may already be gone again
+        let created = event.mask.intersects(EventMask::CREATE | EventMask::MOVED_TO);
+        if recursive && created && event.mask.contains(EventMask::ISDIR) {
+            if let Ok(new_directories) = watched_directories(&path.join(&filename), true).await {
+                for relative_dir in new_directories {
+                    let relative_dir = filename.join(relative_dir);
+                    if let Ok(wd) = watches.add(path.join(&relative_dir), watch_mask()) {
+                        directories.insert(wd, relative_dir);
+                    }
+                }
+            }
+        }
+
+        let event_type = if event.mask.intersects(EventMask::MODIFY | EventMask::ATTRIB) {
+            "change"
+        } else {
+            "rename"
+        };
+
+        let _ = tx.send(Operations::Watch(WatchEvent::Change {
+            emitter: emitter.clone(),
+            closed: closed.clone(),
+            event_type,
+            filename: filename.to_string_lossy().into_owned(),
+        }));
+    }
+
+    Ok(())
+}
+
+// fs.watchFile listeners by path, each polling on its own task
+pub struct StatWatcher {
+    listener: v8::Global<v8::Function>,
+    active: Arc<AtomicBool>,
+    task: JoinHandle<()>,
+}
+
+impl StatWatcher {
+    fn stop(&self) {
+        self.active.store(false, Ordering::SeqCst);
+        self.task.abort();
+    }
+}
+
+pub type StatWatchers = Mutex<HashMap<PathBuf, Vec<StatWatcher>>>;
+
+// Missing files are reported with zeroed stats, like Node does
+async fn stat_or_default(path: &Path) -> FileStats {
+    tokio::fs::metadata(path).await
+        .map(|metadata| FileStats::from_metadata(&metadata))
+        .unwrap_or_default()
+}
+
+fn stats_changed(current: &FileStats, previous: &FileStats) -> bool {
+    current.mtime_ms != previous.mtime_ms
+        || current.ctime_ms != previous.ctime_ms
+        || current.size != previous.size
+        || current.ino != previous.ino
+        || current.mode != previous.mode
+        || current.nlink != previous.nlink
+        || current.kind != previous.kind
+}
+
+async fn poll_stats(
+    path: PathBuf,
+    interval: Duration,
+    listener: v8::Global<v8::Function>,
+    active: Arc<AtomicBool>,
+    tx: UnboundedSender<Operations>
+) {
+    let mut previous = stat_or_default(&path).await;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let current = stat_or_default(&path).await;
+
+        if stats_changed(&current, &previous) {
+            let _ = tx.send(Operations::Watch(WatchEvent::StatChange {
+                listener: listener.clone(),
+                active: active.clone(),
+                current: current.clone(),
+                previous,
+            }));
+        }
+        previous = current;
+    }
+}
+
+pub fn handle_watch_event(scope: &mut v8::HandleScope, event: WatchEvent) {
+    match event {
+        WatchEvent::Change { emitter, closed, event_type, filename } => {
+            if closed.load(Ordering::SeqCst) {
+                return;
+            }
+            let event_type = v8::String::new(scope, event_type).unwrap();
+            let filename = v8::String::new(scope, &filename).unwrap();
+            emit_event(scope, &emitter, "change", &[event_type.into(), filename.into()]);
+        }
+
+        WatchEvent::Error { emitter, error_message } => {
+            let error_value = v8::String::new(scope, &error_message).unwrap();
+            emit_event(scope, &emitter, "error", &[error_value.into()]);
+        }
+
+        WatchEvent::Close { emitter } => {
+            emit_event(scope, &emitter, "close", &[]);
+        }
+
+        WatchEvent::StatChange { listener, active, current, previous } => {
+            if !active.load(Ordering::SeqCst) {
+                return;
+            }
+            let undefined = v8::undefined(scope).into();
+            let current = create_stats_object(scope, &current);
+            let previous = create_stats_object(scope, &previous);
+            let listener_fn = listener.open(scope);
+            listener_fn.call(scope, undefined, &[current.into(), previous.into()]);
+        }
+    }
+}
+
+fn get_watcher<'a>(scope: &mut v8::HandleScope, args: &v8::FunctionCallbackArguments) -> &'a FsWatcher {
+    let internal_field = args.this().get_internal_field(scope, 0).unwrap();
+    let external_watcher = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &*(external_watcher.value() as *const FsWatcher) }
+}
+
+fn watcher_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    get_watcher(scope, &args).close();
+}
+
+fn create_watcher_object<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    watcher: Box<FsWatcher>
+) -> v8::Local<'s, v8::Object> {
+    let watcher_template = v8::ObjectTemplate::new(scope);
+    watcher_template.set_internal_field_count(1); // Store the Rust FsWatcher struct internally
+    let watcher_obj = watcher_template.new_instance(scope).unwrap();
+
+    attach_event_emitter(scope, watcher_obj, &watcher.event_emitter);
+    set_function(scope, watcher_obj, "close", watcher_close_callback);
+
+    let external_watcher = v8::External::new(scope, Box::into_raw(watcher) as *const _ as *mut c_void);
+    watcher_obj.set_internal_field(0, external_watcher.into());
+
+    watcher_obj
+}
+
+fn get_options_and_listener<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments
+) -> (Option<v8::Local<'s, v8::Object>>, Option<v8::Local<'s, v8::Function>>) {
+    // (path, [options], [listener]), options may also be an encoding string
+    let (options, listener) = if args.get(1).is_function() {
+        (None, args.get(1))
+    } else {
+        (v8::Local::<v8::Object>::try_from(args.get(1)).ok(), args.get(2))
+    };
+
+    let options = options.map(|options| v8::Local::new(scope, options));
+    let listener = v8::Local::<v8::Function>::try_from(listener).ok()
+        .map(|listener| v8::Local::new(scope, listener));
+    (options, listener)
+}
+
+fn get_option<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    options: Option<v8::Local<v8::Object>>,
+    name: &str
+) -> Option<v8::Local<'s, v8::Value>> {
+    let options = options?;
+    let key = v8::String::new(scope, name).unwrap();
+    options.get(scope, key.into()).filter(|value| !value.is_undefined())
+}
+
+pub fn fs_watch_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let raw_ptr = retrieve_tx(scope, "channel").unwrap();
+    let tx = unsafe { &*raw_ptr };
+
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let (options, listener) = get_options_and_listener(scope, &args);
+    let recursive = get_option(scope, options, "recursive")
+        .map(|value| value.boolean_value(scope))
+        .unwrap_or(false);
+
+    let permissions = get_fs_instance(scope, &args).permissions.clone();
+    let watcher = FsWatcher::watch(PathBuf::from(path), recursive, permissions, tx.clone());
+    if let Some(listener) = listener {
+        let listener = v8::Global::new(scope, listener);
+        watcher.event_emitter.borrow_mut().on("change".to_string(), listener);
+    }
+
+    let watcher_obj = create_watcher_object(scope, Box::new(watcher));
+    rv.set(watcher_obj.into());
+}
+
+pub fn fs_watch_file_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let raw_ptr = retrieve_tx(scope, "channel").unwrap();
+    let tx = unsafe { &*raw_ptr };
+    let fs_ptr = get_fs_instance(scope, &args);
+
+    let path = PathBuf::from(args.get(0).to_rust_string_lossy(scope));
+    let (options, listener) = get_options_and_listener(scope, &args);
+    let Some(listener) = listener else {
+        eprintln!("Error: fs.watchFile listener must be a function");
+        return;
+    };
+
+    let interval = get_option(scope, options, "interval")
+        .and_then(|value| value.integer_value(scope))
+        .map(|interval| interval.max(1) as u64)
+        .unwrap_or(STAT_POLL_INTERVAL_MS);
+
+    // Polling has no way to report errors, so a denied path throws right away
+    if let Err(e) = fs_ptr.permissions.check_read(&path) {
+        throw_error(scope, &e.to_string());
+        return;
+    }
+
+    let listener = v8::Global::new(scope, listener);
+    let active = Arc::new(AtomicBool::new(true));
+    let task = tokio::task::spawn_local(poll_stats(
+        path.clone(),
+        Duration::from_millis(interval),
+        listener.clone(),
+        active.clone(),
+        tx.clone(),
+    ));
+
+    let watcher = StatWatcher { listener, active, task };
+    fs_ptr.stat_watchers.lock().unwrap().entry(path).or_default().push(watcher);
+}
+
+pub fn fs_unwatch_file_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _return_value: v8::ReturnValue,
+) {
+    let fs_ptr = get_fs_instance(scope, &args);
+    let path = PathBuf::from(args.get(0).to_rust_string_lossy(scope));
+    let listener = args.get(1);
+
+    let mut stat_watchers = fs_ptr.stat_watchers.lock().unwrap();
+    let Some(watchers) = stat_watchers.get_mut(&path) else { return };
+
+    // Without a listener every watcher of the path is removed
+    watchers.retain(|watcher| {
+        let remove = !listener.is_function()
+            || v8::Local::new(scope, &watcher.listener).strict_equals(listener);
+        if remove {
+            watcher.stop();
+        }
+        !remove
+    });
+
+    if watchers.is_empty() {
+        stat_watchers.remove(&path);
+    }
+}
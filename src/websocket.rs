@@ -0,0 +1,971 @@
+use rusty_v8 as v8;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::frame::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::WebSocketStream;
+use futures::{SinkExt, StreamExt};
+use http::HeaderValue;
+use url::Url;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::abort::abort_error;
+use crate::client::{string_option, tls_options};
+use crate::headers::is_token_byte;
+use crate::emitter::{attach_event_emitter, EventEmitter};
+use crate::helper::{bytes_to_uint8array, define_class, define_class_with_data, is_instance, new_instance, require_new, set_function, throw_error, throw_type_error, value_to_bytes};
+use crate::http::{Http, UpgradedConnection};
+use crate::interface::{HttpOperation, Operations, StreamEvent, WebSocketEvent};
+use crate::request::set_socket_address;
+use crate::stream::Chunk;
+use crate::tls::{Socket, TlsOptions};
+
+// readyState values, also exposed as WebSocket.CONNECTING and friends
+const CONNECTING: u16 = 0;
+const OPEN: u16 = 1;
+const CLOSING: u16 = 2;
+const CLOSED: u16 = 3;
+const READY_STATES: [(&str, u16); 4] = [("CONNECTING", CONNECTING), ("OPEN", OPEN), ("CLOSING", CLOSING), ("CLOSED", CLOSED)];
+
+// Control frames carry at most 125 bytes, a close reason 123 after its code
+const MAX_CONTROL_PAYLOAD: usize = 125;
+const MAX_CLOSE_REASON: usize = 123;
+
+enum SocketCommand {
+    Write(Vec<u8>, Option<v8::Global<v8::Function>>),
+    End,
+    Destroy,
+    // acceptWebSocket() takes the connection back from the socket's task
+    Take(oneshot::Sender<UpgradedConnection>),
+}
+
+// The raw connection passed to 'upgrade' listeners. It is a small duplex stream until
+// http.acceptWebSocket() takes it over
+pub struct UpgradedSocket {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    commands: UnboundedSender<SocketCommand>,
+    remote_address: Option<SocketAddr>,
+    ended: bool,
+    taken: bool,
+}
+
+impl UpgradedSocket {
+    pub fn new(connection: UpgradedConnection, remote_address: Option<SocketAddr>, tx: UnboundedSender<Operations>) -> Self {
+        let event_emitter = Rc::new(RefCell::new(EventEmitter::new()));
+        let (commands, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::task::spawn_local(run_upgraded_socket(connection, receiver, event_emitter.clone(), tx));
+
+        UpgradedSocket { event_emitter, commands, remote_address, ended: false, taken: false }
+    }
+
+    fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<(), String> {
+        if self.ended || self.taken {
+            return Err("write after end".to_string());
+        }
+        let _ = self.commands.send(SocketCommand::Write(data, callback));
+        Ok(())
+    }
+
+    fn end(&mut self) {
+        if !self.ended && !self.taken {
+            self.ended = true;
+            let _ = self.commands.send(SocketCommand::End);
+        }
+    }
+
+    fn destroy(&mut self) {
+        if !self.taken {
+            self.ended = true;
+            let _ = self.commands.send(SocketCommand::Destroy);
+        }
+    }
+
+    fn take(&mut self) -> Result<oneshot::Receiver<UpgradedConnection>, String> {
+        if self.ended || self.taken {
+            return Err("The socket was already ended or accepted".to_string());
+        }
+        self.taken = true;
+        let (sender, receiver) = oneshot::channel();
+        let _ = self.commands.send(SocketCommand::Take(sender));
+        Ok(receiver)
+    }
+}
+
+// Emits what the client sends as 'data' until the socket is taken, ended or destroyed.
+// Reads are dropped mid-way when a command arrives, which loses nothing
+async fn run_upgraded_socket(
+    connection: UpgradedConnection,
+    mut commands: UnboundedReceiver<SocketCommand>,
+    emitter: Rc<RefCell<EventEmitter>>,
+    tx: UnboundedSender<Operations>
+) {
+    let UpgradedConnection { mut reader, mut writer, head } = connection;
+    let mut chunk = vec![0u8; 16 * 1024];
+    let mut reading = true;
+    let mut ended = false;
+
+    let error_message = loop {
+        tokio::select! {
+            read = reader.read(&mut chunk), if reading => match read {
+                Ok(0) => {
+                    reading = false;
+                    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter: emitter.clone(), event: "end" }));
+                    if ended {
+                        break None;
+                    }
+                }
+                Ok(n) => {
+                    let op = StreamEvent::Data{ emitter: emitter.clone(), chunk: Chunk::Bytes(chunk[..n].to_vec()) };
+                    let _ = tx.send(Operations::Stream(op));
+                }
+                Err(e) => break Some(e.to_string()),
+            },
+            command = commands.recv() => match command {
+                Some(SocketCommand::Write(data, callback)) => {
+                    let written = match writer.write_all(&data).await {
+                        Ok(()) => writer.flush().await,
+                        Err(e) => Err(e),
+                    };
+                    let error_message = written.err().map(|e| e.to_string());
+                    if let Some(callback) = callback {
+                        let _ = tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message: error_message.clone() }));
+                    }
+                    if error_message.is_some() {
+                        break error_message;
+                    }
+                }
+                // Both sides are done once the client ended too
+                Some(SocketCommand::End) => {
+                    ended = true;
+                    let _ = writer.shutdown().await;
+                    if !reading {
+                        break None;
+                    }
+                }
+                // The new owner emits the events from now on
+                Some(SocketCommand::Take(sender)) => {
+                    let _ = sender.send(UpgradedConnection { reader, writer, head });
+                    return;
+                }
+                Some(SocketCommand::Destroy) | None => break None,
+            },
+        }
+    };
+
+    if let Some(error_message) = error_message {
+        let _ = tx.send(Operations::Stream(StreamEvent::Error{ emitter: emitter.clone(), error_message }));
+    }
+    let _ = tx.send(Operations::Stream(StreamEvent::Emit{ emitter, event: "close" }));
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BinaryType {
+    ArrayBuffer,
+    // Uint8Array, our stand-in for Node's Buffer
+    NodeBuffer,
+}
+
+enum WebSocketCommand {
+    // The message and its size, taken off bufferedAmount once it was sent
+    Send(Message, usize),
+    Ping(Vec<u8>),
+    Close(Option<(u16, String)>),
+}
+
+// The state behind a JS WebSocket, client or server side
+pub struct WebSocket {
+    url: String,
+    protocol: String,
+    ready_state: u16,
+    binary_type: BinaryType,
+    buffered_amount: Arc<AtomicUsize>,
+    commands: UnboundedSender<WebSocketCommand>,
+    // addEventListener() listeners by event type, run after the on<type> property
+    listeners: HashMap<String, Vec<v8::Global<v8::Function>>>,
+}
+
+impl WebSocket {
+    fn new(url: String, ready_state: u16, commands: UnboundedSender<WebSocketCommand>) -> Self {
+        WebSocket {
+            url,
+            protocol: String::new(),
+            ready_state,
+            binary_type: BinaryType::ArrayBuffer,
+            buffered_amount: Arc::new(AtomicUsize::new(0)),
+            commands,
+            listeners: HashMap::new(),
+        }
+    }
+
+    // Messages sent while the socket is closing are dropped, as in browsers
+    fn send(&mut self, message: Message, size: usize) -> Result<(), String> {
+        match self.ready_state {
+            CONNECTING => Err("Still in CONNECTING state.".to_string()),
+            OPEN => {
+                self.buffered_amount.fetch_add(size, Ordering::SeqCst);
+                let _ = self.commands.send(WebSocketCommand::Send(message, size));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn ping(&mut self, data: Vec<u8>) -> Result<(), String> {
+        if self.ready_state != OPEN {
+            return Err("WebSocket is not open".to_string());
+        }
+        let _ = self.commands.send(WebSocketCommand::Ping(data));
+        Ok(())
+    }
+
+    // A socket that is still connecting gives up, an open one starts the closing handshake
+    fn close(&mut self, frame: Option<(u16, String)>) {
+        if matches!(self.ready_state, CONNECTING | OPEN) {
+            self.ready_state = CLOSING;
+            let _ = self.commands.send(WebSocketCommand::Close(frame));
+        }
+    }
+
+    fn binary_value<'s>(&self, scope: &mut v8::HandleScope<'s>, bytes: Vec<u8>) -> v8::Local<'s, v8::Value> {
+        let array = bytes_to_uint8array(scope, bytes);
+        match self.binary_type {
+            BinaryType::ArrayBuffer => array.buffer(scope).unwrap().into(),
+            BinaryType::NodeBuffer => array.into(),
+        }
+    }
+}
+
+// Sends the events of one WebSocket to the event loop
+struct WebSocketEvents {
+    socket: v8::Global<v8::Object>,
+    tx: UnboundedSender<Operations>,
+}
+
+impl WebSocketEvents {
+    fn send(&self, event: WebSocketEvent) {
+        let op = HttpOperation::WebSocket { socket: self.socket.clone(), event };
+        let _ = self.tx.send(Operations::Http(op));
+    }
+
+    // The connection failed or broke: 'error', then a 'close' that was not clean
+    fn fail(&self, error_message: String) {
+        self.send(WebSocketEvent::Error { error_message });
+        self.send(WebSocketEvent::Close { code: 1006, reason: String::new(), was_clean: false });
+    }
+}
+
+async fn connect_websocket(
+    url: &Url,
+    tls: Option<&TlsOptions>,
+    protocols: &[String]
+) -> Result<(WebSocketStream<Socket>, String), String> {
+    let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+    let socket = TcpStream::connect((host, port)).await.map_err(|e| e.to_string())?;
+    let _ = socket.set_nodelay(true);
+
+    let socket = match tls {
+        Some(tls) => Socket::Tls(Box::new(tls.connect(host, socket).await.map_err(|e| e.to_string())?)),
+        None => Socket::Plain(socket),
+    };
+
+    let mut request = url.as_str().into_client_request().map_err(|e| e.to_string())?;
+    if !protocols.is_empty() {
+        let protocols = HeaderValue::from_str(&protocols.join(", ")).map_err(|e| e.to_string())?;
+        request.headers_mut().insert("Sec-WebSocket-Protocol", protocols);
+    }
+
+    let (stream, response) = tokio_tungstenite::client_async(request, socket).await.map_err(|e| e.to_string())?;
+    let protocol = response.headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|protocol| protocol.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    Ok((stream, protocol))
+}
+
+// Connects and runs the handshake, close() before it finished abandons the connection
+async fn run_client(
+    url: Url,
+    tls: Option<TlsOptions>,
+    protocols: Vec<String>,
+    mut commands: UnboundedReceiver<WebSocketCommand>,
+    buffered_amount: Arc<AtomicUsize>,
+    events: WebSocketEvents
+) {
+    let connected = tokio::select! {
+        connected = connect_websocket(&url, tls.as_ref(), &protocols) => connected,
+        _ = commands.recv() => Err("WebSocket was closed before the connection was established".to_string()),
+    };
+    match connected {
+        Ok((stream, protocol)) => {
+            events.send(WebSocketEvent::Open { protocol });
+            run_websocket(stream, commands, buffered_amount, events).await;
+        }
+        Err(error_message) => events.fail(error_message),
+    }
+}
+
+// Answers the handshake on the connection taken from the upgraded socket, then runs the
+// server side of the WebSocket. Bytes the client sent past its request head are kept
+async fn run_server(
+    taken: oneshot::Receiver<UpgradedConnection>,
+    handshake: String,
+    commands: UnboundedReceiver<WebSocketCommand>,
+    buffered_amount: Arc<AtomicUsize>,
+    events: WebSocketEvents
+) {
+    let Ok(UpgradedConnection { reader, mut writer, head }) = taken.await else {
+        events.fail("The socket closed before the WebSocket was accepted".to_string());
+        return;
+    };
+
+    let written = match writer.write_all(handshake.as_bytes()).await {
+        Ok(()) => writer.flush().await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = written {
+        events.fail(e.to_string());
+        return;
+    }
+
+    let stream = WebSocketStream::from_partially_read(tokio::io::join(reader, writer), head, Role::Server, None).await;
+    run_websocket(stream, commands, buffered_amount, events).await;
+}
+
+// Turns incoming frames into events and sends what JS queued. tungstenite answers pings and
+// close frames itself, the stream ends once the closing handshake is complete
+async fn run_websocket<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: WebSocketStream<S>,
+    mut commands: UnboundedReceiver<WebSocketCommand>,
+    buffered_amount: Arc<AtomicUsize>,
+    events: WebSocketEvents
+) {
+    // The code and reason of the peer's close frame, 1005 when it had none
+    let mut close_frame = None;
+
+    loop {
+        tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => events.send(WebSocketEvent::Message(Chunk::Text(text))),
+                Some(Ok(Message::Binary(data))) => events.send(WebSocketEvent::Message(Chunk::Bytes(data))),
+                Some(Ok(Message::Ping(data))) => events.send(WebSocketEvent::Ping(data)),
+                Some(Ok(Message::Pong(data))) => events.send(WebSocketEvent::Pong(data)),
+                Some(Ok(Message::Close(frame))) => {
+                    close_frame = Some(frame.map(|frame| (u16::from(frame.code), frame.reason.into_owned())).unwrap_or((1005, String::new())));
+                }
+                Some(Ok(Message::Frame(_))) => {}
+                Some(Err(WsError::ConnectionClosed)) | None => break,
+                Some(Err(e)) => {
+                    events.fail(e.to_string());
+                    return;
+                }
+            },
+            Some(command) = commands.recv() => {
+                let result = match command {
+                    WebSocketCommand::Send(message, size) => {
+                        let result = stream.send(message).await;
+                        buffered_amount.fetch_sub(size, Ordering::SeqCst);
+                        result
+                    }
+                    WebSocketCommand::Ping(data) => stream.send(Message::Ping(data)).await,
+                    WebSocketCommand::Close(frame) => {
+                        let frame = frame.map(|(code, reason)| CloseFrame { code: code.into(), reason: reason.into() });
+                        stream.close(frame).await
+                    }
+                };
+                match result {
+                    Ok(()) | Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => {}
+                    Err(e) => {
+                        events.fail(e.to_string());
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    let (code, reason) = close_frame.unwrap_or((1005, String::new()));
+    events.send(WebSocketEvent::Close { code, reason, was_clean: true });
+}
+
+// Dispatch from the event loop. The event object is passed to the on<type> property, then to
+// the listeners, with the socket as `this`
+pub fn handle_websocket_event(scope: &mut v8::HandleScope, socket: v8::Global<v8::Object>, event: WebSocketEvent) {
+    let socket_obj = v8::Local::new(scope, socket);
+    let websocket = get_websocket(scope, socket_obj);
+    let event_obj = v8::Object::new(scope);
+
+    let mut fields: Vec<(&str, v8::Local<v8::Value>)> = Vec::new();
+    let event_type = match event {
+        WebSocketEvent::Open { protocol } => {
+            websocket.ready_state = OPEN;
+            websocket.protocol = protocol;
+            "open"
+        }
+        WebSocketEvent::Message(Chunk::Text(text)) => {
+            fields.push(("data", v8::String::new(scope, &text).unwrap().into()));
+            "message"
+        }
+        WebSocketEvent::Message(Chunk::Bytes(bytes)) => {
+            fields.push(("data", websocket.binary_value(scope, bytes)));
+            "message"
+        }
+        WebSocketEvent::Ping(data) => {
+            fields.push(("data", websocket.binary_value(scope, data)));
+            "ping"
+        }
+        WebSocketEvent::Pong(data) => {
+            fields.push(("data", websocket.binary_value(scope, data)));
+            "pong"
+        }
+        WebSocketEvent::Error { error_message } => {
+            fields.push(("message", v8::String::new(scope, &error_message).unwrap().into()));
+            "error"
+        }
+        WebSocketEvent::Close { code, reason, was_clean } => {
+            websocket.ready_state = CLOSED;
+            fields.push(("code", v8::Integer::new(scope, code as i32).into()));
+            fields.push(("reason", v8::String::new(scope, &reason).unwrap().into()));
+            fields.push(("wasClean", v8::Boolean::new(scope, was_clean).into()));
+            "close"
+        }
+    };
+    fields.push(("type", v8::String::new(scope, event_type).unwrap().into()));
+    fields.push(("target", socket_obj.into()));
+    for (name, value) in fields {
+        let key = v8::String::new(scope, name).unwrap();
+        event_obj.set(scope, key.into(), value);
+    }
+
+    let handler_key = v8::String::new(scope, &format!("on{}", event_type)).unwrap();
+    let handler = socket_obj.get(scope, handler_key.into())
+        .and_then(|handler| v8::Local::<v8::Function>::try_from(handler).ok());
+    let mut callbacks: Vec<v8::Local<v8::Function>> = handler.into_iter().collect();
+    if let Some(listeners) = websocket.listeners.get(event_type) {
+        callbacks.extend(listeners.iter().map(|listener| v8::Local::new(scope, listener)));
+    }
+    for callback in callbacks {
+        callback.call(scope, socket_obj.into(), &[event_obj.into()]);
+    }
+}
+
+// The value of Sec-WebSocket-Protocol, which lists tokens once each
+fn parse_protocols(scope: &mut v8::HandleScope, value: v8::Local<v8::Value>) -> Result<Vec<String>, String> {
+    let protocols: Vec<String> = if let Ok(array) = v8::Local::<v8::Array>::try_from(value) {
+        (0..array.length())
+            .map(|i| array.get_index(scope, i).unwrap().to_rust_string_lossy(scope))
+            .collect()
+    } else if value.is_undefined() {
+        Vec::new()
+    } else {
+        vec![value.to_rust_string_lossy(scope)]
+    };
+
+    for (i, protocol) in protocols.iter().enumerate() {
+        if protocol.is_empty() || !protocol.bytes().all(is_token_byte) {
+            return Err(format!("The subprotocol '{}' is invalid.", protocol));
+        }
+        if protocols[..i].contains(protocol) {
+            return Err(format!("The subprotocol '{}' is duplicated.", protocol));
+        }
+    }
+    Ok(protocols)
+}
+
+// What browsers throw, a DOMException with a name
+fn throw_dom_exception(scope: &mut v8::HandleScope, name: &str, message: &str) {
+    let exception = abort_error(scope, name, message);
+    scope.throw_exception(exception);
+}
+
+// V8 Callbacks
+fn get_websocket<'a>(scope: &mut v8::HandleScope, socket_obj: v8::Local<v8::Object>) -> &'a mut WebSocket {
+    let internal_field = socket_obj.get_internal_field(scope, 0).unwrap();
+    let external_websocket = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_websocket.value() as *mut WebSocket) }
+}
+
+fn get_upgraded_socket<'a>(scope: &mut v8::HandleScope, socket_obj: v8::Local<v8::Object>) -> &'a mut UpgradedSocket {
+    let internal_field = socket_obj.get_internal_field(scope, 0).unwrap();
+    let external_socket = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_socket.value() as *mut UpgradedSocket) }
+}
+
+fn websocket_url_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    rv.set(v8::String::new(scope, &websocket.url).unwrap().into());
+}
+
+fn websocket_protocol_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    rv.set(v8::String::new(scope, &websocket.protocol).unwrap().into());
+}
+
+fn websocket_ready_state_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    rv.set(v8::Integer::new(scope, websocket.ready_state as i32).into());
+}
+
+fn websocket_buffered_amount_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let buffered_amount = websocket.buffered_amount.load(Ordering::SeqCst);
+    rv.set(v8::Number::new(scope, buffered_amount as f64).into());
+}
+
+fn websocket_binary_type_getter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    args: v8::PropertyCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let binary_type = match websocket.binary_type {
+        BinaryType::ArrayBuffer => "arraybuffer",
+        BinaryType::NodeBuffer => "nodebuffer",
+    };
+    rv.set(v8::String::new(scope, binary_type).unwrap().into());
+}
+
+// Other values are ignored, as browsers do
+fn websocket_binary_type_setter(
+    scope: &mut v8::HandleScope,
+    _name: v8::Local<v8::Name>,
+    value: v8::Local<v8::Value>,
+    args: v8::PropertyCallbackArguments,
+) {
+    let websocket = get_websocket(scope, args.this());
+    match value.to_rust_string_lossy(scope).as_str() {
+        "arraybuffer" => websocket.binary_type = BinaryType::ArrayBuffer,
+        "nodebuffer" => websocket.binary_type = BinaryType::NodeBuffer,
+        _ => {}
+    }
+}
+
+// send(data), strings go out as text messages, ArrayBuffers and typed arrays as binary ones
+fn websocket_send_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let data = args.get(0);
+    let (message, size) = if data.is_array_buffer() || data.is_array_buffer_view() {
+        let bytes = value_to_bytes(scope, data);
+        let size = bytes.len();
+        (Message::Binary(bytes), size)
+    } else {
+        let text = data.to_rust_string_lossy(scope);
+        let size = text.len();
+        (Message::Text(text), size)
+    };
+
+    if let Err(e) = websocket.send(message, size) {
+        throw_dom_exception(scope, "InvalidStateError", &format!("Failed to execute 'send' on 'WebSocket': {}", e));
+    }
+}
+
+// close([code], [reason]), scripts may only send 1000 or a code from 3000 to 4999
+fn websocket_close_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+
+    let code = match args.get(0).is_undefined() {
+        true => None,
+        false => Some(args.get(0).integer_value(scope).unwrap_or(0)),
+    };
+    if let Some(code) = code.filter(|code| *code != 1000 && !(3000..=4999).contains(code)) {
+        let message = format!("The code must be either 1000, or between 3000 and 4999. {} is neither.", code);
+        throw_dom_exception(scope, "InvalidAccessError", &message);
+        return;
+    }
+    let reason = match args.get(1).is_undefined() {
+        true => String::new(),
+        false => args.get(1).to_rust_string_lossy(scope),
+    };
+    if reason.len() > MAX_CLOSE_REASON {
+        throw_dom_exception(scope, "SyntaxError", "The close reason must not be greater than 123 UTF-8 bytes.");
+        return;
+    }
+
+    // A reason needs a code to travel with
+    let code = code.or(if reason.is_empty() { None } else { Some(1000) });
+    websocket.close(code.map(|code| (code as u16, reason)));
+}
+
+// ping([data]), not in browsers. The answer arrives as a 'pong' event
+fn websocket_ping_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let data = match args.get(0).is_undefined() {
+        true => Vec::new(),
+        false => value_to_bytes(scope, args.get(0)),
+    };
+    if data.len() > MAX_CONTROL_PAYLOAD {
+        throw_error(scope, "The ping payload must not be greater than 125 bytes");
+        return;
+    }
+    if let Err(e) = websocket.ping(data) {
+        throw_dom_exception(scope, "InvalidStateError", &e);
+    }
+}
+
+fn websocket_add_event_listener_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let event_type = args.get(0).to_rust_string_lossy(scope);
+    let Ok(listener) = v8::Local::<v8::Function>::try_from(args.get(1)) else {
+        return;
+    };
+
+    let listeners = websocket.listeners.entry(event_type).or_default();
+    if !listeners.iter().any(|registered| v8::Local::new(scope, registered).strict_equals(listener.into())) {
+        listeners.push(v8::Global::new(scope, listener));
+    }
+}
+
+fn websocket_remove_event_listener_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let websocket = get_websocket(scope, args.this());
+    let event_type = args.get(0).to_rust_string_lossy(scope);
+    let Ok(listener) = v8::Local::<v8::Function>::try_from(args.get(1)) else {
+        return;
+    };
+
+    if let Some(listeners) = websocket.listeners.get_mut(&event_type) {
+        listeners.retain(|registered| !v8::Local::new(scope, registered).strict_equals(listener.into()));
+    }
+}
+
+// A WebSocket object whose task is started by the caller, which needs the object first
+fn create_websocket_object<'s>(scope: &mut v8::HandleScope<'s>, websocket: Box<WebSocket>) -> v8::Local<'s, v8::Object> {
+    let socket_obj = new_instance(scope, "WebSocket");
+
+    let url_key = v8::String::new(scope, "url").unwrap();
+    let protocol_key = v8::String::new(scope, "protocol").unwrap();
+    let ready_state_key = v8::String::new(scope, "readyState").unwrap();
+    let buffered_amount_key = v8::String::new(scope, "bufferedAmount").unwrap();
+    let binary_type_key = v8::String::new(scope, "binaryType").unwrap();
+    socket_obj.set_accessor(scope, url_key.into(), websocket_url_getter);
+    socket_obj.set_accessor(scope, protocol_key.into(), websocket_protocol_getter);
+    socket_obj.set_accessor(scope, ready_state_key.into(), websocket_ready_state_getter);
+    socket_obj.set_accessor(scope, buffered_amount_key.into(), websocket_buffered_amount_getter);
+    socket_obj.set_accessor_with_setter(scope, binary_type_key.into(), websocket_binary_type_getter, websocket_binary_type_setter);
+
+    for handler in ["onopen", "onmessage", "onerror", "onclose", "onping", "onpong"] {
+        let handler_key = v8::String::new(scope, handler).unwrap();
+        let null_value = v8::null(scope);
+        socket_obj.set(scope, handler_key.into(), null_value.into());
+    }
+
+    let external_websocket = v8::External::new(scope, Box::into_raw(websocket) as *mut c_void);
+    socket_obj.set_internal_field(0, external_websocket.into());
+    socket_obj
+}
+
+// new WebSocket(url, [protocols]). Instead of the protocols, the second argument may be an
+// object with `protocols` and the `ca` and `rejectUnauthorized` TLS options of https.request
+fn websocket_constructor(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    if !require_new(scope, &args, "WebSocket") {
+        return;
+    }
+    let external_http = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let http = unsafe { &*(external_http.value() as *const Http) };
+
+    let url_value = args.get(0).to_rust_string_lossy(scope);
+    let mut url = match Url::parse(&url_value) {
+        Ok(url) if url.host_str().is_some() => url,
+        _ => {
+            throw_dom_exception(scope, "SyntaxError", &format!("The URL '{}' is invalid.", url_value));
+            return;
+        }
+    };
+    let secure = match url.scheme() {
+        "ws" | "http" => false,
+        "wss" | "https" => true,
+        scheme => {
+            let message = format!("The URL's scheme must be either 'http', 'https', 'ws', or 'wss'. '{}' is not allowed.", scheme);
+            throw_dom_exception(scope, "SyntaxError", &message);
+            return;
+        }
+    };
+    if url.fragment().is_some() {
+        throw_dom_exception(scope, "SyntaxError", "The URL contains a fragment identifier.");
+        return;
+    }
+    let _ = url.set_scheme(if secure { "wss" } else { "ws" });
+
+    let options = v8::Local::<v8::Object>::try_from(args.get(1)).ok()
+        .filter(|_| !args.get(1).is_array() && !args.get(1).is_string());
+    let protocols_value = match options {
+        Some(options) => {
+            let protocols_key = v8::String::new(scope, "protocols").unwrap();
+            options.get(scope, protocols_key.into()).unwrap()
+        }
+        None => args.get(1),
+    };
+    let protocols = match parse_protocols(scope, protocols_value) {
+        Ok(protocols) => protocols,
+        Err(e) => {
+            throw_dom_exception(scope, "SyntaxError", &e);
+            return;
+        }
+    };
+
+    let tls = match (secure, options) {
+        (true, Some(options)) => tls_options(scope, options).map(Some),
+        (true, None) => Ok(Some(TlsOptions::default())),
+        (false, _) => Ok(None),
+    };
+    let tls = match tls {
+        Ok(tls) => tls,
+        Err(e) => {
+            throw_type_error(scope, &e);
+            return;
+        }
+    };
+
+    let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+    if let Err(e) = http.permissions.check_net(&host, port) {
+        throw_error(scope, &e.to_string());
+        return;
+    }
+
+    let (commands, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let websocket = WebSocket::new(url.to_string(), CONNECTING, commands);
+    let buffered_amount = websocket.buffered_amount.clone();
+    let socket_obj = create_websocket_object(scope, Box::new(websocket));
+
+    let events = WebSocketEvents { socket: v8::Global::new(scope, socket_obj), tx: http.tx.clone() };
+    tokio::task::spawn_local(run_client(url, tls, protocols, receiver, buffered_amount, events));
+    rv.set(socket_obj.into());
+}
+
+// Upgraded sockets only come from 'upgrade' events
+fn upgraded_socket_constructor(
+    scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    throw_type_error(scope, "Illegal constructor");
+}
+
+// socket.write(data, [encoding], [callback])
+fn upgraded_socket_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let socket = get_upgraded_socket(scope, args.this());
+    let data = value_to_bytes(scope, args.get(0));
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match socket.write(data, callback) {
+        Ok(()) => rv.set(v8::Boolean::new(scope, true).into()),
+        Err(e) => throw_error(scope, &e),
+    }
+}
+
+// socket.end([data]), closes the writable side once the data was written
+fn upgraded_socket_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let socket = get_upgraded_socket(scope, args.this());
+    if !args.get(0).is_undefined() && !args.get(0).is_function() {
+        let data = value_to_bytes(scope, args.get(0));
+        let _ = socket.write(data, None);
+    }
+    socket.end();
+    rv.set(args.this().into());
+}
+
+fn upgraded_socket_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let socket = get_upgraded_socket(scope, args.this());
+    socket.destroy();
+    rv.set(args.this().into());
+}
+
+pub fn create_upgraded_socket_object<'s>(scope: &mut v8::HandleScope<'s>, socket: Box<UpgradedSocket>) -> v8::Local<'s, v8::Object> {
+    let socket_obj = new_instance(scope, "UpgradedSocket");
+    attach_event_emitter(scope, socket_obj, &socket.event_emitter);
+    if let Some(remote_address) = socket.remote_address {
+        set_socket_address(scope, socket_obj, remote_address);
+    }
+
+    let external_socket = v8::External::new(scope, Box::into_raw(socket) as *mut c_void);
+    socket_obj.set_internal_field(0, external_socket.into());
+    socket_obj
+}
+
+// http.acceptWebSocket(req, socket, [options]), completes the handshake of an 'upgrade'
+// request and returns an open WebSocket. options.protocol is the subprotocol to answer with
+fn accept_websocket_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let external_http = v8::Local::<v8::External>::try_from(args.data().unwrap()).unwrap();
+    let http = unsafe { &*(external_http.value() as *const Http) };
+
+    let Ok(request_obj) = v8::Local::<v8::Object>::try_from(args.get(0)) else {
+        throw_type_error(scope, "The \"req\" argument must be the request of an 'upgrade' event");
+        return;
+    };
+    if !is_instance(scope, args.get(1), "UpgradedSocket") {
+        throw_type_error(scope, "The \"socket\" argument must be the socket of an 'upgrade' event");
+        return;
+    }
+    let socket_obj = v8::Local::<v8::Object>::try_from(args.get(1)).unwrap();
+
+    let headers_key = v8::String::new(scope, "headers").unwrap();
+    let headers = request_obj.get(scope, headers_key.into())
+        .and_then(|headers| v8::Local::<v8::Object>::try_from(headers).ok());
+    let Some(headers) = headers else {
+        throw_type_error(scope, "The \"req\" argument must be the request of an 'upgrade' event");
+        return;
+    };
+    let upgrade = string_option(scope, headers, "upgrade").unwrap_or_default();
+    let version = string_option(scope, headers, "sec-websocket-version").unwrap_or_default();
+    let key = string_option(scope, headers, "sec-websocket-key").unwrap_or_default();
+    if !upgrade.split(',').any(|protocol| protocol.trim().eq_ignore_ascii_case("websocket")) {
+        throw_error(scope, "Invalid WebSocket handshake: the Upgrade header is not 'websocket'");
+        return;
+    }
+    if version.trim() != "13" {
+        throw_error(scope, "Invalid WebSocket handshake: unsupported Sec-WebSocket-Version");
+        return;
+    }
+    if key.trim().is_empty() {
+        throw_error(scope, "Invalid WebSocket handshake: missing Sec-WebSocket-Key");
+        return;
+    }
+
+    let protocol = v8::Local::<v8::Object>::try_from(args.get(2)).ok()
+        .and_then(|options| string_option(scope, options, "protocol"))
+        .unwrap_or_default();
+    if !protocol.bytes().all(is_token_byte) {
+        throw_type_error(scope, &format!("Invalid subprotocol \"{}\"", protocol));
+        return;
+    }
+
+    let socket = get_upgraded_socket(scope, socket_obj);
+    let taken = match socket.take() {
+        Ok(taken) => taken,
+        Err(e) => {
+            throw_error(scope, &e);
+            return;
+        }
+    };
+
+    let mut handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n",
+        derive_accept_key(key.trim().as_bytes())
+    );
+    if !protocol.is_empty() {
+        handshake.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", protocol));
+    }
+    handshake.push_str("\r\n");
+
+    let url_key = v8::String::new(scope, "url").unwrap();
+    let url = request_obj.get(scope, url_key.into()).unwrap().to_rust_string_lossy(scope);
+    let (commands, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut websocket = WebSocket::new(url, OPEN, commands);
+    websocket.protocol = protocol;
+    let buffered_amount = websocket.buffered_amount.clone();
+    let websocket_obj = create_websocket_object(scope, Box::new(websocket));
+
+    let events = WebSocketEvents { socket: v8::Global::new(scope, websocket_obj), tx: http.tx.clone() };
+    tokio::task::spawn_local(run_server(taken, handshake, receiver, buffered_amount, events));
+    rv.set(websocket_obj.into());
+}
+
+pub fn initialize_websocket<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    http_obj: v8::Local<'s, v8::Object>,
+    external_http: v8::Local<'s, v8::External>
+) {
+    let global = scope.get_current_context().global(scope);
+    let (websocket_class, websocket_prototype) = define_class_with_data(scope, global, "WebSocket", websocket_constructor, external_http.into());
+    set_function(scope, websocket_prototype, "send", websocket_send_callback);
+    set_function(scope, websocket_prototype, "close", websocket_close_callback);
+    set_function(scope, websocket_prototype, "ping", websocket_ping_callback);
+    set_function(scope, websocket_prototype, "addEventListener", websocket_add_event_listener_callback);
+    set_function(scope, websocket_prototype, "removeEventListener", websocket_remove_event_listener_callback);
+    for (name, value) in READY_STATES {
+        let key = v8::String::new(scope, name).unwrap();
+        let value = v8::Integer::new(scope, value as i32);
+        websocket_class.set(scope, key.into(), value.into());
+        websocket_prototype.set(scope, key.into(), value.into());
+    }
+
+    // Not a global, the class only gives the sockets of 'upgrade' events their methods
+    let classes = v8::Object::new(scope);
+    let (_, socket_prototype) = define_class(scope, classes, "UpgradedSocket", upgraded_socket_constructor);
+    set_function(scope, socket_prototype, "write", upgraded_socket_write_callback);
+    set_function(scope, socket_prototype, "end", upgraded_socket_end_callback);
+    set_function(scope, socket_prototype, "destroy", upgraded_socket_destroy_callback);
+
+    let accept_fn = v8::Function::builder(accept_websocket_callback)
+        .data(external_http.into())
+        .build(scope)
+        .unwrap();
+    let accept_key = v8::String::new(scope, "acceptWebSocket").unwrap();
+    http_obj.set(scope, accept_key.into(), accept_fn.into());
+}
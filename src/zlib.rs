@@ -0,0 +1,482 @@
+use rusty_v8 as v8;
+use tokio::sync::mpsc::UnboundedSender;
+use flate2::write::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use brotli::{CompressorWriter, DecompressorWriter};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::io::{self, Write};
+
+use crate::emitter::{attach_event_emitter, EventEmitter};
+use crate::headers::Headers;
+use crate::helper::{bytes_to_uint8array, retrieve_tx, set_function, throw_error, value_to_bytes};
+use crate::interface::{Operations, StreamEvent, ZlibOperation};
+use crate::stream::{stream_pipe_callback, Chunk};
+
+// Ids in options.params, the rest of zlib.constants is only informative
+const BROTLI_PARAM_QUALITY: u32 = 1;
+const BROTLI_PARAM_LGWIN: u32 = 2;
+const CONSTANTS: [(&str, i32); 11] = [
+    ("Z_NO_COMPRESSION", 0),
+    ("Z_BEST_SPEED", 1),
+    ("Z_BEST_COMPRESSION", 9),
+    ("Z_DEFAULT_COMPRESSION", -1),
+    ("BROTLI_PARAM_QUALITY", BROTLI_PARAM_QUALITY as i32),
+    ("BROTLI_PARAM_LGWIN", BROTLI_PARAM_LGWIN as i32),
+    ("BROTLI_MIN_QUALITY", 0),
+    ("BROTLI_MAX_QUALITY", 11),
+    ("BROTLI_DEFAULT_QUALITY", 11),
+    ("BROTLI_MIN_WINDOW_BITS", 10),
+    ("BROTLI_MAX_WINDOW_BITS", 24),
+];
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+// Each format gets a sync, a callback and a stream function, e.g. gzipSync, gzip and createGzip
+const FORMATS: [(&str, &str, Format); 6] = [
+    ("gzip", "Gzip", Format::Gzip),
+    ("gunzip", "Gunzip", Format::Gunzip),
+    ("deflate", "Deflate", Format::Deflate),
+    ("inflate", "Inflate", Format::Inflate),
+    ("brotliCompress", "BrotliCompress", Format::BrotliCompress),
+    ("brotliDecompress", "BrotliDecompress", Format::BrotliDecompress),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Gzip,
+    Gunzip,
+    // zlib-wrapped deflate, what Content-Encoding: deflate means
+    Deflate,
+    Inflate,
+    BrotliCompress,
+    BrotliDecompress,
+}
+
+// options.level for gzip and deflate, options.params for brotli
+#[derive(Clone, Copy)]
+pub struct CodecOptions {
+    level: Compression,
+    quality: u32,
+    window: u32,
+}
+
+impl Default for CodecOptions {
+    fn default() -> Self {
+        CodecOptions { level: Compression::default(), quality: 11, window: 22 }
+    }
+}
+
+impl CodecOptions {
+    fn from_js(scope: &mut v8::HandleScope, options: v8::Local<v8::Value>) -> Result<Self, String> {
+        let mut codec_options = CodecOptions::default();
+        let Ok(options) = v8::Local::<v8::Object>::try_from(options) else {
+            return Ok(codec_options);
+        };
+
+        let level_key = v8::String::new(scope, "level").unwrap();
+        let level = options.get(scope, level_key.into()).unwrap();
+        if !level.is_undefined() {
+            match level.integer_value(scope) {
+                Some(-1) => {}
+                Some(level @ 0..=9) => codec_options.level = Compression::new(level as u32),
+                _ => return Err(format!("The value of \"options.level\" is out of range. It must be >= -1 and <= 9. Received {}", level.to_rust_string_lossy(scope))),
+            }
+        }
+
+        let params_key = v8::String::new(scope, "params").unwrap();
+        let params = options.get(scope, params_key.into()).unwrap();
+        if let Ok(params) = v8::Local::<v8::Object>::try_from(params) {
+            let mut param = |id: u32| {
+                let value = params.get_index(scope, id)?;
+                if value.is_undefined() { None } else { value.integer_value(scope) }
+            };
+            if let Some(quality) = param(BROTLI_PARAM_QUALITY) {
+                if !(0..=11).contains(&quality) {
+                    return Err(format!("Invalid brotli quality {}, it must be >= 0 and <= 11", quality));
+                }
+                codec_options.quality = quality as u32;
+            }
+            if let Some(window) = param(BROTLI_PARAM_LGWIN) {
+                if !(10..=24).contains(&window) {
+                    return Err(format!("Invalid brotli window {}, it must be >= 10 and <= 24", window));
+                }
+                codec_options.window = window as u32;
+            }
+        }
+        Ok(codec_options)
+    }
+}
+
+// One format in one direction. Data is written in pieces and what came out so far is taken
+// after each piece, so the same codec serves whole buffers and streams
+pub enum Codec {
+    Gzip(GzEncoder<Vec<u8>>),
+    Gunzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+    Inflate(ZlibDecoder<Vec<u8>>),
+    BrotliCompress(Box<CompressorWriter<Vec<u8>>>),
+    BrotliDecompress(Box<DecompressorWriter<Vec<u8>>>),
+}
+
+impl Codec {
+    pub fn new(format: Format, options: CodecOptions) -> Self {
+        match format {
+            Format::Gzip => Codec::Gzip(GzEncoder::new(Vec::new(), options.level)),
+            Format::Gunzip => Codec::Gunzip(GzDecoder::new(Vec::new())),
+            Format::Deflate => Codec::Deflate(ZlibEncoder::new(Vec::new(), options.level)),
+            Format::Inflate => Codec::Inflate(ZlibDecoder::new(Vec::new())),
+            Format::BrotliCompress => Codec::BrotliCompress(Box::new(CompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE, options.quality, options.window))),
+            Format::BrotliDecompress => Codec::BrotliDecompress(Box::new(DecompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE))),
+        }
+    }
+
+    // The decoder for a response body, None for identity, several codings or unknown ones
+    pub fn for_content_encoding(headers: &Headers) -> Option<Self> {
+        let encoding = headers.get_joined("Content-Encoding")?.trim().to_ascii_lowercase();
+        let format = match encoding.as_str() {
+            "gzip" | "x-gzip" => Format::Gunzip,
+            "deflate" => Format::Inflate,
+            "br" => Format::BrotliDecompress,
+            _ => return None,
+        };
+        Some(Codec::new(format, CodecOptions::default()))
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Codec::Gzip(writer) => writer,
+            Codec::Gunzip(writer) => writer,
+            Codec::Deflate(writer) => writer,
+            Codec::Inflate(writer) => writer,
+            Codec::BrotliCompress(writer) => writer.as_mut(),
+            Codec::BrotliDecompress(writer) => writer.as_mut(),
+        }
+    }
+
+    fn output(&mut self) -> &mut Vec<u8> {
+        match self {
+            Codec::Gzip(writer) => writer.get_mut(),
+            Codec::Gunzip(writer) => writer.get_mut(),
+            Codec::Deflate(writer) => writer.get_mut(),
+            Codec::Inflate(writer) => writer.get_mut(),
+            Codec::BrotliCompress(writer) => writer.get_mut(),
+            Codec::BrotliDecompress(writer) => writer.get_mut(),
+        }
+    }
+
+    // Returns the output produced so far, compressors hold back data until they have enough
+    pub fn write(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.writer().write_all(data)?;
+        Ok(std::mem::take(self.output()))
+    }
+
+    // The rest of the output. Decoders fail when the data ended early
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Gzip(writer) => writer.finish(),
+            Codec::Gunzip(writer) => writer.finish(),
+            Codec::Deflate(writer) => writer.finish(),
+            Codec::Inflate(writer) => writer.finish(),
+            Codec::BrotliCompress(writer) => Ok(writer.into_inner()),
+            Codec::BrotliDecompress(writer) => writer.into_inner()
+                .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of file")),
+        }
+    }
+}
+
+// A whole buffer at once, what the sync and callback functions do
+pub fn transform(format: Format, options: CodecOptions, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut codec = Codec::new(format, options);
+    let mut output = codec.write(data)?;
+    output.extend(codec.finish()?);
+    Ok(output)
+}
+
+// zlib.createGzip() and friends: written chunks come out as 'data' on the same object.
+// The work is done in write() itself, the events still arrive through the event loop
+pub struct ZlibStream {
+    pub event_emitter: Rc<RefCell<EventEmitter>>,
+    // None once the stream ended, failed or was destroyed
+    codec: Option<Codec>,
+    tx: UnboundedSender<Operations>,
+}
+
+impl ZlibStream {
+    pub fn new(format: Format, options: CodecOptions, tx: UnboundedSender<Operations>) -> Self {
+        ZlibStream {
+            event_emitter: Rc::new(RefCell::new(EventEmitter::new())),
+            codec: Some(Codec::new(format, options)),
+            tx,
+        }
+    }
+
+    fn emit(&self, event: &'static str) {
+        let _ = self.tx.send(Operations::Stream(StreamEvent::Emit{ emitter: self.event_emitter.clone(), event }));
+    }
+
+    fn emit_data(&self, bytes: Vec<u8>) {
+        if !bytes.is_empty() {
+            let op = StreamEvent::Data{ emitter: self.event_emitter.clone(), chunk: Chunk::Bytes(bytes) };
+            let _ = self.tx.send(Operations::Stream(op));
+        }
+    }
+
+    fn callback(&self, callback: Option<v8::Global<v8::Function>>, error_message: Option<String>) {
+        if let Some(callback) = callback {
+            let _ = self.tx.send(Operations::Stream(StreamEvent::Callback{ callback, error_message }));
+        }
+    }
+
+    // Bad input ends the stream with 'error' and 'close'
+    fn fail(&mut self, error: io::Error, callback: Option<v8::Global<v8::Function>>) {
+        self.codec = None;
+        self.callback(callback, Some(error.to_string()));
+        let _ = self.tx.send(Operations::Stream(StreamEvent::Error{ emitter: self.event_emitter.clone(), error_message: error.to_string() }));
+        self.emit("close");
+    }
+
+    pub fn write(&mut self, data: Vec<u8>, callback: Option<v8::Global<v8::Function>>) -> Result<(), String> {
+        let Some(codec) = self.codec.as_mut() else {
+            return Err("write after end".to_string());
+        };
+        match codec.write(&data) {
+            Ok(output) => {
+                self.emit_data(output);
+                self.callback(callback, None);
+            }
+            Err(e) => self.fail(e, callback),
+        }
+        Ok(())
+    }
+
+    pub fn end(&mut self, data: Option<Vec<u8>>, callback: Option<v8::Global<v8::Function>>) {
+        if let Some(data) = data {
+            let _ = self.write(data, None);
+        }
+        let Some(codec) = self.codec.take() else {
+            return;
+        };
+        match codec.finish() {
+            Ok(output) => {
+                self.emit_data(output);
+                self.emit("finish");
+                self.emit("end");
+                self.callback(callback, None);
+                self.emit("close");
+            }
+            Err(e) => self.fail(e, callback),
+        }
+    }
+
+    pub fn destroy(&mut self) {
+        if self.codec.take().is_some() {
+            self.emit("close");
+        }
+    }
+}
+
+// Completion of the callback functions
+pub fn handle_zlib_operation(scope: &mut v8::HandleScope, operation: ZlibOperation) {
+    match operation {
+        ZlibOperation::Complete { callback, result } => {
+            let undefined = v8::undefined(scope).into();
+            let args: [v8::Local<v8::Value>; 2] = match result {
+                Ok(bytes) => [v8::null(scope).into(), bytes_to_uint8array(scope, bytes).into()],
+                Err(error_message) => [v8::String::new(scope, &error_message).unwrap().into(), undefined],
+            };
+            let callback_fn = callback.open(scope);
+            callback_fn.call(scope, undefined, &args);
+        }
+    }
+}
+
+// V8 Callbacks
+fn get_format(args: &v8::FunctionCallbackArguments) -> Format {
+    let index = v8::Local::<v8::Integer>::try_from(args.data().unwrap()).unwrap().value();
+    FORMATS[index as usize].2
+}
+
+fn get_zlib_stream<'a>(scope: &mut v8::HandleScope, stream_obj: v8::Local<v8::Object>) -> &'a mut ZlibStream {
+    let internal_field = stream_obj.get_internal_field(scope, 0).unwrap();
+    let external_stream = v8::Local::<v8::External>::try_from(internal_field).unwrap();
+    unsafe { &mut *(external_stream.value() as *mut ZlibStream) }
+}
+
+// zlib.gzipSync(data, [options]) and friends, throws when the input can't be decoded
+fn zlib_sync_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let format = get_format(&args);
+    let options = match CodecOptions::from_js(scope, args.get(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            throw_error(scope, &e);
+            return;
+        }
+    };
+    let data = value_to_bytes(scope, args.get(0));
+
+    match transform(format, options, &data) {
+        Ok(output) => rv.set(bytes_to_uint8array(scope, output).into()),
+        Err(e) => throw_error(scope, &e.to_string()),
+    }
+}
+
+// zlib.gzip(data, [options], callback) and friends, the work runs off the main thread
+fn zlib_async_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let format = get_format(&args);
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok());
+    let Some(callback) = callback else {
+        throw_error(scope, "The \"callback\" argument must be of type function");
+        return;
+    };
+    let options = match args.get(1).is_function() {
+        true => Ok(CodecOptions::default()),
+        false => CodecOptions::from_js(scope, args.get(1)),
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            throw_error(scope, &e);
+            return;
+        }
+    };
+    let data = value_to_bytes(scope, args.get(0));
+
+    let callback = v8::Global::new(scope, callback);
+    let tx = unsafe { &*retrieve_tx(scope, "channel").unwrap() }.clone();
+    tokio::task::spawn_local(async move {
+        let result = tokio::task::spawn_blocking(move || transform(format, options, &data))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()));
+        let _ = tx.send(Operations::Zlib(ZlibOperation::Complete { callback, result }));
+    });
+}
+
+// stream.write(chunk, [encoding], [callback])
+fn zlib_stream_write_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_zlib_stream(scope, args.this());
+    let data = value_to_bytes(scope, args.get(0));
+    let callback = [args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    match stream.write(data, callback) {
+        Ok(()) => rv.set(v8::Boolean::new(scope, true).into()),
+        Err(e) => throw_error(scope, &e),
+    }
+}
+
+// stream.end([chunk], [encoding], [callback])
+fn zlib_stream_end_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let stream = get_zlib_stream(scope, args.this());
+    let data = match args.get(0).is_null_or_undefined() || args.get(0).is_function() {
+        true => None,
+        false => Some(value_to_bytes(scope, args.get(0))),
+    };
+    let callback = [args.get(0), args.get(1), args.get(2)].into_iter()
+        .find_map(|value| v8::Local::<v8::Function>::try_from(value).ok())
+        .map(|callback| v8::Global::new(scope, callback));
+
+    stream.end(data, callback);
+    rv.set(args.this().into());
+}
+
+fn zlib_stream_destroy_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    get_zlib_stream(scope, args.this()).destroy();
+    rv.set(args.this().into());
+}
+
+// zlib.createGzip([options]) and friends
+fn zlib_create_stream_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let format = get_format(&args);
+    let options = match CodecOptions::from_js(scope, args.get(0)) {
+        Ok(options) => options,
+        Err(e) => {
+            throw_error(scope, &e);
+            return;
+        }
+    };
+    let tx = unsafe { &*retrieve_tx(scope, "channel").unwrap() }.clone();
+    let stream = Box::new(ZlibStream::new(format, options, tx));
+
+    let stream_template = v8::ObjectTemplate::new(scope);
+    stream_template.set_internal_field_count(1); // Store the Rust ZlibStream struct internally
+    let stream_obj = stream_template.new_instance(scope).unwrap();
+
+    attach_event_emitter(scope, stream_obj, &stream.event_emitter);
+    set_function(scope, stream_obj, "write", zlib_stream_write_callback);
+    set_function(scope, stream_obj, "end", zlib_stream_end_callback);
+    set_function(scope, stream_obj, "destroy", zlib_stream_destroy_callback);
+    set_function(scope, stream_obj, "pipe", stream_pipe_callback);
+
+    let external_stream = v8::External::new(scope, Box::into_raw(stream) as *const _ as *mut c_void);
+    stream_obj.set_internal_field(0, external_stream.into());
+    rv.set(stream_obj.into());
+}
+
+fn set_format_function(
+    scope: &mut v8::HandleScope,
+    zlib_obj: v8::Local<v8::Object>,
+    name: &str,
+    index: usize,
+    callback: impl v8::MapFnTo<v8::FunctionCallback>
+) {
+    let format_index = v8::Integer::new(scope, index as i32);
+    let function = v8::Function::builder(callback)
+        .data(format_index.into())
+        .build(scope)
+        .unwrap();
+    let key = v8::String::new(scope, name).unwrap();
+    zlib_obj.set(scope, key.into(), function.into());
+}
+
+pub fn initialize_zlib(scope: &mut v8::ContextScope<'_, v8::HandleScope<'_>>) {
+    let zlib_obj = v8::Object::new(scope);
+
+    for (index, (name, class_name, _)) in FORMATS.iter().enumerate() {
+        set_format_function(scope, zlib_obj, &format!("{}Sync", name), index, zlib_sync_callback);
+        set_format_function(scope, zlib_obj, name, index, zlib_async_callback);
+        set_format_function(scope, zlib_obj, &format!("create{}", class_name), index, zlib_create_stream_callback);
+    }
+
+    let constants = v8::Object::new(scope);
+    for (name, value) in CONSTANTS {
+        let key = v8::String::new(scope, name).unwrap();
+        let value = v8::Integer::new(scope, value);
+        constants.set(scope, key.into(), value.into());
+    }
+    let constants_key = v8::String::new(scope, "constants").unwrap();
+    zlib_obj.set(scope, constants_key.into(), constants.into());
+
+    let global = scope.get_current_context().global(scope);
+    let zlib_key = v8::String::new(scope, "zlib").unwrap();
+    global.set(scope, zlib_key.into(), zlib_obj.into());
+}